@@ -0,0 +1,15 @@
+#version 140
+
+in vec2 v_tex_coords;
+in vec4 v_tint;
+out vec4 color;
+
+uniform sampler2D tex;
+uniform bool multiply;
+
+void main() {
+    color = texture(tex, v_tex_coords) * v_tint;
+    if (multiply) {
+        color = vec4(mix(vec3(1.0), color.rgb, color.a), color.a);
+    }
+}
@@ -2,11 +2,14 @@
 
 in vec2 position;
 in vec2 tex_coords;
+in vec4 tint;
 out vec2 v_tex_coords;
+out vec4 v_tint;
 
 uniform mat4 matrix;
 
 void main() {
 	v_tex_coords = tex_coords;
+	v_tint = tint;
 	gl_Position = matrix * vec4(position, 0.0, 1.0);
 }
\ No newline at end of file
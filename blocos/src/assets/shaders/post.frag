@@ -0,0 +1,79 @@
+#version 140
+
+in vec2 v_tex_coords;
+out vec4 color;
+
+// The frame drawn so far
+uniform sampler2D tex;
+// Size of the frame in pixels
+uniform vec2 resolution;
+// 0 crt, 1 bloom, 2 color grading, 3 vignette, 4 pixelate
+uniform int effect;
+// The parameters of the effect, in the order of the fields of `PostEffect`
+uniform vec4 params;
+uniform vec4 tint;
+
+vec4 crt() {
+    // Bends the coordinates away from the center, like the glass of a tube
+    vec2 centered = v_tex_coords * 2.0 - 1.0;
+    centered *= 1.0 + params.y * dot(centered.yx, centered.yx);
+    vec2 uv = centered * 0.5 + 0.5;
+    if (uv.x < 0.0 || uv.x > 1.0 || uv.y < 0.0 || uv.y > 1.0) {
+        return vec4(0.0, 0.0, 0.0, 1.0);
+    }
+    vec4 sampled = texture(tex, uv);
+    float scanline = 1.0 - params.x * (0.5 + 0.5 * sin(uv.y * resolution.y * 3.14159));
+    return vec4(sampled.rgb * scanline, sampled.a);
+}
+
+vec4 bloom() {
+    vec4 sampled = texture(tex, v_tex_coords);
+    vec3 glow = vec3(0.0);
+    float total = 0.0;
+    for (int x = -3; x <= 3; x++) {
+        for (int y = -3; y <= 3; y++) {
+            vec2 offset = vec2(x, y) / 3.0;
+            float weight = exp(-dot(offset, offset) * 2.0);
+            vec3 around = texture(tex, v_tex_coords + offset * params.z / resolution).rgb;
+            float brightness = max(around.r, max(around.g, around.b));
+            glow += around * step(params.x, brightness) * weight;
+            total += weight;
+        }
+    }
+    return vec4(sampled.rgb + glow / total * params.y, sampled.a);
+}
+
+vec4 color_grading() {
+    vec4 sampled = texture(tex, v_tex_coords);
+    vec3 graded = sampled.rgb * params.x;
+    graded = (graded - 0.5) * params.y + 0.5;
+    float luma = dot(graded, vec3(0.2126, 0.7152, 0.0722));
+    graded = mix(vec3(luma), graded, params.z) * tint.rgb;
+    return vec4(clamp(graded, 0.0, 1.0), sampled.a);
+}
+
+vec4 vignette() {
+    vec4 sampled = texture(tex, v_tex_coords);
+    float distance = length(v_tex_coords - 0.5) * 1.41421;
+    float shade = smoothstep(params.x, params.x + params.y, distance) * params.z;
+    return vec4(sampled.rgb * (1.0 - shade), sampled.a);
+}
+
+vec4 pixelate() {
+    vec2 cells = resolution / max(params.x, 1.0);
+    return texture(tex, (floor(v_tex_coords * cells) + 0.5) / cells);
+}
+
+void main() {
+    if (effect == 0) {
+        color = crt();
+    } else if (effect == 1) {
+        color = bloom();
+    } else if (effect == 2) {
+        color = color_grading();
+    } else if (effect == 3) {
+        color = vignette();
+    } else {
+        color = pixelate();
+    }
+}
@@ -0,0 +1,13 @@
+#version 140
+
+in vec4 vertex_color;
+out vec4 color;
+
+uniform bool multiply;
+
+void main() {
+    color = vertex_color;
+    if (multiply) {
+        color = vec4(mix(vec3(1.0), color.rgb, color.a), color.a);
+    }
+}
@@ -1,9 +1,13 @@
 #version 140
 uniform sampler2D tex;
+uniform bool multiply;
 in vec2 v_tex_coords;
 in vec4 v_colour;
 out vec4 f_colour;
 
 void main() {
 	f_colour = v_colour * vec4(1.0, 1.0, 1.0, texture(tex, v_tex_coords).r);
-}
\ No newline at end of file
+    if (multiply) {
+        f_colour = vec4(mix(vec3(1.0), f_colour.rgb, f_colour.a), f_colour.a);
+    }
+}
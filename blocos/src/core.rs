@@ -2,3 +2,4 @@
 #[macro_use]
 pub mod vector2;
 pub mod rgb;
+pub mod timestep;
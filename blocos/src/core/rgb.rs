@@ -1,5 +1,7 @@
 //! This module has a struct to encode a color in `rgb`
-#[derive(Copy,Clone, Debug)]
+use std::{fmt, str::FromStr};
+
+#[derive(Copy,Clone, Debug, PartialEq, Eq)]
 /// Representation of a color in RGB
 pub struct Rgb {
 	/// Red component
@@ -25,4 +27,77 @@ impl Rgb {
 	pub const fn new(r:u8,g:u8, b:u8) -> Rgb {
 		Rgb {r ,g , b}
 	}
-}
\ No newline at end of file
+}
+
+/// Formats the color in hexadecimal, like `#ff8000`
+impl fmt::Display for Rgb {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+	}
+}
+
+/// Error when parsing a [Rgb] from a string that is not like `#ff8000`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseRgbError;
+
+impl FromStr for Rgb {
+	type Err = ParseRgbError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let hex = s.strip_prefix('#').ok_or(ParseRgbError)?;
+		if hex.len() != 6 || !hex.is_ascii() {
+			return Err(ParseRgbError);
+		}
+		let component = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| ParseRgbError);
+		Ok(Rgb::new(component(0)?, component(2)?, component(4)?))
+	}
+}
+#[derive(Copy,Clone, Debug, PartialEq, Eq)]
+/// Representation of a color in RGB with an alpha channel for transparency
+pub struct Rgba {
+	/// Red component
+	pub r: u8,
+	/// Green component
+	pub g: u8,
+	/// Blue component
+	pub b: u8,
+	/// Alpha component, 0 is transparent and 255 is opaque
+	pub a: u8
+}
+
+impl Rgba {
+	/// Representation of black in RGBA
+	pub const BLACK : Rgba = Rgba::new(0,0,0,255);
+	/// Representation of white in RGBA
+	pub const WHITE : Rgba = Rgba::new(255,255,255,255);
+	/// A color that does not show
+	pub const TRANSPARENT : Rgba = Rgba::new(0,0,0,0);
+	/// Create a new [Rgba]
+	pub const fn new(r:u8,g:u8, b:u8, a:u8) -> Rgba {
+		Rgba {r ,g , b, a}
+	}
+	/// The components as floats from 0 to 1, in the order red, green, blue and alpha
+	pub fn to_array(self) -> [f32; 4] {
+		[self.r, self.g, self.b, self.a].map(|c| c as f32 / 255.)
+	}
+	/// The color `t` of the way from this one to `other`, `t` going from 0 to 1
+	pub fn lerp(self, other: Rgba, t: f32) -> Rgba {
+		let t = t.clamp(0., 1.);
+		let mix = |from: u8, to: u8| (from as f32 + (to as f32 - from as f32) * t).round() as u8;
+		Rgba::new(mix(self.r, other.r), mix(self.g, other.g), mix(self.b, other.b), mix(self.a, other.a))
+	}
+}
+
+impl Rgb {
+	/// This color with an alpha, 0 is transparent and 255 is opaque
+	pub const fn with_alpha(self, a: u8) -> Rgba {
+		Rgba::new(self.r, self.g, self.b, a)
+	}
+}
+
+/// An opaque [Rgba] of the color
+impl From<Rgb> for Rgba {
+	fn from(rgb: Rgb) -> Self {
+		rgb.with_alpha(255)
+	}
+}
@@ -0,0 +1,141 @@
+//! This module has the fixed timestep that separates the simulation rate from the rendering rate
+use std::time::{Duration, Instant};
+
+/// Number of simulation ticks per second
+pub const TICKS_PER_SECOND: u32 = 60;
+/// Duration of a simulation tick in microseconds
+pub const TICK_MICROS: u128 = 1_000_000 / TICKS_PER_SECOND as u128;
+/// Longest time a single frame can add to the simulation
+///
+/// After a stall (dragging the window, a breakpoint, etc) the simulation skips the rest
+/// instead of running hundreds of ticks to catch up, which would make the next frame even slower
+const MAX_FRAME_TIME: Duration = Duration::from_millis(250);
+
+/// Accumulates the real time between frames and converts it in a whole number of fixed ticks
+#[derive(Debug)]
+pub struct FixedTimestep {
+    /// Duration of a tick
+    tick: Duration,
+    /// Time not simulated yet
+    accumulator: Duration,
+    /// Instant of the last call to [FixedTimestep::advance]
+    last: Instant,
+}
+
+impl Default for FixedTimestep {
+    /// A timestep with [TICKS_PER_SECOND] ticks per second, starting now
+    fn default() -> Self {
+        FixedTimestep {
+            tick: Duration::from_micros(TICK_MICROS as u64),
+            accumulator: Duration::ZERO,
+            last: Instant::now(),
+        }
+    }
+}
+
+impl FixedTimestep {
+    /// Create a timestep with [TICKS_PER_SECOND] ticks per second, starting now
+    pub fn new() -> FixedTimestep {
+        FixedTimestep::default()
+    }
+
+    /// Adds the time since the last call and returns how many ticks must be simulated
+    pub fn advance(&mut self, now: Instant) -> u32 {
+        let elapsed = now.saturating_duration_since(self.last).min(MAX_FRAME_TIME);
+        self.last = now;
+        self.accumulator += elapsed;
+        let mut ticks = 0;
+        while self.accumulator >= self.tick {
+            self.accumulator -= self.tick;
+            ticks += 1;
+        }
+        ticks
+    }
+
+    /// How far the real time is between the last tick and the next one, from 0 to 1
+    ///
+    /// Used to interpolate the drawing between the previous and the current state
+    pub fn alpha(&self) -> f32 {
+        self.accumulator.as_secs_f32() / self.tick.as_secs_f32()
+    }
+}
+
+/// How the rate of frames drawn is limited
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameLimit {
+    /// Waits the vertical synchronization of the monitor when presenting each frame
+    Vsync,
+    /// Draws at most this number of frames per second
+    Fps(u32),
+    /// Draws as fast as possible
+    Unlimited,
+}
+
+impl FrameLimit {
+    /// Reads the limit from the command line arguments `--vsync`, `--fps <n>` or `--fps 0` for unlimited
+    pub fn from_args<I: IntoIterator<Item = String>>(args: I) -> Option<FrameLimit> {
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--vsync" => return Some(FrameLimit::Vsync),
+                "--fps" => {
+                    return args.next().and_then(|fps| fps.parse().ok()).map(|fps| match fps {
+                        0 => FrameLimit::Unlimited,
+                        fps => FrameLimit::Fps(fps),
+                    })
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+
+    /// Minimum time between two frames, if the limit is a number of frames per second
+    pub fn frame_time(self) -> Option<Duration> {
+        match self {
+            FrameLimit::Fps(fps) => Some(Duration::from_secs(1) / fps),
+            FrameLimit::Vsync | FrameLimit::Unlimited => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A timestep and the instant it started
+    fn started() -> (FixedTimestep, Instant) {
+        let timestep = FixedTimestep::new();
+        let start = timestep.last;
+        (timestep, start)
+    }
+
+    #[test]
+    fn counts_the_whole_ticks_and_keeps_the_rest() {
+        let (mut timestep, start) = started();
+        let tick = Duration::from_micros(TICK_MICROS as u64);
+        assert_eq!(timestep.advance(start + tick / 2), 0);
+        assert!((timestep.alpha() - 0.5).abs() < 1e-3);
+        assert_eq!(timestep.advance(start + tick * 3), 3);
+        assert!(timestep.alpha() < 1e-3);
+        assert_eq!(timestep.advance(start + tick * 3), 0);
+    }
+
+    #[test]
+    fn a_stall_adds_at_most_the_longest_frame() {
+        let (mut timestep, start) = started();
+        let ticks = timestep.advance(start + Duration::from_secs(10));
+        let tick = Duration::from_micros(TICK_MICROS as u64);
+        assert_eq!(ticks, (MAX_FRAME_TIME.as_micros() / tick.as_micros()) as u32);
+        assert!(timestep.alpha() < 1.);
+    }
+
+    #[test]
+    fn time_going_back_adds_nothing() {
+        let (mut timestep, start) = started();
+        timestep.advance(start + Duration::from_millis(5));
+        let alpha = timestep.alpha();
+        assert_eq!(timestep.advance(start), 0);
+        assert_eq!(timestep.alpha(), alpha);
+    }
+}
@@ -69,7 +69,6 @@ impl<T: Field> Vector2<T>{
    /// assert_eq!(v.x, 1.0); 
    /// assert_eq!(v.y, 2.0); 
    /// ```
-   
     #[inline]
     pub fn new<K: Into<T>>( x: K, y: K) -> Self {
         Vector2 {
@@ -123,6 +122,31 @@ impl Vec2 {
    pub const RIGHT: Vec2 = Vec2 { x: 1.0, y: 0.0 };
    /// The zero vector.
    pub const ZERO: Vec2 = Vec2 { x: 0., y: 0. };
+
+   /// The length of the vector.
+   pub fn length(self) -> f32 {
+       self.x.hypot(self.y)
+   }
+
+   /// The vector with the same direction and length 1, or the zero vector if it has no direction.
+   pub fn normalized(self) -> Vec2 {
+       let length = self.length();
+       if length == 0. {
+           Vec2::ZERO
+       } else {
+           self / length
+       }
+   }
+
+   /// The vector rotated by 90 degrees counterclockwise.
+   pub fn perpendicular(self) -> Vec2 {
+       Vec2 { x: -self.y, y: self.x }
+   }
+
+   /// The z component of the cross product, positive if `other` is counterclockwise from this vector.
+   pub fn cross(self, other: Vec2) -> f32 {
+       self.x * other.y - self.y * other.x
+   }
 }
 /// # Examples
 ///
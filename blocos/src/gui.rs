@@ -1,9 +1,26 @@
 //! Where access and manipulation to visual interface components is available, such as camera, transformations, window manipulations, etc.
 
+pub mod animation;
+pub mod assets;
+pub mod atlas;
+pub mod backend;
+pub mod capture;
+pub mod debug;
+mod error;
+mod camera;
+pub mod fonts;
+mod headless;
 pub mod interface;
+pub mod particles;
+mod render_target;
+pub mod snapshot;
 pub mod systems;
 mod transform;
+pub mod window;
 
+pub use camera::*;
+pub use error::*;
+pub use render_target::*;
 pub use transform::*;
 
 /// Import the vec2 and vector2::Vec2 modules.
@@ -49,6 +66,29 @@ pub struct Rect {
 }
 
 impl Rect {
+    /// The square from (0, 0) to (1, 1), used as the coordinates of a whole texture.
+    pub const UNIT: Rect = Rect {
+        size: Vec2 { x: 1., y: 1. },
+        center: Vec2 { x: 0.5, y: 0.5 },
+    };
+
+    /// Creates the rectangle with `min` as the bottom left corner and `max` as the top right one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main(){
+    /// let rect = Rect::from_corners(vec2!(0., 0.), vec2!(10., 20.));
+    /// assert_eq!(rect.center, vec2!(5., 10.));
+    /// # }
+    ///```
+    pub fn from_corners(min: Vec2, max: Vec2) -> Rect {
+        Rect {
+            size: max - min,
+            center: (min + max) / 2.,
+        }
+    }
+
     /// Returns the left edge of the rectangle.
     ///
     /// # Examples
@@ -0,0 +1,339 @@
+//! Animated sprites, sequences of frames of a sprite sheet played over time
+//!
+//! The animations advance by the microseconds of each update, the same `delta_t` the scenes receive,
+//! so they follow the fixed timestep of the game
+use std::{fmt, rc::Rc};
+
+use super::{
+    atlas::{Sprite, SpriteSheet},
+    systems::{ImageObject, Layer},
+    Rect,
+};
+
+/// How an animation goes on after its last frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PlayMode {
+    /// Starts again from the first frame
+    #[default]
+    Loop,
+    /// Goes back to the first frame and then forward again
+    PingPong,
+    /// Stops on the last frame
+    Once,
+}
+
+/// A frame of an animation
+#[derive(Debug, Clone)]
+pub struct AnimationFrame {
+    /// What is drawn
+    pub sprite: Sprite,
+    /// How long the frame is shown, in microseconds
+    pub duration: u128,
+}
+
+/// A sequence of frames, shared by the [AnimatedSprite]s that play it
+#[derive(Debug, Clone)]
+pub struct Animation {
+    /// Name of the animation, given to the callback when it finishes
+    pub name: String,
+    /// The frames in order
+    pub frames: Vec<AnimationFrame>,
+    /// How the animation goes on after its last frame
+    pub mode: PlayMode,
+}
+
+impl Animation {
+    /// An animation of frames of a sprite sheet, each one shown for `duration` microseconds
+    ///
+    /// `None` if the sheet has no frame with one of the names
+    pub fn from_sheet(
+        name: &str,
+        sheet: &SpriteSheet,
+        frames: &[&str],
+        duration: u128,
+        mode: PlayMode,
+    ) -> Option<Animation> {
+        let frames = frames
+            .iter()
+            .map(|frame| sheet.get(frame).map(|sprite| AnimationFrame { sprite, duration }))
+            .collect::<Option<Vec<_>>>()?;
+        Some(Animation {
+            name: name.to_owned(),
+            frames,
+            mode,
+        })
+    }
+
+    /// Duration of a cycle of the animation in microseconds, forward and back for [PlayMode::PingPong]
+    pub fn duration(&self) -> u128 {
+        let forward: u128 = self.frames.iter().map(|frame| frame.duration).sum();
+        match self.mode {
+            PlayMode::PingPong if self.frames.len() > 1 => {
+                let ends = self.frames[0].duration + self.frames[self.frames.len() - 1].duration;
+                2 * forward - ends
+            }
+            _ => forward,
+        }
+    }
+}
+
+/// Called with the name of the animation when an [AnimatedSprite] finishes it
+pub type FinishCallback = Box<dyn FnMut(&str)>;
+
+/// Plays an [Animation], keeping the frame shown
+///
+/// # Examples
+///
+/// ```
+/// let walk = Rc::new(Animation::from_sheet("walk", &sheet, &["walk1", "walk2", "walk3"], 100_000, PlayMode::Loop)?);
+/// let mut sprite = AnimatedSprite::new(walk);
+/// sprite.update(delta_t);
+/// if let Some(image) = sprite.image(region, layer::PIECE) {
+///     canvas.draw(image);
+/// }
+/// ```
+pub struct AnimatedSprite {
+    animation: Rc<Animation>,
+    /// How fast the animation plays, 1 is the duration of the frames and 2 is twice as fast
+    ///
+    /// 0 or less holds the frame shown, the animations do not play backwards
+    pub speed: f32,
+    /// Whether the animation is advancing
+    pub playing: bool,
+    /// Index of the frame shown
+    frame: usize,
+    /// Microseconds since the start of the current cycle
+    elapsed: f64,
+    /// Whether an animation [PlayMode::Once] reached its end
+    finished: bool,
+    on_finish: Option<FinishCallback>,
+}
+
+impl fmt::Debug for AnimatedSprite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AnimatedSprite")
+            .field("animation", &self.animation.name)
+            .field("speed", &self.speed)
+            .field("playing", &self.playing)
+            .field("frame", &self.frame)
+            .field("finished", &self.finished)
+            .finish_non_exhaustive()
+    }
+}
+
+impl AnimatedSprite {
+    /// Create a sprite playing an animation from its first frame
+    pub fn new(animation: Rc<Animation>) -> AnimatedSprite {
+        AnimatedSprite {
+            animation,
+            speed: 1.,
+            playing: true,
+            frame: 0,
+            elapsed: 0.,
+            finished: false,
+            on_finish: None,
+        }
+    }
+
+    /// Calls `callback` when the animation finishes: once at the end of [PlayMode::Once],
+    /// and at the end of every cycle of [PlayMode::Loop] and [PlayMode::PingPong]
+    pub fn on_finish<F: FnMut(&str) + 'static>(&mut self, callback: F) {
+        self.on_finish = Some(Box::new(callback));
+    }
+
+    /// Plays another animation from its first frame, keeping the speed and the callback
+    ///
+    /// Does nothing if the animation is already playing, so it can be called every update
+    pub fn play(&mut self, animation: &Rc<Animation>) {
+        if !Rc::ptr_eq(&self.animation, animation) {
+            self.animation = animation.clone();
+            self.restart();
+        }
+    }
+
+    /// Goes back to the first frame
+    pub fn restart(&mut self) {
+        self.frame = 0;
+        self.elapsed = 0.;
+        self.finished = false;
+        self.playing = true;
+    }
+
+    /// The animation being played
+    pub fn animation(&self) -> &Rc<Animation> {
+        &self.animation
+    }
+
+    /// Index of the frame shown
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    /// Whether an animation [PlayMode::Once] reached its last frame
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// The sprite of the frame shown, `None` if the animation has no frames
+    pub fn sprite(&self) -> Option<&Sprite> {
+        self.animation.frames.get(self.frame).map(|frame| &frame.sprite)
+    }
+
+    /// Creates an image object drawing the frame shown in `region`, see [Sprite::image]
+    ///
+    /// `None` if the animation has no frames
+    pub fn image(&self, region: Rect, layer: Layer) -> Option<ImageObject> {
+        self.sprite().map(|sprite| sprite.image(region, layer))
+    }
+
+    /// Advances the animation by `delta_t` microseconds, scaled by the speed
+    ///
+    /// The frame is found from the time in the cycle, so a long update or short frames cost the same.
+    /// The callback is called once when one or more cycles end in the same update
+    pub fn update(&mut self, delta_t: u128) {
+        let frames = self.animation.frames.len();
+        if !self.playing || self.finished || frames == 0 || self.speed <= 0. {
+            return;
+        }
+        self.elapsed += delta_t as f64 * self.speed as f64;
+        let cycle: f64 = self.cycle_frames().map(|frame| self.duration(frame)).sum();
+        if self.elapsed >= cycle {
+            if self.animation.mode == PlayMode::Once {
+                self.finished = true;
+                self.elapsed = 0.;
+                self.frame = frames - 1;
+                self.call_finish();
+                return;
+            }
+            self.elapsed %= cycle;
+            self.call_finish();
+        }
+        self.frame = self.frame_at(self.elapsed);
+    }
+
+    /// Indices of the frames of a cycle in the order they are shown,
+    /// forward and back without repeating the ends for [PlayMode::PingPong]
+    fn cycle_frames(&self) -> impl Iterator<Item = usize> {
+        let frames = self.animation.frames.len();
+        let back = match self.animation.mode {
+            PlayMode::PingPong => 1..frames.saturating_sub(1),
+            PlayMode::Loop | PlayMode::Once => 0..0,
+        };
+        (0..frames).chain(back.rev())
+    }
+
+    /// Microseconds a frame is shown, at least one so every cycle takes some time
+    fn duration(&self, frame: usize) -> f64 {
+        self.animation.frames[frame].duration.max(1) as f64
+    }
+
+    /// The frame shown `elapsed` microseconds after the start of a cycle
+    fn frame_at(&self, mut elapsed: f64) -> usize {
+        let mut shown = 0;
+        for frame in self.cycle_frames() {
+            shown = frame;
+            let duration = self.duration(frame);
+            if elapsed < duration {
+                break;
+            }
+            elapsed -= duration;
+        }
+        shown
+    }
+
+    /// Calls the callback with the name of the animation
+    fn call_finish(&mut self) {
+        let animation = self.animation.clone();
+        if let Some(callback) = &mut self.on_finish {
+            callback(&animation.name);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use glium::texture::{MipmapsOption, SrgbFormat, SrgbTexture2d};
+
+    use super::*;
+    use crate::gui::backend::Backend;
+
+    /// An animation of `frames` frames of 100 microseconds
+    fn animation(mode: PlayMode, frames: usize) -> Rc<Animation> {
+        let backend = Backend::headless(1, 1).unwrap();
+        let texture =
+            SrgbTexture2d::empty_with_format(&backend, SrgbFormat::U8U8U8U8, MipmapsOption::NoMipmap, 1, 1).unwrap();
+        let sprite = Sprite { texture: Rc::new(texture), source: Rect::UNIT };
+        Rc::new(Animation {
+            name: "test".to_owned(),
+            frames: vec![AnimationFrame { sprite, duration: 100 }; frames],
+            mode,
+        })
+    }
+
+    /// The frames shown after each of `updates` updates of `delta_t` microseconds
+    fn frames(sprite: &mut AnimatedSprite, updates: usize, delta_t: u128) -> Vec<usize> {
+        (0..updates)
+            .map(|_| {
+                sprite.update(delta_t);
+                sprite.frame()
+            })
+            .collect()
+    }
+
+    /// Counts the calls of the callback of `sprite`
+    fn count_finishes(sprite: &mut AnimatedSprite) -> Rc<Cell<u32>> {
+        let count = Rc::new(Cell::new(0));
+        let counter = count.clone();
+        sprite.on_finish(move |_| counter.set(counter.get() + 1));
+        count
+    }
+
+    #[test]
+    fn ping_pong_does_not_repeat_the_ends() {
+        let mut sprite = AnimatedSprite::new(animation(PlayMode::PingPong, 4));
+        let finishes = count_finishes(&mut sprite);
+        assert_eq!(frames(&mut sprite, 8, 100), [1, 2, 3, 2, 1, 0, 1, 2]);
+        assert_eq!(finishes.get(), 1);
+        assert_eq!(sprite.animation().duration(), 600);
+    }
+
+    #[test]
+    fn once_stops_on_the_last_frame_and_finishes_once() {
+        let mut sprite = AnimatedSprite::new(animation(PlayMode::Once, 3));
+        let finishes = count_finishes(&mut sprite);
+        assert_eq!(frames(&mut sprite, 5, 100), [1, 2, 2, 2, 2]);
+        assert!(sprite.is_finished());
+        assert_eq!(finishes.get(), 1);
+        sprite.update(10_000);
+        assert_eq!((sprite.frame(), finishes.get()), (2, 1));
+    }
+
+    #[test]
+    fn a_long_update_skips_frames_and_cycles() {
+        let mut sprite = AnimatedSprite::new(animation(PlayMode::Loop, 3));
+        let finishes = count_finishes(&mut sprite);
+        sprite.update(250);
+        assert_eq!((sprite.frame(), finishes.get()), (2, 0));
+        // 1250 microseconds are four cycles and 50 microseconds, the callback is called once for all of them
+        sprite.update(1000);
+        assert_eq!((sprite.frame(), finishes.get()), (0, 1));
+
+        let mut sprite = AnimatedSprite::new(animation(PlayMode::Once, 3));
+        sprite.update(1000);
+        assert_eq!((sprite.frame(), sprite.is_finished()), (2, true));
+    }
+
+    #[test]
+    fn a_speed_of_zero_or_less_holds_the_frame() {
+        let mut sprite = AnimatedSprite::new(animation(PlayMode::Loop, 3));
+        sprite.update(100);
+        for speed in [0., -1.] {
+            sprite.speed = speed;
+            assert_eq!(frames(&mut sprite, 3, 100), [1, 1, 1]);
+        }
+        sprite.speed = 2.;
+        assert_eq!(frames(&mut sprite, 2, 50), [2, 0]);
+    }
+}
@@ -0,0 +1,320 @@
+//! Assets loaded at runtime from a directory, with the versions embedded in the executable as fallback
+//!
+//! In debug builds the files are watched, and the textures and shaders that change are reloaded without restarting.
+//! The images and texts read to build something else, like an atlas, are reported so it can be built again
+use std::{
+    cell::RefCell,
+    fs,
+    io::Cursor,
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::{Duration, Instant, SystemTime},
+};
+
+use glium::texture::{RawImage2d, SrgbTexture2d};
+use image::RgbaImage;
+
+use super::{backend::Backend, GuiError};
+
+/// Environment variable with the directory of the assets, overriding [Assets::default_dir]
+pub const ASSETS_ENV: &str = "BLOCOS_ASSETS";
+
+/// Handle of a texture loaded by [Assets], whose texture can change when its file is reloaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextureHandle(usize);
+
+/// The source code of a shader program
+#[derive(Debug, Clone)]
+pub struct ShaderSource {
+    /// Source of the vertex shader
+    pub vertex: String,
+    /// Source of the fragment shader
+    pub fragment: String,
+}
+
+/// A shader embedded in the executable, loaded from `shaders/<name>.vert` and `shaders/<name>.frag` at runtime
+#[derive(Debug, Clone, Copy)]
+pub struct EmbeddedShader {
+    /// Name of the files of the shader
+    pub name: &'static str,
+    /// Embedded source of the vertex shader
+    pub vertex: &'static str,
+    /// Embedded source of the fragment shader
+    pub fragment: &'static str,
+}
+
+impl EmbeddedShader {
+    /// Paths of the vertex and fragment shaders in the assets
+    fn paths(&self, dir: &Path) -> [PathBuf; 2] {
+        ["vert", "frag"].map(|extension| dir.join("shaders").join(format!("{}.{extension}", self.name)))
+    }
+}
+
+struct TextureAsset {
+    path: PathBuf,
+    texture: Rc<SrgbTexture2d>,
+    /// Modification time of the file when it was loaded, `None` if the fallback was used
+    modified: Option<SystemTime>,
+}
+
+/// A file read with [Assets::image] or [Assets::text], watched so what was built from it can be built again
+struct FileAsset {
+    /// Path relative to the directory of the assets
+    path: String,
+    /// Modification time of the file when it was read, `None` if it was missing
+    modified: Option<SystemTime>,
+}
+
+/// What changed in the files since the last [Assets::poll]
+#[derive(Default)]
+pub struct Changes {
+    /// The shaders whose files changed, with their new source
+    pub shaders: Vec<(&'static str, ShaderSource)>,
+    /// The files read with [Assets::image] or [Assets::text] that changed, relative to the directory of the assets
+    pub files: Vec<String>,
+}
+
+struct ShaderAsset {
+    shader: EmbeddedShader,
+    /// Modification time of the vertex and fragment files when they were loaded
+    modified: [Option<SystemTime>; 2],
+}
+
+/// Loads the textures, fonts and shaders of the game from a directory
+pub struct Assets {
+    dir: PathBuf,
+    textures: RefCell<Vec<TextureAsset>>,
+    shaders: Vec<ShaderAsset>,
+    files: RefCell<Vec<FileAsset>>,
+    /// Whether the files are watched for changes, only in debug builds
+    watch: bool,
+    last_poll: Instant,
+}
+
+impl Assets {
+    /// Minimum time between two checks of the files
+    const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+    /// Create the assets loaded from `dir`
+    pub fn new(dir: PathBuf) -> Assets {
+        Assets::watching(dir, cfg!(debug_assertions))
+    }
+
+    /// Create the assets loaded from `dir`, watching the files whatever the build
+    fn watching(dir: PathBuf, watch: bool) -> Assets {
+        Assets {
+            dir,
+            textures: RefCell::new(Vec::new()),
+            shaders: Vec::new(),
+            files: RefCell::new(Vec::new()),
+            watch,
+            last_poll: Instant::now(),
+        }
+    }
+
+    /// The directory in [ASSETS_ENV], or else the assets of the source code in debug builds and `assets` in release builds
+    pub fn default_dir() -> PathBuf {
+        if let Some(dir) = std::env::var_os(ASSETS_ENV) {
+            return dir.into();
+        }
+        if cfg!(debug_assertions) {
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("src").join("assets")
+        } else {
+            PathBuf::from("assets")
+        }
+    }
+
+    /// The directory where the assets are loaded from
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Loads the image in `path`, relative to the directory of the assets, as a texture
+    ///
+    /// `fallback` is the embedded image, used when the file is missing or invalid.
+    /// Fails if the embedded image cannot be uploaded either
+    pub fn load_texture(&self, facade: &Backend, path: &str, fallback: &'static [u8]) -> Result<TextureHandle, GuiError> {
+        let path = self.dir.join(path);
+        let (texture, modified) = match fs::read(&path) {
+            Ok(bytes) => match create_texture(facade, &bytes) {
+                Ok(texture) => (texture, modified(&path)),
+                Err(err) => {
+                    eprintln!("could not load {}, using the embedded version: {err}", path.display());
+                    (create_texture(facade, fallback)?, None)
+                }
+            },
+            Err(_) => (create_texture(facade, fallback)?, None),
+        };
+        let mut textures = self.textures.borrow_mut();
+        textures.push(TextureAsset {
+            path,
+            texture: Rc::new(texture),
+            modified,
+        });
+        Ok(TextureHandle(textures.len() - 1))
+    }
+
+    /// Decodes the image in `path`, relative to the directory of the assets, to be packed in an atlas
+    ///
+    /// `fallback` is the embedded image, used when the file is missing or invalid.
+    /// Fails if the embedded image cannot be decoded either.
+    /// The file is watched, and returned by [Assets::poll] when it changes
+    pub fn image(&self, path: &str, fallback: &'static [u8]) -> Result<RgbaImage, GuiError> {
+        self.watch_file(path);
+        let path = self.dir.join(path);
+        match fs::read(&path) {
+            Ok(bytes) => decode(&bytes).or_else(|err| {
+                eprintln!("could not load {}, using the embedded version: {err}", path.display());
+                decode(fallback)
+            }),
+            Err(_) => decode(fallback),
+        }
+    }
+
+    /// The current texture of a handle
+    pub fn texture(&self, handle: TextureHandle) -> Rc<SrgbTexture2d> {
+        self.textures.borrow()[handle.0].texture.clone()
+    }
+
+    /// Reads the font in `path`, relative to the directory of the assets, or returns `fallback` if it is missing
+    pub fn font(&self, path: &str, fallback: &'static [u8]) -> Vec<u8> {
+        self.read(path).unwrap_or_else(|| fallback.to_vec())
+    }
+
+    /// Reads the text file in `path`, relative to the directory of the assets, or returns `fallback` if it is missing
+    /// or is not UTF-8
+    ///
+    /// The file is watched, and returned by [Assets::poll] when it changes
+    pub fn text(&self, path: &str, fallback: &'static str) -> String {
+        self.watch_file(path);
+        self.read(path)
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+            .unwrap_or_else(|| fallback.to_owned())
+    }
+
+    /// Keeps the modification time of a file read to build something else, to report when it changes
+    fn watch_file(&self, path: &str) {
+        let modified = modified(&self.dir.join(path));
+        let mut files = self.files.borrow_mut();
+        match files.iter_mut().find(|file| file.path == path) {
+            Some(file) => file.modified = modified,
+            None => files.push(FileAsset { path: path.to_owned(), modified }),
+        }
+    }
+
+    /// Reads the file in `path`, relative to the directory of the assets, `None` if it is missing
+    pub fn read(&self, path: &str) -> Option<Vec<u8>> {
+        fs::read(self.dir.join(path)).ok()
+    }
+
+    /// Reads the source of a shader, using the embedded source of the files that are missing
+    ///
+    /// The shader is watched, and returned by [Assets::poll] when its files change
+    pub fn shader(&mut self, shader: EmbeddedShader) -> ShaderSource {
+        let paths = shader.paths(&self.dir);
+        self.shaders.push(ShaderAsset {
+            shader,
+            modified: paths.each_ref().map(|path| modified(path)),
+        });
+        read_shader(&paths, shader)
+    }
+
+    /// Reloads the textures whose files changed and returns the shaders and the other files that changed
+    ///
+    /// Does nothing in release builds, or if it was called less than [Assets::POLL_INTERVAL] ago
+    pub fn poll(&mut self, facade: &Backend) -> Changes {
+        if !self.watch || self.last_poll.elapsed() < Self::POLL_INTERVAL {
+            return Changes::default();
+        }
+        self.poll_now(facade)
+    }
+
+    /// Reloads what changed since the last poll, however recent it was
+    fn poll_now(&mut self, facade: &Backend) -> Changes {
+        self.last_poll = Instant::now();
+
+        for asset in self.textures.get_mut() {
+            let current = modified(&asset.path);
+            if current.is_none() || current == asset.modified {
+                continue;
+            }
+            asset.modified = current;
+            let texture = fs::read(&asset.path)
+                .map_err(|err| GuiError::Texture(err.into()))
+                .and_then(|bytes| create_texture(facade, &bytes));
+            match texture {
+                Ok(texture) => {
+                    asset.texture = Rc::new(texture);
+                    println!("reloaded {}", asset.path.display());
+                }
+                Err(err) => eprintln!("could not reload {}: {err}", asset.path.display()),
+            }
+        }
+
+        let mut changes = Changes::default();
+        for asset in &mut self.shaders {
+            let paths = asset.shader.paths(&self.dir);
+            let current = paths.each_ref().map(|path| modified(path));
+            if current != asset.modified {
+                asset.modified = current;
+                changes.shaders.push((asset.shader.name, read_shader(&paths, asset.shader)));
+            }
+        }
+        for file in self.files.get_mut() {
+            let current = modified(&self.dir.join(&file.path));
+            if current.is_some() && current != file.modified {
+                file.modified = current;
+                changes.files.push(file.path.clone());
+            }
+        }
+        changes
+    }
+}
+
+/// Modification time of a file, `None` if it can not be read
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+fn read_shader([vertex, fragment]: &[PathBuf; 2], shader: EmbeddedShader) -> ShaderSource {
+    ShaderSource {
+        vertex: fs::read_to_string(vertex).unwrap_or_else(|_| shader.vertex.to_owned()),
+        fragment: fs::read_to_string(fragment).unwrap_or_else(|_| shader.fragment.to_owned()),
+    }
+}
+
+/// Decodes an image, in any format supported by `image`
+fn decode(bytes: &[u8]) -> Result<RgbaImage, GuiError> {
+    Ok(image::load(Cursor::new(bytes), image::guess_format(bytes)?)?.to_rgba8())
+}
+
+/// Decodes an image and uploads it as a texture
+fn create_texture(facade: &Backend, bytes: &[u8]) -> Result<SrgbTexture2d, GuiError> {
+    let image = decode(bytes)?;
+    let dimensions = image.dimensions();
+    let raw = RawImage2d::from_raw_rgba_reversed(&image.into_raw(), dimensions);
+    Ok(SrgbTexture2d::new(facade, raw)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_the_files_read_that_changed() {
+        let backend = Backend::headless(1, 1).unwrap();
+        let dir = std::env::temp_dir().join(format!("blocos-assets-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("sheet.toml"), "old").unwrap();
+        let mut assets = Assets::watching(dir.clone(), true);
+        assert_eq!(assets.text("sheet.toml", ""), "old");
+        assert_eq!(assets.text("missing.toml", "fallback"), "fallback");
+
+        fs::write(dir.join("sheet.toml"), "new").unwrap();
+        let file = fs::File::options().write(true).open(dir.join("sheet.toml")).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(1)).unwrap();
+        let changes = assets.poll_now(&backend);
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(changes.files, vec!["sheet.toml".to_owned()]);
+    }
+}
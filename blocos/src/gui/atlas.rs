@@ -0,0 +1,337 @@
+//! Texture atlases and sprite sheets, many images in a single texture so they can be drawn in the same batch
+use std::{collections::HashMap, fmt, rc::Rc};
+
+use glium::texture::{RawImage2d, SrgbTexture2d};
+use image::{imageops, RgbaImage};
+
+use crate::{core::rgb::Rgba, vec2};
+
+use super::{
+    interface::Interface,
+    systems::{BlendMode, ImageObject, Layer},
+    GuiError, Rect,
+};
+
+/// A part of a texture that is drawn as an image
+#[derive(Debug, Clone)]
+pub struct Sprite {
+    /// The texture shared by the sprites of the atlas or sheet
+    pub texture: Rc<SrgbTexture2d>,
+    /// The part of the texture, in texture coordinates
+    pub source: Rect,
+}
+
+impl Sprite {
+    /// Creates an image object drawing the sprite in `region`, without rotation, tint or blending
+    pub fn image(&self, region: Rect, layer: Layer) -> ImageObject {
+        ImageObject {
+            region,
+            texture: self.texture.clone(),
+            source: self.source,
+            rotation: 0.,
+            pivot: vec2!(0_f32, 0.),
+            layer,
+            tint: Rgba::WHITE,
+            blend: BlendMode::Alpha,
+        }
+    }
+}
+
+/// Converts a rectangle in pixels, with `y` going down from the top of the image, to texture coordinates
+///
+/// The textures are uploaded with the rows reversed, so the bottom of the image is at `v = 0`
+fn pixels_to_uv(x: u32, y: u32, width: u32, height: u32, dimensions: (u32, u32)) -> Rect {
+    let (w, h) = (dimensions.0 as f32, dimensions.1 as f32);
+    Rect::from_corners(
+        vec2!(x as f32 / w, 1. - (y + height) as f32 / h),
+        vec2!((x + width) as f32 / w, 1. - y as f32 / h),
+    )
+}
+
+/// Many images packed in a single texture, each one found by its name
+pub struct Atlas {
+    texture: Rc<SrgbTexture2d>,
+    sprites: HashMap<String, Rect>,
+    /// The frames of the images added as sprite sheets, in the texture of the atlas
+    sheets: HashMap<String, Vec<(String, Rect)>>,
+}
+
+impl Atlas {
+    /// The sprite of an image added to the builder
+    pub fn get(&self, name: &str) -> Option<Sprite> {
+        self.sprites.get(name).map(|&source| Sprite {
+            texture: self.texture.clone(),
+            source,
+        })
+    }
+
+    /// The frames of an image added to the builder with [AtlasBuilder::add_sheet], sharing the texture of the atlas
+    pub fn sheet(&self, name: &str) -> Option<SpriteSheet> {
+        self.sheets.get(name).map(|frames| SpriteSheet {
+            texture: self.texture.clone(),
+            frames: frames.clone(),
+        })
+    }
+
+    /// The texture with all the images
+    pub fn texture(&self) -> &Rc<SrgbTexture2d> {
+        &self.texture
+    }
+}
+
+/// Collects images and packs them in an [Atlas]
+///
+/// # Examples
+///
+/// ```ignore
+/// fn load_atlas(interface: &Interface) -> Result<Atlas, GuiError> {
+///     let mut builder = AtlasBuilder::new();
+///     builder.add("brick", interface.load_image("brick.png", include_bytes!("assets/brick.png"))?);
+///     let blocks = interface.load_image("blocks.png", include_bytes!("assets/blocks.png"))?;
+///     builder.add_sheet("blocks", blocks, &interface.load_text("blocks.toml", include_str!("assets/blocks.toml")))?;
+///     builder.build(interface)
+/// }
+///
+/// let atlas = load_atlas(&interface)?;
+/// canvas.draw(atlas.get("brick").unwrap().image(region, 0));
+/// ```
+#[derive(Default)]
+pub struct AtlasBuilder {
+    images: Vec<(String, RgbaImage)>,
+    /// The frames of the images added as sprite sheets, in pixels of the image
+    sheets: HashMap<String, Vec<(String, Frame)>>,
+}
+
+impl AtlasBuilder {
+    /// Empty pixels around each image, so the filtering does not mix neighbour images
+    const PADDING: u32 = 1;
+
+    /// Create a builder without images
+    pub fn new() -> AtlasBuilder {
+        AtlasBuilder::default()
+    }
+
+    /// Adds an image to be packed, replacing any image with the same name
+    pub fn add(&mut self, name: &str, image: RgbaImage) -> &mut Self {
+        self.images.retain(|(other, _)| other != name);
+        self.sheets.remove(name);
+        self.images.push((name.to_owned(), image));
+        self
+    }
+
+    /// Adds an image with many frames to be packed, with the frames read from the `descriptor` of a [SpriteSheet]
+    ///
+    /// The frames are found with [Atlas::sheet], and the image as a whole with [Atlas::get]
+    pub fn add_sheet(&mut self, name: &str, image: RgbaImage, descriptor: &str) -> Result<&mut Self, SheetError> {
+        let frames = parse_frames(descriptor, image.dimensions())?;
+        self.add(name, image);
+        self.sheets.insert(name.to_owned(), frames);
+        Ok(self)
+    }
+
+    /// Packs the images in shelves, from the tallest to the shortest, and uploads the texture
+    pub fn build(&self, interface: &Interface) -> Result<Atlas, GuiError> {
+        let padding = Self::PADDING;
+        let area: u32 = self
+            .images
+            .iter()
+            .map(|(_, image)| (image.width() + padding) * (image.height() + padding))
+            .sum();
+        let widest = self.images.iter().map(|(_, image)| image.width() + padding).max().unwrap_or(1);
+        let width = ((area as f32).sqrt().ceil() as u32).max(widest).next_power_of_two();
+
+        let mut order: Vec<&(String, RgbaImage)> = self.images.iter().collect();
+        order.sort_by_key(|(_, image)| std::cmp::Reverse(image.height()));
+
+        let mut positions = Vec::with_capacity(order.len());
+        let (mut x, mut y, mut shelf_height) = (0, 0, 0);
+        for (_, image) in &order {
+            if x + image.width() + padding > width {
+                x = 0;
+                y += shelf_height;
+                shelf_height = 0;
+            }
+            positions.push((x, y));
+            x += image.width() + padding;
+            shelf_height = shelf_height.max(image.height() + padding);
+        }
+        let height = (y + shelf_height).max(1).next_power_of_two();
+
+        let mut packed = RgbaImage::new(width, height);
+        let mut sprites = HashMap::new();
+        let mut sheets = HashMap::new();
+        for ((name, image), (x, y)) in order.into_iter().zip(positions) {
+            imageops::replace(&mut packed, image, x as i64, y as i64);
+            sprites.insert(name.clone(), pixels_to_uv(x, y, image.width(), image.height(), (width, height)));
+            if let Some(frames) = self.sheets.get(name) {
+                let frames = frames
+                    .iter()
+                    .map(|(frame, f)| (frame.clone(), pixels_to_uv(x + f.x, y + f.y, f.w, f.h, (width, height))))
+                    .collect();
+                sheets.insert(name.clone(), frames);
+            }
+        }
+        let texture = interface.create_texture(RawImage2d::from_raw_rgba_reversed(&packed.into_raw(), (width, height)))?;
+        Ok(Atlas { texture, sprites, sheets })
+    }
+}
+
+/// Error in the descriptor of a [SpriteSheet]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SheetError {
+    /// Line of the descriptor, starting at 1
+    pub line: usize,
+    /// What is wrong in the line
+    pub message: String,
+}
+
+impl fmt::Display for SheetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for SheetError {}
+
+/// An image with many frames, whose rectangles are read from a descriptor
+///
+/// The descriptor is a TOML file with the rectangle of each frame in pixels, from the top left corner of the image:
+///
+/// ```toml
+/// [frames]
+/// block = { x = 0, y = 0, w = 16, h = 16 }
+/// star = { x = 16, y = 0, w = 16, h = 16 }
+/// ```
+pub struct SpriteSheet {
+    texture: Rc<SrgbTexture2d>,
+    /// The frames in the order of the descriptor, with their texture coordinates
+    frames: Vec<(String, Rect)>,
+}
+
+impl SpriteSheet {
+    /// Reads the frames of `texture` from the `descriptor`
+    pub fn parse(descriptor: &str, texture: Rc<SrgbTexture2d>) -> Result<SpriteSheet, SheetError> {
+        let dimensions = texture.dimensions();
+        let frames = parse_frames(descriptor, dimensions)?
+            .into_iter()
+            .map(|(name, Frame { x, y, w, h })| (name, pixels_to_uv(x, y, w, h, dimensions)))
+            .collect();
+        Ok(SpriteSheet { texture, frames })
+    }
+
+    /// The sprite of a frame
+    pub fn get(&self, name: &str) -> Option<Sprite> {
+        self.frames.iter().find(|(frame, _)| frame == name).map(|&(_, source)| Sprite {
+            texture: self.texture.clone(),
+            source,
+        })
+    }
+
+    /// The sprites of every frame, in the order of the descriptor
+    pub fn sprites(&self) -> impl Iterator<Item = Sprite> + '_ {
+        self.frames.iter().map(|&(_, source)| Sprite {
+            texture: self.texture.clone(),
+            source,
+        })
+    }
+}
+
+/// A frame of a sprite sheet, in pixels from the top left corner of its image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Frame {
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+}
+
+/// Reads the frames of an image of size `dimensions` from the `descriptor` of a [SpriteSheet], in its order
+fn parse_frames(descriptor: &str, dimensions: (u32, u32)) -> Result<Vec<(String, Frame)>, SheetError> {
+    let mut frames = Vec::new();
+    let mut in_frames = false;
+    for (i, line) in descriptor.lines().enumerate() {
+        let error = |message: String| SheetError { line: i + 1, message };
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            in_frames = section.trim() == "frames";
+            continue;
+        }
+        if !in_frames {
+            continue;
+        }
+        let (name, value) = line.split_once('=').ok_or_else(|| error("expected `name = { ... }`".to_owned()))?;
+        let name = name.trim().trim_matches('"');
+        let fields = value
+            .trim()
+            .strip_prefix('{')
+            .and_then(|v| v.strip_suffix('}'))
+            .ok_or_else(|| error(format!("the frame `{name}` is not an inline table")))?;
+        let mut rect = HashMap::new();
+        for field in fields.split(',').filter(|f| !f.trim().is_empty()) {
+            let (key, number) = field.split_once('=').ok_or_else(|| error(format!("invalid field `{}`", field.trim())))?;
+            let number: u32 = number
+                .trim()
+                .parse()
+                .map_err(|_| error(format!("`{}` is not a number of pixels", number.trim())))?;
+            rect.insert(key.trim(), number);
+        }
+        let get = |key: &str| rect.get(key).copied().ok_or_else(|| error(format!("the frame `{name}` has no `{key}`")));
+        let (x, y, w, h) = (get("x")?, get("y")?, get("w")?, get("h")?);
+        // A malformed descriptor can have sizes so big that the end of the frame does not fit in a u32
+        let outside = |start: u32, size: u32, limit: u32| start.checked_add(size).is_none_or(|end| end > limit);
+        if outside(x, w, dimensions.0) || outside(y, h, dimensions.1) {
+            return Err(error(format!("the frame `{name}` is outside the image")));
+        }
+        frames.push((name.to_owned(), Frame { x, y, w, h }));
+    }
+    Ok(frames)
+}
+
+/// The line without its comment, a `#` inside a quoted name does not start one
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_hash_inside_a_quoted_name_is_not_a_comment() {
+        let descriptor = "[frames] # the frames\n\"tile#1\" = { x = 0, y = 0, w = 8, h = 8 } # the first tile\n";
+        let frames = parse_frames(descriptor, (16, 8)).unwrap();
+        assert_eq!(frames, vec![("tile#1".to_owned(), Frame { x: 0, y: 0, w: 8, h: 8 })]);
+    }
+
+    #[test]
+    fn the_frames_of_a_sheet_are_moved_into_the_atlas() {
+        let interface = Interface::headless(8, 8).unwrap();
+        let descriptor = "[frames]\nfirst = { x = 0, y = 0, w = 8, h = 8 }\nsecond = { x = 8, y = 0, w = 8, h = 8 }";
+        let mut builder = AtlasBuilder::new();
+        builder.add("tall", RgbaImage::new(4, 32));
+        builder.add_sheet("sheet", RgbaImage::new(16, 8), descriptor).unwrap();
+        let atlas = builder.build(&interface).unwrap();
+        let sheet = atlas.sheet("sheet").unwrap();
+        assert!(Rc::ptr_eq(&sheet.texture, atlas.texture()));
+        assert!(atlas.sheet("tall").is_none());
+
+        // The tall image goes first, so the sheet starts after it and its padding
+        let dimensions = atlas.texture().dimensions();
+        let corners = |rect: Rect| (rect.left(), rect.bottom(), rect.right(), rect.top());
+        let source = |sprite: Option<Sprite>| corners(sprite.unwrap().source);
+        assert_eq!(source(atlas.get("sheet")), corners(pixels_to_uv(5, 0, 16, 8, dimensions)));
+        assert_eq!(source(sheet.get("first")), corners(pixels_to_uv(5, 0, 8, 8, dimensions)));
+        assert_eq!(source(sheet.get("second")), corners(pixels_to_uv(13, 0, 8, 8, dimensions)));
+    }
+}
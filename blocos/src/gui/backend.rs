@@ -0,0 +1,86 @@
+//! The OpenGL context where the interface draws, a window or an offscreen image
+use std::rc::Rc;
+
+use glium::{
+    backend::{Context, Facade},
+    framebuffer::SimpleFrameBuffer,
+    texture::{MipmapsOption, SrgbFormat, SrgbTexture2d},
+    uniforms::MagnifySamplerFilter,
+    BlitTarget, Display, Surface,
+};
+use image::{imageops, RgbaImage};
+
+use super::{headless, GuiError};
+
+/// The OpenGL context of the [Interface](super::interface::Interface) and where its frames go
+pub enum Backend {
+    /// Draws to a window, presenting each frame
+    Window(Display),
+    /// Draws to a texture with no window, so the frames can be read back as images
+    Headless {
+        /// The context, without a window
+        renderer: Rc<Context>,
+        /// The texture where the frames are drawn
+        target: SrgbTexture2d,
+    },
+}
+
+impl Backend {
+    /// Creates a context with no window that draws in a `width` x `height` image
+    ///
+    /// Uses EGL without a surface, so it works with no display server and no GPU, with the software rasterizer
+    /// of Mesa (llvmpipe)
+    pub fn headless(width: u32, height: u32) -> Result<Backend, GuiError> {
+        let renderer = headless::surfaceless_context(width, height)?;
+        let target =
+            SrgbTexture2d::empty_with_format(&renderer, SrgbFormat::U8U8U8U8, MipmapsOption::NoMipmap, width, height)?;
+        Ok(Backend::Headless { renderer, target })
+    }
+
+    /// Scale factor of the window, 1 when headless
+    pub fn scale_factor(&self) -> f64 {
+        match self {
+            Backend::Window(display) => display.gl_window().window().scale_factor(),
+            Backend::Headless { .. } => 1.,
+        }
+    }
+
+    /// Size in pixels of the frames drawn
+    pub fn dimensions(&self) -> (u32, u32) {
+        match self {
+            Backend::Window(display) => display.get_framebuffer_dimensions(),
+            Backend::Headless { target, .. } => target.dimensions(),
+        }
+    }
+
+    /// Reads the frame being drawn in `frame`, before it is presented, or the texture when headless
+    ///
+    /// The back buffer of the window is copied to a texture, since after the frame is presented the buffers
+    /// are swapped and their contents are undefined. `None` if the copy cannot be created
+    pub fn read_frame<S: Surface>(&self, frame: &S) -> Option<RgbaImage> {
+        let raw: glium::texture::RawImage2d<u8> = match self {
+            Backend::Window(display) => {
+                let (width, height) = frame.get_dimensions();
+                let (format, mipmaps) = (SrgbFormat::U8U8U8U8, MipmapsOption::NoMipmap);
+                let copy = SrgbTexture2d::empty_with_format(display, format, mipmaps, width, height).ok()?;
+                let target = SimpleFrameBuffer::new(display, &copy).ok()?;
+                let rect = BlitTarget { left: 0, bottom: 0, width: width as i32, height: height as i32 };
+                frame.blit_whole_color_to(&target, &rect, MagnifySamplerFilter::Nearest);
+                copy.read()
+            }
+            Backend::Headless { target, .. } => target.read(),
+        };
+        let image = RgbaImage::from_raw(raw.width, raw.height, raw.data.into_owned())?;
+        // OpenGL stores the rows from the bottom to the top
+        Some(imageops::flip_vertical(&image))
+    }
+}
+
+impl Facade for Backend {
+    fn get_context(&self) -> &Rc<Context> {
+        match self {
+            Backend::Window(display) => display.get_context(),
+            Backend::Headless { renderer, .. } => renderer,
+        }
+    }
+}
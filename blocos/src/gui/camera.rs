@@ -0,0 +1,398 @@
+//! The camera, which region of the world is shown and how it fits the window
+use crate::{vec2, vector2::Vec2};
+
+use super::{transform::Transform, Rect};
+
+/// How the region of the world shown by the [Camera] is scaled to the window
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScalePolicy {
+    /// The whole region is shown with the same scale on both axes, the rest of the window shows more of the world
+    #[default]
+    Fit,
+    /// Like [ScalePolicy::Fit], but the rest of the window is covered by bars of the clear color
+    Letterbox,
+    /// The region covers the whole window with the same scale on both axes, cropping what does not fit
+    Fill,
+    /// The region covers the whole window, with different scales on each axis if the proportions differ
+    Stretch,
+    /// Like [ScalePolicy::Letterbox], with a whole number of pixels in a unit of the world, so pixel art stays sharp
+    ///
+    /// Windows too small for one pixel per unit use the scale of [ScalePolicy::Letterbox]
+    PixelPerfect,
+    /// A unit of the world is a pixel, with (0, 0) at the bottom left of the viewport, for interfaces in screen units
+    ///
+    /// The world follows the size of the viewport, ignoring the goal and the zoom
+    Screen,
+}
+
+/// A camera.
+///
+/// This struct represents a camera. It has a world rectangle and a target rectangle.
+/// The world rectangle represents the area of the world that the camera can see.
+/// The target rectangle represents the area of the screen where the world will be rendered.
+///
+/// The world moves smoothly toward [Camera::goal] in [Camera::update], with the [Camera::zoom] and the screen shake
+/// applied over it
+#[derive(Debug, Clone)]
+pub struct Camera {
+    /// The world rectangle.
+    ///
+    /// This field represents the area of the world that the camera can see.
+    /// It is a rectangle that defines the boundaries of the world in the game world coordinates.
+    pub world: Rect,
+
+    /// The target rectangle.
+    ///
+    /// This field represents the area of the screen where the world will be rendered.
+    /// It is a rectangle that defines the boundaries of the screen in the screen coordinates, from -1 to 1.
+    pub target: Rect,
+
+    /// How the world is scaled to the window
+    pub policy: ScalePolicy,
+    /// The region the camera moves toward
+    pub goal: Rect,
+    /// How fast the camera reaches the goal, the fraction of the distance left covered per second is `1 - e^-rate`
+    ///
+    /// [f32::INFINITY] jumps to the goal at once
+    pub follow_rate: f32,
+    /// How much the camera is zoomed in, 2 shows half of the region
+    pub zoom: f32,
+    /// The point of the world that stays in place when zooming
+    pub zoom_center: Vec2,
+    /// The largest distance the camera moves when shaking, in units of the world
+    pub max_shake_offset: f32,
+    /// The largest angle the camera turns when shaking, in radians
+    pub max_shake_angle: f32,
+    /// Size of the window in pixels
+    viewport: (u32, u32),
+    /// The region shown before the zoom, moving toward the goal
+    view: Rect,
+    /// Intensity of the shake from 0 to 1, going down with time
+    trauma: f32,
+    /// Seconds since the camera was created, moving the noise of the shake
+    time: f32,
+    shake_offset: Vec2,
+    shake_angle: f32,
+}
+
+impl Camera {
+    /// Trauma lost per second
+    const TRAUMA_DECAY: f32 = 1.5;
+
+    /// Create a camera showing `world` in a window of `viewport` pixels
+    pub fn new(world: Rect, viewport: (u32, u32)) -> Camera {
+        let mut camera = Camera {
+            world,
+            target: Rect {
+                center: Vec2::ZERO,
+                size: vec2!(2_f32, 2.),
+            },
+            policy: ScalePolicy::default(),
+            goal: world,
+            follow_rate: 8.,
+            zoom: 1.,
+            zoom_center: world.center,
+            max_shake_offset: 3.,
+            max_shake_angle: 0.02,
+            viewport,
+            view: world,
+            trauma: 0.,
+            time: 0.,
+            shake_offset: Vec2::ZERO,
+            shake_angle: 0.,
+        };
+        camera.refresh();
+        camera
+    }
+
+    /// Returns a transformation matrix for the camera.
+    ///
+    /// This method takes a 2D vector and returns a transformation matrix that represents the camera's transformation.
+    /// The transformation is calculated based on the camera's world and target rectangles, moved by the shake.
+    pub fn transformation(&self) -> Transform {
+        let scale = self.scale();
+        Transform::default()
+            .translate(-(self.world.center + self.shake_offset))
+            .rotate(self.shake_angle)
+            .scale(scale)
+            .translate(self.target.center)
+    }
+
+    /// The element-wise ratio of target to word size
+    pub fn scale(&self) -> Vec2 {
+        Vec2 {
+            x: self.target.size.x / self.world.size.x,
+            y: self.target.size.y / self.world.size.y,
+        }
+    }
+    /// transforms a position from the world to the target, moved by the shake like [Camera::transformation]
+    pub fn world_to_target(&self, mut pos : Vec2) -> Vec2 {
+        let scale = self.scale();
+        pos -= self.world.center + self.shake_offset;
+        pos = rotate(pos, self.shake_angle);
+        pos = vec2!(pos.x * scale.x, pos.y * scale.y);
+        pos += self.target.center;
+        pos
+    }
+    /// transforms a position from the target to the world, moved by the shake like [Camera::transformation]
+    pub fn target_to_world(&self, mut pos: Vec2)-> Vec2  {
+        let scale = self.scale();
+        pos -= self.target.center;
+        pos = vec2!(pos.x / scale.x, pos.y / scale.y);
+        pos = rotate(pos, -self.shake_angle);
+        pos += self.world.center + self.shake_offset;
+        pos
+    }
+
+    /// Size of the window in pixels
+    pub fn viewport(&self) -> (u32, u32) {
+        self.viewport
+    }
+
+    /// Changes the size of the window, in pixels
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.viewport = (width, height);
+        self.refresh();
+    }
+
+    /// Shows a region at once, without moving toward it
+    pub fn show(&mut self, region: Rect) {
+        self.goal = region;
+        self.view = region;
+        self.refresh();
+    }
+
+    /// Zooms by `zoom` keeping `center`, a point of the world, in the same place of the window
+    pub fn zoom_at(&mut self, center: Vec2, zoom: f32) {
+        self.zoom_center = center;
+        self.zoom = zoom;
+        self.refresh();
+    }
+
+    /// Shakes the camera, `amount` from 0 to 1 is added to the trauma, which is at most 1
+    ///
+    /// The shake grows with the square of the trauma, so small hits barely move the camera
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.trauma = (self.trauma + amount).clamp(0., 1.);
+    }
+
+    /// Moves the camera toward the goal and advances the shake by `delta` seconds
+    pub fn update(&mut self, delta: f32) {
+        let follow = 1. - (-self.follow_rate * delta).exp();
+        self.view = Rect {
+            center: self.view.center + (self.goal.center - self.view.center) * follow,
+            size: self.view.size + (self.goal.size - self.view.size) * follow,
+        };
+
+        self.time += delta;
+        self.trauma = (self.trauma - Self::TRAUMA_DECAY * delta).max(0.);
+        let shake = self.trauma * self.trauma;
+        self.shake_offset = vec2!(noise(self.time, 0.), noise(self.time, 1.)) * (self.max_shake_offset * shake);
+        self.shake_angle = noise(self.time, 2.) * self.max_shake_angle * shake;
+        self.refresh();
+    }
+
+    /// The parts of the window outside the region, covered by bars with [ScalePolicy::Letterbox] and
+    /// [ScalePolicy::PixelPerfect]
+    pub fn letterbox(&self) -> Vec<glium::Rect> {
+        if !matches!(self.policy, ScalePolicy::Letterbox | ScalePolicy::PixelPerfect) {
+            return Vec::new();
+        }
+        let (width, height) = self.viewport;
+        let content_width = ((self.target.size.x / 2. * width as f32).round() as u32).min(width);
+        let content_height = ((self.target.size.y / 2. * height as f32).round() as u32).min(height);
+        let (side, bottom) = ((width - content_width) / 2, (height - content_height) / 2);
+        let mut bars = Vec::new();
+        if side > 0 {
+            bars.push(glium::Rect { left: 0, bottom: 0, width: side, height });
+            bars.push(glium::Rect { left: side + content_width, bottom: 0, width: width - side - content_width, height });
+        }
+        if bottom > 0 {
+            bars.push(glium::Rect { left: 0, bottom: 0, width, height: bottom });
+            bars.push(glium::Rect {
+                left: 0,
+                bottom: bottom + content_height,
+                width,
+                height: height - bottom - content_height,
+            });
+        }
+        bars
+    }
+
+    /// Applies the zoom to the view and fits the target to the window according to the policy
+    fn refresh(&mut self) {
+        if self.policy == ScalePolicy::Screen {
+            let size = vec2!(self.viewport.0.max(1) as f32, self.viewport.1.max(1) as f32);
+            self.world = Rect { center: size / 2., size };
+            self.target.size = vec2!(2_f32, 2.);
+            return;
+        }
+        let zoom = self.zoom.max(f32::EPSILON);
+        self.world = Rect {
+            center: self.zoom_center + (self.view.center - self.zoom_center) / zoom,
+            size: self.view.size / zoom,
+        };
+
+        let (width, height) = (self.viewport.0.max(1) as f32, self.viewport.1.max(1) as f32);
+        let (horizontal, vertical) = (width / self.world.size.x, height / self.world.size.y);
+        let pixels_per_unit = match self.policy {
+            ScalePolicy::Fit | ScalePolicy::Letterbox => horizontal.min(vertical),
+            ScalePolicy::Fill => horizontal.max(vertical),
+            ScalePolicy::Stretch | ScalePolicy::Screen => {
+                self.target.size = vec2!(2_f32, 2.);
+                return;
+            }
+            ScalePolicy::PixelPerfect => {
+                let fit = horizontal.min(vertical);
+                if fit >= 1. {
+                    fit.floor()
+                } else {
+                    fit
+                }
+            }
+        };
+        // The target goes from -1 to 1, so the whole window is 2 units wide and high
+        self.target.size = vec2!(
+            2. * self.world.size.x * pixels_per_unit / width,
+            2. * self.world.size.y * pixels_per_unit / height
+        );
+    }
+}
+
+/// Identifies a viewport of the [Interface](super::interface::Interface)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct ViewportId(pub(super) usize);
+
+impl ViewportId {
+    /// The viewport covering the whole window, with [Interface::camera](super::interface::Interface::camera)
+    pub const MAIN: ViewportId = ViewportId(0);
+
+    /// Index of the viewport in the ones added over the main one, `None` for the main one
+    pub(super) fn index(self) -> Option<usize> {
+        self.0.checked_sub(1)
+    }
+}
+
+/// A part of the window drawn with its own camera, objects outside it are cut
+#[derive(Debug, Clone)]
+pub struct Viewport {
+    /// The camera of the viewport, its viewport size is kept equal to the area of the viewport in pixels
+    pub camera: Camera,
+    /// Part of the window covered, from (0, 0) at the bottom left to (1, 1) at the top right
+    pub region: Rect,
+}
+
+impl Viewport {
+    /// The area of the viewport in pixels, in a window of `window` pixels
+    pub fn area(&self, (width, height): (u32, u32)) -> glium::Rect {
+        let (width, height) = (width as f32, height as f32);
+        let left = (self.region.left().clamp(0., 1.) * width).round();
+        let bottom = (self.region.bottom().clamp(0., 1.) * height).round();
+        let right = (self.region.right().clamp(0., 1.) * width).round();
+        let top = (self.region.top().clamp(0., 1.) * height).round();
+        glium::Rect {
+            left: left as u32,
+            bottom: bottom as u32,
+            width: (right - left).max(0.) as u32,
+            height: (top - bottom).max(0.) as u32,
+        }
+    }
+}
+
+/// `vector` rotated by `angle` radians counterclockwise
+fn rotate(vector: Vec2, angle: f32) -> Vec2 {
+    let (sin, cos) = angle.sin_cos();
+    vec2!(vector.x * cos - vector.y * sin, vector.x * sin + vector.y * cos)
+}
+
+/// Smooth noise from -1 to 1 varying with `time`, different for each `seed`
+fn noise(time: f32, seed: f32) -> f32 {
+    ((time * 23. + seed * 1.7).sin() + (time * 37. + seed * 3.1).sin()) / 2.
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A camera showing the square of side 10 around the origin in a 16:9 window
+    fn wide_camera(policy: ScalePolicy) -> Camera {
+        let mut camera = Camera::new(Rect { center: Vec2::ZERO, size: vec2!(10_f32, 10.) }, (1600, 900));
+        camera.policy = policy;
+        camera.resize(1600, 900);
+        camera
+    }
+
+    fn assert_close(a: Vec2, b: Vec2) {
+        assert!((a.x - b.x).abs() < 1e-4 && (a.y - b.y).abs() < 1e-4, "{a:?} != {b:?}");
+    }
+
+    #[test]
+    fn fit_and_letterbox_show_the_whole_world_with_bars_at_the_sides() {
+        for policy in [ScalePolicy::Fit, ScalePolicy::Letterbox] {
+            let camera = wide_camera(policy);
+            assert_close(camera.target.size, vec2!(2_f32 * 900. / 1600., 2.));
+        }
+        assert!(wide_camera(ScalePolicy::Fit).letterbox().is_empty());
+        let bars = wide_camera(ScalePolicy::Letterbox).letterbox();
+        assert_eq!(
+            bars,
+            vec![
+                glium::Rect { left: 0, bottom: 0, width: 350, height: 900 },
+                glium::Rect { left: 1250, bottom: 0, width: 350, height: 900 },
+            ]
+        );
+    }
+
+    #[test]
+    fn fill_crops_the_world_and_stretch_covers_the_window() {
+        assert_close(wide_camera(ScalePolicy::Fill).target.size, vec2!(2_f32, 2. * 1600. / 900.));
+        assert_close(wide_camera(ScalePolicy::Stretch).target.size, vec2!(2_f32, 2.));
+        assert!(wide_camera(ScalePolicy::Fill).letterbox().is_empty());
+    }
+
+    #[test]
+    fn pixel_perfect_uses_whole_pixels_per_unit() {
+        let camera = wide_camera(ScalePolicy::PixelPerfect);
+        // 90 pixels per unit fit exactly in the height
+        assert_close(camera.target.size, vec2!(2_f32 * 900. / 1600., 2.));
+        let mut camera = Camera::new(Rect { center: Vec2::ZERO, size: vec2!(7_f32, 7.) }, (1600, 900));
+        camera.policy = ScalePolicy::PixelPerfect;
+        camera.resize(1600, 900);
+        // 900 / 7 is 128.57, rounded down to 128 pixels per unit
+        assert_close(camera.target.size, vec2!(2_f32 * 7. * 128. / 1600., 2. * 7. * 128. / 900.));
+        assert_eq!(camera.letterbox().len(), 4);
+    }
+
+    #[test]
+    fn screen_has_a_unit_per_pixel() {
+        let camera = wide_camera(ScalePolicy::Screen);
+        assert_close(camera.world.size, vec2!(1600_f32, 900.));
+        assert_close(camera.target_to_world(vec2!(-1_f32, -1.)), Vec2::ZERO);
+    }
+
+    #[test]
+    fn zooming_keeps_the_anchor_in_place() {
+        let mut camera = wide_camera(ScalePolicy::Fit);
+        let anchor = vec2!(3_f32, -2.);
+        let before = camera.world_to_target(anchor);
+        camera.zoom_at(anchor, 2.5);
+        assert_close(camera.world_to_target(anchor), before);
+        assert_close(camera.world.size, vec2!(4_f32, 4.));
+    }
+
+    #[test]
+    fn target_to_world_follows_the_shake() {
+        let mut camera = wide_camera(ScalePolicy::Fit);
+        camera.add_trauma(1.);
+        camera.update(0.05);
+        assert!(camera.shake_offset.length() > 0. && camera.shake_angle != 0.);
+        let point = vec2!(2_f32, 1.);
+        let on_target = camera.transformation().0;
+        let projected = vec2!(
+            on_target[0][0] * point.x + on_target[1][0] * point.y + on_target[3][0],
+            on_target[0][1] * point.x + on_target[1][1] * point.y + on_target[3][1]
+        );
+        assert_close(camera.world_to_target(point), projected);
+        assert_close(camera.target_to_world(projected), point);
+    }
+}
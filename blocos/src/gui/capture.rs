@@ -0,0 +1,195 @@
+//! Screenshots and clips of the frames of the interface, saved as PNG images and animated GIFs
+//!
+//! The clips keep only their last seconds, so a recording can be left running and saved when something happens
+use std::{
+    collections::VecDeque,
+    fs::{self, File},
+    io::{self, BufWriter},
+    path::{Path, PathBuf},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use image::{
+    codecs::gif::{GifEncoder, Repeat},
+    imageops::{self, FilterType},
+    Delay, Frame, RgbaImage,
+};
+
+/// Milliseconds since the Unix epoch, naming the files so they do not overwrite each other
+fn timestamp() -> u128 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis()).unwrap_or(0)
+}
+
+/// Writes `image` to `dir` as `screenshot-<timestamp>.png` in another thread, like the clips
+///
+/// The thread returns the path of the file
+pub fn save_screenshot(image: RgbaImage, dir: &Path) -> JoinHandle<io::Result<PathBuf>> {
+    let dir = dir.to_owned();
+    thread::spawn(move || {
+        fs::create_dir_all(&dir)?;
+        let path = dir.join(format!("screenshot-{}.png", timestamp()));
+        image.save(&path).map_err(io::Error::other)?;
+        Ok(path)
+    })
+}
+
+/// How the frames of a clip are saved
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ClipFormat {
+    /// An animated GIF, `clip-<timestamp>.gif`
+    #[default]
+    Gif,
+    /// A directory `clip-<timestamp>` with the frames as `frame-0000.png`, `frame-0001.png` and so on
+    PngSequence,
+}
+
+/// Keeps the frames of the last seconds at a fixed rate, to save them as a clip
+#[derive(Debug)]
+pub struct Recorder {
+    /// Time between two frames captured
+    pub interval: Duration,
+    /// How long the clip is, older frames are dropped
+    pub length: Duration,
+    /// Widest the frames are kept, larger frames are scaled down to save memory
+    pub max_width: u32,
+    /// How the clip is saved
+    pub format: ClipFormat,
+    /// The frames captured and when, the oldest first
+    frames: VecDeque<(Instant, RgbaImage)>,
+}
+
+impl Recorder {
+    /// Create a recorder of `fps` frames per second keeping the last `seconds`, saved as a GIF
+    pub fn new(fps: u32, seconds: f32) -> Recorder {
+        Recorder {
+            interval: Duration::from_secs(1) / fps.max(1),
+            length: Duration::from_secs_f32(seconds.max(0.)),
+            max_width: 480,
+            format: ClipFormat::Gif,
+            frames: VecDeque::new(),
+        }
+    }
+
+    /// Whether a frame presented at `now` should be captured
+    pub fn wants_frame(&self, now: Instant) -> bool {
+        !self.frames.back().is_some_and(|(last, _)| now.duration_since(*last) < self.interval)
+    }
+
+    /// Adds a frame presented at `now`, dropping the ones older than the length of the clip
+    pub fn push(&mut self, now: Instant, image: RgbaImage) {
+        let image = if image.width() > self.max_width {
+            let height = (image.height() as u64 * self.max_width as u64 / image.width() as u64).max(1) as u32;
+            imageops::resize(&image, self.max_width, height, FilterType::Triangle)
+        } else {
+            image
+        };
+        // The frames of a clip share their size, so the ones from before the window was resized are dropped
+        if self.frames.back().is_some_and(|(_, last)| last.dimensions() != image.dimensions()) {
+            self.frames.clear();
+        }
+        self.frames.push_back((now, image));
+        while self.frames.front().is_some_and(|(time, _)| now.duration_since(*time) > self.length) {
+            self.frames.pop_front();
+        }
+    }
+
+    /// Number of frames kept
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Whether no frame was captured yet
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Saves the frames kept to `dir` in another thread, since encoding a GIF takes a while, and removes them
+    ///
+    /// The thread returns the path of the clip
+    pub fn save(&mut self, dir: &Path) -> JoinHandle<io::Result<PathBuf>> {
+        let frames: Vec<_> = self.frames.drain(..).collect();
+        let (dir, format, interval) = (dir.to_owned(), self.format, self.interval);
+        thread::spawn(move || {
+            fs::create_dir_all(&dir)?;
+            let name = format!("clip-{}", timestamp());
+            match format {
+                ClipFormat::Gif => {
+                    let path = dir.join(name).with_extension("gif");
+                    write_gif(&path, frames, interval)?;
+                    Ok(path)
+                }
+                ClipFormat::PngSequence => {
+                    let path = dir.join(name);
+                    fs::create_dir_all(&path)?;
+                    for (i, (_, image)) in frames.iter().enumerate() {
+                        image.save(path.join(format!("frame-{i:04}.png"))).map_err(io::Error::other)?;
+                    }
+                    Ok(path)
+                }
+            }
+        })
+    }
+}
+
+/// Writes the frames as a looping GIF, each one shown until the time the next was captured
+fn write_gif(path: &Path, frames: Vec<(Instant, RgbaImage)>, interval: Duration) -> io::Result<()> {
+    let delays: Vec<Duration> = frames
+        .windows(2)
+        .map(|pair| pair[1].0.duration_since(pair[0].0))
+        .chain(std::iter::once(interval))
+        .collect();
+    let mut encoder = GifEncoder::new(BufWriter::new(File::create(path)?));
+    encoder.set_repeat(Repeat::Infinite).map_err(io::Error::other)?;
+    let frames = frames
+        .into_iter()
+        .zip(delays)
+        .map(|((_, image), delay)| Frame::from_parts(image, 0, 0, Delay::from_saturating_duration(delay)));
+    encoder.encode_frames(frames).map_err(io::Error::other)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A recorder of 10 frames per second keeping the last second
+    fn recorder() -> Recorder {
+        Recorder::new(10, 1.)
+    }
+
+    #[test]
+    fn wants_a_frame_per_interval() {
+        let mut recorder = recorder();
+        let start = Instant::now();
+        assert!(recorder.wants_frame(start));
+        recorder.push(start, RgbaImage::new(4, 4));
+        assert!(!recorder.wants_frame(start + Duration::from_millis(99)));
+        assert!(recorder.wants_frame(start + Duration::from_millis(100)));
+    }
+
+    #[test]
+    fn drops_the_frames_older_than_the_length() {
+        let mut recorder = recorder();
+        let start = Instant::now();
+        for i in 0..15 {
+            recorder.push(start + Duration::from_millis(100 * i), RgbaImage::new(4, 4));
+        }
+        // The frames from 0.4 to 1.4 seconds, the last second
+        assert_eq!(recorder.len(), 11);
+        assert_eq!(recorder.frames.front().unwrap().0, start + Duration::from_millis(400));
+    }
+
+    #[test]
+    fn scales_down_wide_frames_and_restarts_when_the_size_changes() {
+        let mut recorder = recorder();
+        recorder.max_width = 8;
+        let start = Instant::now();
+        recorder.push(start, RgbaImage::new(16, 6));
+        assert_eq!(recorder.frames.back().unwrap().1.dimensions(), (8, 3));
+        recorder.push(start + Duration::from_millis(100), RgbaImage::new(32, 12));
+        assert_eq!(recorder.len(), 2);
+        recorder.push(start + Duration::from_millis(200), RgbaImage::new(6, 6));
+        assert_eq!(recorder.len(), 1);
+        assert_eq!(recorder.frames.back().unwrap().1.dimensions(), (6, 6));
+    }
+}
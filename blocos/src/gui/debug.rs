@@ -0,0 +1,259 @@
+//! Overlay for developers, showing how fast the game runs, what the canvas draws and where the regions of the
+//! interface are
+//!
+//! The texts and the graph are drawn in a viewport of their own in pixels of the window, the hitboxes and the
+//! cameras in the world, over the scene
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use crate::{core::rgb::Rgba, vec2, vector2::Vec2};
+
+use super::{
+    fonts::FontId,
+    interface::{Canvas, SystemStats},
+    systems::{BlendMode, Layer, Shape, ShapeObject, SolidColorObject, TextLayout, TextObject},
+    Camera, Rect, ScalePolicy, ViewportId,
+};
+
+/// What the game shows in the overlay besides the counters of the interface
+#[derive(Debug, Clone)]
+pub struct DebugInfo {
+    /// Name of the current scene
+    pub scene: &'static str,
+    /// Position of the mouse in the world
+    pub mouse: Vec2,
+    /// Regions of the interface drawn as hitboxes, with their names
+    pub regions: Vec<(&'static str, Rect)>,
+}
+
+/// Overlay with the FPS, a graph of the frame times, the tick rate, the work of each system, the glyph cache,
+/// the scene, the cameras, the mouse and the hitboxes
+#[derive(Debug, Default)]
+pub struct DebugOverlay {
+    /// Whether the overlay is drawn
+    pub visible: bool,
+    /// Durations of the last frames in seconds, the oldest first
+    frame_times: VecDeque<f32>,
+    /// Ticks of the simulation run in the last second, with when
+    ticks: VecDeque<(Instant, u32)>,
+    /// The viewport of the texts and the graph, added in the first draw
+    viewport: Option<ViewportId>,
+}
+
+impl DebugOverlay {
+    /// Number of frames shown in the graph
+    const HISTORY: usize = 120;
+    /// Over the scene and the interface of the game
+    const LAYER: Layer = Layer::MAX;
+    /// Size of the texts in pixels
+    const FONT_SIZE: f32 = 14.;
+    /// Pixels of the graph for a millisecond of a frame
+    const GRAPH_SCALE: f32 = 3.;
+    /// Height of the graph in pixels, longer frames are cut
+    const GRAPH_HEIGHT: f32 = 100.;
+    /// Width of the bar of a frame in the graph, in pixels
+    const BAR_WIDTH: f32 = 3.;
+    /// Duration of a frame at 60 FPS, marked in the graph
+    const TARGET_FRAME: f32 = 1. / 60.;
+
+    /// Create a hidden overlay
+    pub fn new() -> DebugOverlay {
+        DebugOverlay::default()
+    }
+
+    /// Shows or hides the overlay
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    /// Records a frame presented at `now`, `delta` seconds after the previous one, which ran `ticks` of the simulation
+    pub fn record_frame(&mut self, now: Instant, delta: f32, ticks: u32) {
+        if self.frame_times.len() == Self::HISTORY {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(delta);
+        self.ticks.push_back((now, ticks));
+        while self.ticks.front().is_some_and(|(time, _)| now.duration_since(*time) > Duration::from_secs(1)) {
+            self.ticks.pop_front();
+        }
+    }
+
+    /// Frames per second, over the frames of the graph
+    pub fn fps(&self) -> f32 {
+        let total: f32 = self.frame_times.iter().sum();
+        if total > 0. {
+            self.frame_times.len() as f32 / total
+        } else {
+            0.
+        }
+    }
+
+    /// Ticks of the simulation run in the last second
+    pub fn tick_rate(&self) -> u32 {
+        self.ticks.iter().map(|(_, ticks)| ticks).sum()
+    }
+
+    /// Draws the overlay if it is visible, with the counters of the last frame finished
+    pub fn draw(&mut self, canvas: &mut Canvas, info: &DebugInfo) {
+        if !self.visible {
+            return;
+        }
+        self.draw_world(canvas, info);
+
+        let viewport = *self.viewport.get_or_insert_with(|| {
+            let interface = &mut *canvas.interface;
+            let mut camera = Camera::new(Rect::UNIT, interface.backend.dimensions());
+            camera.policy = ScalePolicy::Screen;
+            interface.add_viewport(Rect::UNIT, camera)
+        });
+        let Some((_, height)) = canvas.interface.viewport(viewport).map(|viewport| viewport.camera.viewport()) else {
+            return;
+        };
+        canvas.set_viewport(viewport);
+        let top_left = vec2!(8_f32, height as f32 - 8.);
+        let text = self.lines(canvas, info).join("\n");
+        let lines = text.lines().count() as f32;
+        let panel = Rect::from_corners(
+            top_left + vec2!(-4_f32, -lines * Self::FONT_SIZE - Self::GRAPH_HEIGHT - 12.),
+            top_left + vec2!(Self::HISTORY as f32 * Self::BAR_WIDTH + 4., 4.),
+        );
+        canvas.draw(SolidColorObject {
+            region: panel,
+            color: Rgba::new(0, 0, 0, 180),
+            rotation: 0.,
+            pivot: Vec2::ZERO,
+            layer: Self::LAYER,
+            blend: BlendMode::Alpha,
+        });
+        canvas.draw(TextObject {
+            text,
+            position: top_left,
+            color: Rgba::WHITE,
+            font_size: Self::FONT_SIZE,
+            font: FontId::DEFAULT,
+            layer: Self::LAYER,
+            blend: BlendMode::Alpha,
+            layout: TextLayout::default(),
+        });
+        self.draw_graph(canvas, vec2!(top_left.x, panel.bottom() + 4.));
+        canvas.set_viewport(ViewportId::MAIN);
+    }
+
+    /// The lines of text of the overlay
+    fn lines(&self, canvas: &Canvas, info: &DebugInfo) -> Vec<String> {
+        let interface = &*canvas.interface;
+        let stats = interface.frame_stats;
+        let (frame, worst) = (self.frame_times.back().copied().unwrap_or(0.), self.worst_frame());
+        let system = |name: &str, stats: SystemStats| {
+            format!("  {name:<9}{:>4} calls {:>7} vertices", stats.draw_calls, stats.vertices)
+        };
+        let rect = |rect: Rect| {
+            format!("({:.1}, {:.1}) {:.1}x{:.1}", rect.center.x, rect.center.y, rect.size.x, rect.size.y)
+        };
+        let cache = stats.glyph_cache;
+        let camera = &interface.camera;
+        vec![
+            format!("{:.0} FPS  {:.2} ms  worst {:.2} ms", self.fps(), frame * 1000., worst * 1000.),
+            format!("{} ticks/s", self.tick_rate()),
+            format!("{} objects  {} draw calls", stats.objects, stats.draw_calls),
+            system("color", stats.color),
+            system("image", stats.image),
+            system("text", stats.text),
+            system("material", stats.material),
+            system("post", stats.post),
+            format!(
+                "glyph cache {} glyphs  {:.1}% of {}x{}  {} uploaded",
+                cache.glyphs,
+                cache.usage() * 100.,
+                cache.width,
+                cache.height,
+                cache.uploaded
+            ),
+            format!("scene {}", info.scene),
+            format!("camera {}", rect(camera.world)),
+            format!("goal   {}", rect(camera.goal)),
+            format!("mouse  ({:.1}, {:.1})", info.mouse.x, info.mouse.y),
+        ]
+    }
+
+    /// Longest frame of the graph, in seconds
+    fn worst_frame(&self) -> f32 {
+        self.frame_times.iter().copied().fold(0., f32::max)
+    }
+
+    /// Bars of the durations of the frames from `origin` to the right, green up to 60 FPS, yellow up to 30 and
+    /// red for longer ones, with a line at 60 FPS
+    fn draw_graph(&self, canvas: &mut Canvas, origin: Vec2) {
+        for (i, &time) in self.frame_times.iter().enumerate() {
+            let height = (time * 1000. * Self::GRAPH_SCALE).min(Self::GRAPH_HEIGHT);
+            let color = if time <= Self::TARGET_FRAME * 1.05 {
+                Rgba::new(80, 220, 80, 255)
+            } else if time <= Self::TARGET_FRAME * 2.1 {
+                Rgba::new(230, 210, 60, 255)
+            } else {
+                Rgba::new(230, 60, 60, 255)
+            };
+            let left = i as f32 * Self::BAR_WIDTH;
+            canvas.draw(SolidColorObject {
+                region: Rect::from_corners(origin + vec2!(left, 0_f32), origin + vec2!(left + Self::BAR_WIDTH - 1., height)),
+                color,
+                rotation: 0.,
+                pivot: Vec2::ZERO,
+                layer: Self::LAYER,
+                blend: BlendMode::Alpha,
+            });
+        }
+        let target = Self::TARGET_FRAME * 1000. * Self::GRAPH_SCALE;
+        canvas.draw(SolidColorObject {
+            region: Rect::from_corners(
+                origin + vec2!(0_f32, target),
+                origin + vec2!(Self::HISTORY as f32 * Self::BAR_WIDTH, target + 1.),
+            ),
+            color: Rgba::new(255, 255, 255, 160),
+            rotation: 0.,
+            pivot: Vec2::ZERO,
+            layer: Self::LAYER,
+            blend: BlendMode::Alpha,
+        });
+    }
+
+    /// Outlines of the hitboxes with their names and of the goal of the main camera, in the world
+    fn draw_world(&self, canvas: &mut Canvas, info: &DebugInfo) {
+        let camera = &canvas.interface.camera;
+        let (_, height) = camera.viewport();
+        // Units of the world in a pixel of the window
+        let pixel = 2. / (camera.scale().y * height.max(1) as f32);
+        let goal = camera.goal;
+        let outline = |region: Rect, color: Rgba| ShapeObject {
+            shape: Shape::Polyline {
+                points: vec![
+                    vec2!(region.left(), region.bottom()),
+                    vec2!(region.right(), region.bottom()),
+                    vec2!(region.right(), region.top()),
+                    vec2!(region.left(), region.top()),
+                ],
+                thickness: pixel * 1.5,
+                closed: true,
+            },
+            color,
+            layer: Self::LAYER,
+            blend: BlendMode::Alpha,
+        };
+        canvas.draw(outline(goal, Rgba::new(80, 160, 255, 255)));
+        for &(name, region) in &info.regions {
+            canvas.draw(outline(region, Rgba::new(255, 0, 255, 255)));
+            canvas.draw(TextObject {
+                text: name.to_owned(),
+                position: vec2!(region.left(), region.top() + pixel * (Self::FONT_SIZE + 2.)),
+                color: Rgba::new(255, 0, 255, 255),
+                font_size: Self::FONT_SIZE * pixel,
+                font: FontId::DEFAULT,
+                layer: Self::LAYER,
+                blend: BlendMode::Alpha,
+                layout: TextLayout::default(),
+            });
+        }
+    }
+}
@@ -0,0 +1,156 @@
+//! Errors of the interface, from creating the window to drawing a frame
+use std::{error::Error, fmt};
+
+use glium::{
+    backend::glutin::DisplayCreationError, framebuffer::ValidationError, texture::TextureCreationError,
+    vertex::BufferCreationError, DrawError, IncompatibleOpenGl, SwapBuffersError,
+};
+
+use super::{atlas::SheetError, systems::ShaderError};
+
+/// Something the interface could not do, with a message saying what the player can do about it
+#[derive(Debug)]
+pub enum GuiError {
+    /// A shader could not be compiled or linked, not even the embedded version
+    Shader(ShaderError),
+    /// The window or the OpenGL context could not be created
+    Context(Box<dyn Error>),
+    /// The OpenGL context without a window, used by the golden images, could not be created
+    Headless(Box<dyn Error>),
+    /// An image could not be decoded or uploaded as a texture, or the descriptor of a sprite sheet is invalid
+    Texture(Box<dyn Error>),
+    /// A font could not be read, with the path of the font
+    Font(String),
+    /// The glyphs of a frame do not fit in the glyph cache, even at its largest size
+    GlyphCacheOverflow {
+        /// Width of the cache in pixels
+        width: u32,
+        /// Height of the cache in pixels
+        height: u32,
+    },
+    /// A draw call, a buffer of vertices or the presentation of a frame failed
+    Draw(Box<dyn Error>),
+}
+
+impl GuiError {
+    /// Whether the interface cannot draw anymore, so the game should close instead of skipping the frame
+    ///
+    /// A lost context, or a window, shader or font that could not be created, fail again in every frame,
+    /// while a texture, the glyph cache or a draw call may only fail in the frames that use them
+    pub fn is_fatal(&self) -> bool {
+        match self {
+            GuiError::Context(_) | GuiError::Headless(_) | GuiError::Shader(_) | GuiError::Font(_) => true,
+            GuiError::Draw(err) => matches!(err.downcast_ref(), Some(SwapBuffersError::ContextLost)),
+            GuiError::Texture(_) | GuiError::GlyphCacheOverflow { .. } => false,
+        }
+    }
+}
+
+impl fmt::Display for GuiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GuiError::Shader(err) => write!(
+                f,
+                "could not compile the {err}. Fix the shader in the assets directory or delete it to use the \
+                 embedded one"
+            ),
+            GuiError::Context(err) => write!(
+                f,
+                "could not create the window: {err}. Blocos needs OpenGL 3.1, update the graphics drivers or run \
+                 with LIBGL_ALWAYS_SOFTWARE=1 to use the software renderer of Mesa"
+            ),
+            GuiError::Headless(err) => write!(
+                f,
+                "could not create the offscreen context: {err}. Drawing without a window uses EGL from Mesa, \
+                 install it (libegl-mesa0 on Debian and Ubuntu)"
+            ),
+            GuiError::Texture(err) => write!(
+                f,
+                "could not create a texture: {err}. Check that the images and sprite sheets of the assets directory \
+                 are valid and that the graphics card has memory left"
+            ),
+            GuiError::Font(path) => write!(
+                f,
+                "could not read the font {path}. Put a valid TrueType font there or delete it to use the embedded one"
+            ),
+            GuiError::GlyphCacheOverflow { width, height } => write!(
+                f,
+                "the glyphs of the frame do not fit in the glyph cache of {width}x{height} pixels, draw fewer or \
+                 smaller texts"
+            ),
+            GuiError::Draw(err) => write!(f, "could not draw the frame: {err}"),
+        }
+    }
+}
+
+impl Error for GuiError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            GuiError::Shader(err) => Some(err),
+            GuiError::Context(err) | GuiError::Headless(err) | GuiError::Texture(err) | GuiError::Draw(err) => {
+                Some(err.as_ref())
+            }
+            GuiError::Font(_) | GuiError::GlyphCacheOverflow { .. } => None,
+        }
+    }
+}
+
+impl From<ShaderError> for GuiError {
+    fn from(err: ShaderError) -> Self {
+        GuiError::Shader(err)
+    }
+}
+
+impl From<DisplayCreationError> for GuiError {
+    fn from(err: DisplayCreationError) -> Self {
+        GuiError::Context(err.into())
+    }
+}
+
+impl From<IncompatibleOpenGl> for GuiError {
+    fn from(err: IncompatibleOpenGl) -> Self {
+        GuiError::Context(err.into())
+    }
+}
+
+impl From<TextureCreationError> for GuiError {
+    fn from(err: TextureCreationError) -> Self {
+        GuiError::Texture(err.into())
+    }
+}
+
+impl From<SheetError> for GuiError {
+    fn from(err: SheetError) -> Self {
+        GuiError::Texture(err.into())
+    }
+}
+
+impl From<image::ImageError> for GuiError {
+    fn from(err: image::ImageError) -> Self {
+        GuiError::Texture(err.into())
+    }
+}
+
+impl From<DrawError> for GuiError {
+    fn from(err: DrawError) -> Self {
+        GuiError::Draw(err.into())
+    }
+}
+
+impl From<SwapBuffersError> for GuiError {
+    fn from(err: SwapBuffersError) -> Self {
+        GuiError::Draw(err.into())
+    }
+}
+
+impl From<ValidationError> for GuiError {
+    fn from(err: ValidationError) -> Self {
+        GuiError::Draw(err.into())
+    }
+}
+
+impl From<BufferCreationError> for GuiError {
+    fn from(err: BufferCreationError) -> Self {
+        GuiError::Draw(err.into())
+    }
+}
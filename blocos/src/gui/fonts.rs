@@ -0,0 +1,168 @@
+//! The fonts available to the texts, named by family and style, with fallbacks for the characters they lack
+use rusttype::{Font, Glyph};
+
+use super::{assets::Assets, GuiError};
+
+/// Identifies a font of a [FontRegistry]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct FontId(usize);
+
+impl FontId {
+    /// The font always present in the registry, and the last fallback of every font
+    pub const DEFAULT: FontId = FontId(0);
+
+    /// Index of the font, used as the font id of the glyph cache
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+/// Weight and slant of a font of a family
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum FontStyle {
+    /// Normal weight, upright
+    #[default]
+    Regular,
+    /// Heavy weight, upright
+    Bold,
+    /// Normal weight, slanted
+    Italic,
+    /// Heavy weight, slanted
+    BoldItalic,
+}
+
+struct RegisteredFont {
+    family: String,
+    style: FontStyle,
+    font: Font<'static>,
+    /// Fonts searched, in order, for the characters this one does not have
+    fallbacks: Vec<FontId>,
+}
+
+/// The fonts loaded, named by family and style
+pub struct FontRegistry {
+    fonts: Vec<RegisteredFont>,
+}
+
+impl FontRegistry {
+    /// The font embedded as fallback for the default one in the assets
+    pub const DEFAULT_FONT: &'static [u8] = include_bytes!("../assets/UbuntuMono-R.ttf");
+    /// Path of the default font in the assets
+    pub const DEFAULT_FONT_PATH: &'static str = "UbuntuMono-R.ttf";
+    /// Family of the default font
+    pub const DEFAULT_FAMILY: &'static str = "mono";
+    /// Fonts loaded from the assets when their files exist, with no embedded version
+    const OPTIONAL_FONTS: [(&'static str, FontStyle, &'static str); 4] = [
+        ("mono", FontStyle::Bold, "fonts/UbuntuMono-B.ttf"),
+        ("mono", FontStyle::Italic, "fonts/UbuntuMono-RI.ttf"),
+        ("mono", FontStyle::BoldItalic, "fonts/UbuntuMono-BI.ttf"),
+        ("symbols", FontStyle::Regular, "fonts/symbols.ttf"),
+    ];
+
+    /// Create a registry with only the default font, of the family [FontRegistry::DEFAULT_FAMILY]
+    pub fn new(default: Font<'static>) -> FontRegistry {
+        FontRegistry {
+            fonts: vec![RegisteredFont {
+                family: Self::DEFAULT_FAMILY.to_owned(),
+                style: FontStyle::Regular,
+                font: default,
+                fallbacks: Vec::new(),
+            }],
+        }
+    }
+
+    /// Loads the default font and the optional fonts found in the assets
+    ///
+    /// The `symbols` font, when present, is a fallback of every other font.
+    /// Fails if neither the default font of the assets nor the embedded one can be read
+    pub fn from_assets(assets: &Assets) -> Result<FontRegistry, GuiError> {
+        let default = Font::try_from_vec(assets.font(Self::DEFAULT_FONT_PATH, Self::DEFAULT_FONT))
+            .or_else(|| {
+                eprintln!("could not load the font {}, using the embedded version", Self::DEFAULT_FONT_PATH);
+                Font::try_from_bytes(Self::DEFAULT_FONT)
+            })
+            .ok_or_else(|| GuiError::Font(assets.dir().join(Self::DEFAULT_FONT_PATH).display().to_string()))?;
+        let mut registry = FontRegistry::new(default);
+        for (family, style, path) in Self::OPTIONAL_FONTS {
+            let Some(data) = assets.read(path) else {
+                continue;
+            };
+            match Font::try_from_vec(data) {
+                Some(font) => {
+                    registry.register(family, style, font);
+                }
+                None => eprintln!("could not load the font {path}"),
+            }
+        }
+        if let Some(symbols) = registry.find("symbols", FontStyle::Regular) {
+            for index in 0..registry.fonts.len() {
+                if FontId(index) != symbols {
+                    registry.fonts[index].fallbacks.push(symbols);
+                }
+            }
+        }
+        Ok(registry)
+    }
+
+    /// Adds a font, replacing the one with the same family and style
+    pub fn register(&mut self, family: &str, style: FontStyle, font: Font<'static>) -> FontId {
+        if let Some(id) = self.find(family, style) {
+            self.fonts[id.0].font = font;
+            return id;
+        }
+        self.fonts.push(RegisteredFont {
+            family: family.to_owned(),
+            style,
+            font,
+            fallbacks: Vec::new(),
+        });
+        FontId(self.fonts.len() - 1)
+    }
+
+    /// The font of a family and style, if it was registered
+    pub fn find(&self, family: &str, style: FontStyle) -> Option<FontId> {
+        self.fonts
+            .iter()
+            .position(|font| font.family == family && font.style == style)
+            .map(FontId)
+    }
+
+    /// The font of a family and style, or the regular one of the family if it is missing, or else the default font
+    pub fn get(&self, family: &str, style: FontStyle) -> FontId {
+        self.find(family, style)
+            .or_else(|| self.find(family, FontStyle::Regular))
+            .unwrap_or(FontId::DEFAULT)
+    }
+
+    /// Sets the fonts searched, in order, for the characters `font` does not have, before the default font
+    pub fn set_fallbacks(&mut self, font: FontId, fallbacks: Vec<FontId>) {
+        self.fonts[font.0].fallbacks = fallbacks;
+    }
+
+    /// The font of an id
+    pub fn font(&self, font: FontId) -> &Font<'static> {
+        &self.fonts[font.0].font
+    }
+
+    /// The glyph of a character, from the first font of the fallback chain of `font` that has it
+    ///
+    /// The chain is `font`, its fallbacks and the default font; if none has the character, the missing glyph of `font`
+    /// is returned
+    pub fn glyph(&self, font: FontId, c: char) -> (FontId, Glyph<'static>) {
+        let chain = std::iter::once(font)
+            .chain(self.fonts[font.0].fallbacks.iter().copied())
+            .chain(std::iter::once(FontId::DEFAULT));
+        for id in chain {
+            let glyph = self.font(id).glyph(c);
+            if glyph.id().0 != 0 {
+                return (id, glyph);
+            }
+        }
+        (font, self.font(font).glyph(c))
+    }
+
+    /// Whether a font of the fallback chain of `font` has a character
+    pub fn has_glyph(&self, font: FontId, c: char) -> bool {
+        self.glyph(font, c).1.id().0 != 0
+    }
+}
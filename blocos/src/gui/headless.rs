@@ -0,0 +1,82 @@
+//! An OpenGL context with no window and no surface, from EGL on the surfaceless platform of Mesa
+//!
+//! It works with no display server and no GPU, with the software rasterizer of Mesa (llvmpipe),
+//! so the golden images render the same on a CI box as on a desktop. The frames go to a texture.
+use std::{ffi::c_void, ptr, rc::Rc};
+
+use glium::{
+    backend::{self, Context},
+    debug::DebugCallbackBehavior,
+    SwapBuffersError,
+};
+use khronos_egl as egl;
+
+use super::GuiError;
+
+/// `EGL_PLATFORM_SURFACELESS_MESA`, a display with no native window system
+const PLATFORM_SURFACELESS: egl::Enum = 0x31DD;
+
+/// The EGL context used by glium, current in the thread that created it
+struct SurfacelessContext {
+    egl: egl::DynamicInstance<egl::EGL1_5>,
+    display: egl::Display,
+    context: egl::Context,
+    /// Size of the frames, there is no default framebuffer to take it from
+    size: (u32, u32),
+}
+
+/// Creates a context with no window whose frames are `width` x `height`
+pub(crate) fn surfaceless_context(width: u32, height: u32) -> Result<Rc<Context>, GuiError> {
+    // Safety: libEGL is a system library, loading it runs no code other than its initializers
+    let egl = unsafe { egl::DynamicInstance::<egl::EGL1_5>::load_required() }
+        .map_err(|err| GuiError::Headless(err.into()))?;
+    let display = egl
+        .get_platform_display(PLATFORM_SURFACELESS, ptr::null_mut(), &[egl::ATTRIB_NONE])
+        .map_err(|err| GuiError::Headless(err.into()))?;
+    egl.initialize(display).map_err(|err| GuiError::Headless(err.into()))?;
+    egl.bind_api(egl::OPENGL_API).map_err(|err| GuiError::Headless(err.into()))?;
+    let config = egl
+        .choose_first_config(display, &[egl::RENDERABLE_TYPE, egl::OPENGL_BIT, egl::SURFACE_TYPE, 0, egl::NONE])
+        .map_err(|err| GuiError::Headless(err.into()))?
+        .ok_or_else(|| GuiError::Headless("no EGL config renders OpenGL without a surface".into()))?;
+    let context = egl
+        .create_context(display, config, None, &[egl::NONE])
+        .map_err(|err| GuiError::Headless(err.into()))?;
+    egl.make_current(display, None, None, Some(context)).map_err(|err| GuiError::Headless(err.into()))?;
+    let backend = SurfacelessContext { egl, display, context, size: (width, height) };
+    // Safety: the context was just made current in this thread, and glium keeps it current while it is used
+    unsafe { Context::new(backend, false, DebugCallbackBehavior::default()) }.map_err(|err| GuiError::Headless(err.into()))
+}
+
+unsafe impl backend::Backend for SurfacelessContext {
+    fn swap_buffers(&self) -> Result<(), SwapBuffersError> {
+        // The frames go to a texture, there is nothing to present
+        Ok(())
+    }
+
+    unsafe fn get_proc_address(&self, symbol: &str) -> *const c_void {
+        self.egl.get_proc_address(symbol).map_or(ptr::null(), |function| function as *const c_void)
+    }
+
+    fn get_framebuffer_dimensions(&self) -> (u32, u32) {
+        self.size
+    }
+
+    fn is_current(&self) -> bool {
+        self.egl.get_current_context() == Some(self.context)
+    }
+
+    unsafe fn make_current(&self) {
+        // glium has no way to report the failure here, a context that cannot be current fails the next draw
+        if let Err(err) = self.egl.make_current(self.display, None, None, Some(self.context)) {
+            eprintln!("could not make the offscreen context current: {err}");
+        }
+    }
+}
+
+impl Drop for SurfacelessContext {
+    fn drop(&mut self) {
+        let _ = self.egl.make_current(self.display, None, None, None);
+        let _ = self.egl.destroy_context(self.display, self.context);
+    }
+}
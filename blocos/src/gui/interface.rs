@@ -1,29 +1,94 @@
 //! Module for accessing the interface, with wrappers for communicating with the interface
-use std::rc::Rc;
+use std::{
+    io,
+    ops::Range,
+    path::{Path, PathBuf},
+    rc::Rc,
+    thread::JoinHandle,
+    time::Instant,
+};
 
 use glium::{
-    glutin::{self, event_loop, window},
-    texture::Texture2dDataSource,
-    Display, Frame,
+    framebuffer::SimpleFrameBuffer,
+    glutin::event_loop,
+    texture::{SrgbTexture2d, Texture2dDataSource},
+    Display, Surface,
 };
+use image::RgbaImage;
 
-use crate::vec2;
-use crate::vector2::Vec2;
+use crate::{core::rgb::Rgba, vec2};
 
 use super::{
-    systems::{Systems, ObjectWrapper},
-    transform::{self, *},
-    Rect,
+    assets::{Assets, TextureHandle},
+    atlas::SpriteSheet,
+    backend::Backend,
+    capture::{self, Recorder},
+    fonts::{FontId, FontStyle},
+    systems::{
+        BlendMode, GlyphCacheStats, Material, MaterialId, MaterialUniform, ObjectWrapper, PostProcess, ShaderError,
+        Systems, TextObject,
+    },
+    transform,
+    window::WindowConfig,
+    Camera, DrawTarget, GuiError, Rect, RenderTarget, RenderTargetId, Viewport, ViewportId,
 };
 
 /// `Interface` struct is used to encapsulate the display, and camera.
 pub struct Interface {
-    /// The `display` represents the display window.
-    pub display: Display,
-    /// The `camera` represents the camera view.
+    /// The `backend` represents the display window, or the offscreen image when headless.
+    pub backend: Backend,
+    /// The `camera` represents the camera view, of the viewport [ViewportId::MAIN] covering the whole window.
     pub camera: Camera,
+    /// The viewports added over the main one, drawn after it in order
+    viewports: Vec<Viewport>,
+    /// The textures the canvas can draw in
+    render_targets: Vec<RenderTarget>,
+    /// The effects applied to the window after the objects of the frame are drawn
+    pub post_process: PostProcess,
     /// Systems for drawing elements on the screen
-    pub systems: Systems
+    pub systems: Systems,
+    /// Textures, fonts and shaders loaded from the assets directory
+    pub assets: Assets,
+    /// Counters of the last frame finished
+    pub frame_stats: FrameStats,
+    /// The config of the window, kept when headless
+    window_config: WindowConfig,
+    /// Keeps the frames presented while recording a clip
+    recorder: Option<Recorder>,
+    /// Whether the next frame finished is kept for [Interface::read_pixels]
+    capture_next: bool,
+    /// The last frame kept
+    captured: Option<RgbaImage>,
+}
+
+/// Counters of the work done to draw a frame
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FrameStats {
+    /// Number of objects drawn on the canvas
+    pub objects: usize,
+    /// Number of draw calls sent to the GPU
+    pub draw_calls: usize,
+    /// Work of the color system, of solid color objects and shapes
+    pub color: SystemStats,
+    /// Work of the image system, of images and nine-slice panels
+    pub image: SystemStats,
+    /// Work of the text system
+    pub text: SystemStats,
+    /// Work of the material system
+    pub material: SystemStats,
+    /// Work of the post-processing passes
+    pub post: SystemStats,
+    /// How the glyph cache of the text system was used
+    pub glyph_cache: GlyphCacheStats,
+}
+
+/// Counters of the work of a system in a frame
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SystemStats {
+    /// Number of draw calls of the system
+    pub draw_calls: usize,
+    /// Number of vertices drawn by the system
+    pub vertices: usize,
 }
 
 
@@ -31,23 +96,30 @@ impl Interface {
     /// Creates a new display for the event loop.
     ///
     /// This function takes a reference to an event loop and creates a new display for it with
-    /// certain properties such as decorations, maximized, resizable, title, and always on top.
+    /// the title, size, fullscreen, icon, vsync and multisampling of `config`.
+    /// When the OpenGL context cannot be created with multisampling, it is created without it.
     ///
     /// # Examples
     ///
     /// ```
     /// let event_loop = event_loop::EventLoop::new();
-    /// let display = Interface::create_display(&event_loop);
+    /// let display = Interface::create_display(&event_loop, &WindowConfig::default())?;
     /// ```
-    pub(crate) fn create_display(event_loop: &event_loop::EventLoop<()>) -> Display {
-        let wb: window::WindowBuilder = window::WindowBuilder::new()
-            .with_decorations(true)
-            .with_maximized(true)
-            .with_resizable(false)
-            .with_title("hello")
-            .with_always_on_top(false);
-        let cb = glutin::ContextBuilder::new();
-        Display::new(wb, cb, event_loop).unwrap()
+    pub(crate) fn create_display(
+        event_loop: &event_loop::EventLoop<()>,
+        config: &WindowConfig,
+    ) -> Result<Display, GuiError> {
+        let wb = config.window_builder(event_loop.primary_monitor());
+        match Display::new(wb, config.context_builder(), event_loop) {
+            Ok(display) => Ok(display),
+            Err(err) if config.samples > 0 => {
+                eprintln!("could not create the window with {} samples, creating it without them: {err}", config.samples);
+                let config = WindowConfig { samples: 0, ..config.clone() };
+                let wb = config.window_builder(event_loop.primary_monitor());
+                Ok(Display::new(wb, config.context_builder(), event_loop)?)
+            }
+            Err(err) => Err(err.into()),
+        }
     }
 
     /// Creates a new `Interface` instance.
@@ -59,28 +131,70 @@ impl Interface {
     ///
     /// ```
     /// let event_loop = event_loop::EventLoop::new();
-    /// let interface = Interface::create(&event_loop);
+    /// let interface = Interface::create(&event_loop, WindowConfig::default())?;
     /// ```
-    pub fn create(event_loop: &event_loop::EventLoop<()>) -> Interface {
-        let display = Self::create_display(event_loop);
-        let dims = display.get_framebuffer_dimensions();
-        let camera = Camera {
-            world: Rect {
-                center: vec2!(0., 0.),
-                size: vec2!(100., 100.),
-            },
-            target: Rect {
-                center: vec2!(0., 0.),
-                size: Vec2 {
-                    x: (dims.1 as f32) / (dims.0 as f32),
-                    y: 1.,
-                },
-            },
+    pub fn create(event_loop: &event_loop::EventLoop<()>, config: WindowConfig) -> Result<Interface, GuiError> {
+        let display = Self::create_display(event_loop, &config)?;
+        let mut interface = Self::with_backend(Backend::Window(display))?;
+        interface.window_config = config;
+        Ok(interface)
+    }
+
+    /// Creates an `Interface` with no window, drawing in a `width` x `height` image.
+    ///
+    /// The frames can be read with [Interface::read_pixels] after [Canvas::finish].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut interface = Interface::headless(640, 480)?;
+    /// interface.capture_next_frame();
+    /// let mut canvas = interface.draw();
+    /// canvas.finish()?;
+    /// interface.read_pixels().unwrap().save("frame.png")?;
+    /// ```
+    pub fn headless(width: u32, height: u32) -> Result<Interface, GuiError> {
+        Self::with_backend(Backend::headless(width, height)?)
+    }
+
+    fn with_backend(backend: Backend) -> Result<Interface, GuiError> {
+        let dims = backend.dimensions();
+        let world = Rect {
+            center: vec2!(0_f32, 0.),
+            size: vec2!(200_f32, 200.),
         };
+        let camera = Camera::new(world, dims);
                 
-        let systems = Systems::new(&display);
+        let mut assets = Assets::new(Assets::default_dir());
+        let systems = Systems::new(&backend, &mut assets)?;
+
+        Ok(Interface {
+            camera,
+            viewports: Vec::new(),
+            render_targets: Vec::new(),
+            post_process: PostProcess::default(),
+            backend, systems, assets, frame_stats: FrameStats::default(),
+            window_config: WindowConfig::default(), recorder: None, capture_next: false, captured: None })
+    }
 
-        Interface { camera, display, systems }
+    /// The config of the window
+    pub fn window_config(&self) -> &WindowConfig {
+        &self.window_config
+    }
+
+    /// Changes the config of the window, applying it to the open window
+    ///
+    /// Returns whether some change, of the vsync or the multisampling, only applies when the game is started again
+    pub fn set_window_config(&mut self, config: WindowConfig) -> bool {
+        if config == self.window_config {
+            return false;
+        }
+        let restart = match &self.backend {
+            Backend::Window(display) => config.apply(&self.window_config, display.gl_window().window()),
+            Backend::Headless { .. } => false,
+        };
+        self.window_config = config;
+        restart
     }
 
     /// Draws the interface.
@@ -94,37 +208,253 @@ impl Interface {
     /// let interface = Interface::create(&event_loop);
     /// let canvas = interface.draw();
     /// ```
-    pub fn draw(&mut self) -> Canvas {
-
+    pub fn draw(&mut self) -> Canvas<'_> {
+        self.systems.clear();
         Canvas {
-            target: self.display.draw(),
-            interface: self
+            interface: self,
+            clear_color: None,
+            objects: Vec::new(),
+            target: DrawTarget::Viewport(ViewportId::MAIN),
         }
     }
+    /// Adds a texture of `width` x `height` pixels showing `world`, where the canvas draws after [Canvas::set_target]
+    pub fn add_render_target(
+        &mut self,
+        width: u32,
+        height: u32,
+        world: Rect,
+    ) -> Result<RenderTargetId, GuiError> {
+        self.render_targets.push(RenderTarget::new(&self.backend, width, height, world)?);
+        Ok(RenderTargetId(self.render_targets.len() - 1))
+    }
+    /// A render target added with [Interface::add_render_target]
+    pub fn render_target(&self, id: RenderTargetId) -> &RenderTarget {
+        &self.render_targets[id.0]
+    }
+    /// A render target to change its camera or clear color, see [Interface::render_target]
+    pub fn render_target_mut(&mut self, id: RenderTargetId) -> &mut RenderTarget {
+        &mut self.render_targets[id.0]
+    }
+    /// The texture of a render target, to draw it as an [ImageObject](super::systems::ImageObject)
+    pub fn render_texture(&self, id: RenderTargetId) -> Rc<SrgbTexture2d> {
+        self.render_targets[id.0].texture.clone()
+    }
+    /// Adds a viewport covering `region` of the window, from (0, 0) at the bottom left to (1, 1) at the top right
+    ///
+    /// The objects drawn in it after [Canvas::set_viewport] use its camera and are cut to its region
+    pub fn add_viewport(&mut self, region: Rect, camera: Camera) -> ViewportId {
+        let mut viewport = Viewport { camera, region };
+        let area = viewport.area(self.backend.dimensions());
+        viewport.camera.resize(area.width, area.height);
+        self.viewports.push(viewport);
+        ViewportId(self.viewports.len())
+    }
+    /// Removes the viewports added with [Interface::add_viewport], keeping the main one
+    pub fn clear_viewports(&mut self) {
+        self.viewports.clear();
+    }
+    /// A viewport added with [Interface::add_viewport], `None` for [ViewportId::MAIN], which is [Interface::camera]
+    pub fn viewport(&self, id: ViewportId) -> Option<&Viewport> {
+        self.viewports.get(id.index()?)
+    }
+    /// A viewport to change its camera or region, see [Interface::viewport]
+    pub fn viewport_mut(&mut self, id: ViewportId) -> Option<&mut Viewport> {
+        self.viewports.get_mut(id.index()?)
+    }
+    /// The camera of a viewport, the main camera if there is no viewport with the id
+    pub fn viewport_camera(&self, id: ViewportId) -> &Camera {
+        viewport_camera(&self.camera, &self.viewports, id)
+    }
+    /// Changes the size of the window in pixels, resizing the cameras of all viewports
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.camera.resize(width, height);
+        for viewport in &mut self.viewports {
+            let area = viewport.area((width, height));
+            viewport.camera.resize(area.width, area.height);
+        }
+    }
+    /// Moves the cameras of all viewports toward their goals by `delta` seconds, see [Camera::update]
+    ///
+    /// Regions of viewports changed since the last call are applied to their cameras
+    pub fn update_cameras(&mut self, delta: f32) {
+        self.camera.update(delta);
+        let dims = self.backend.dimensions();
+        for viewport in &mut self.viewports {
+            let area = viewport.area(dims);
+            if viewport.camera.viewport() != (area.width, area.height) {
+                viewport.camera.resize(area.width, area.height);
+            }
+            viewport.camera.update(delta);
+        }
+    }
+    /// Keeps the next frame finished, read before it is presented, for [Interface::read_pixels]
+    pub fn capture_next_frame(&mut self) {
+        self.capture_next = true;
+    }
+    /// The last frame kept with [Interface::capture_next_frame]
+    pub fn read_pixels(&self) -> Option<RgbaImage> {
+        self.captured.clone()
+    }
+    /// Saves the last frame kept with [Interface::capture_next_frame] to `dir` as a timestamped PNG in another
+    /// thread, see [capture::save_screenshot]
+    ///
+    /// `None` if no frame was kept
+    pub fn screenshot(&self, dir: &Path) -> Option<JoinHandle<io::Result<PathBuf>>> {
+        let image = self.read_pixels()?;
+        Some(capture::save_screenshot(image, dir))
+    }
+    /// Starts capturing the frames finished with `recorder`, replacing the recording in progress
+    pub fn start_recording(&mut self, recorder: Recorder) {
+        self.recorder = Some(recorder);
+    }
+    /// Whether the frames are being captured
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+    /// Stops capturing frames and saves the clip to `dir` in another thread, see [Recorder::save]
+    ///
+    /// `None` if there was no recording or it captured no frame
+    pub fn stop_recording(&mut self, dir: &Path) -> Option<JoinHandle<io::Result<PathBuf>>> {
+        let mut recorder = self.recorder.take()?;
+        (!recorder.is_empty()).then(|| recorder.save(dir))
+    }
+    /// Loads a texture from the assets, with the embedded `fallback` used when the file is missing
+    ///
+    /// The texture of the handle is found with [Interface::texture], and changes when the file is reloaded
+    pub fn load_texture(&self, path: &str, fallback: &'static [u8]) -> Result<TextureHandle, GuiError> {
+        self.assets.load_texture(&self.backend, path, fallback)
+    }
+    /// The current texture of a handle
+    pub fn texture(&self, handle: TextureHandle) -> Rc<SrgbTexture2d> {
+        self.assets.texture(handle)
+    }
+    /// Loads a sprite sheet from the assets, its `image` and its `descriptor`, using the embedded fallbacks when
+    /// the files are missing
+    ///
+    /// The sheet keeps the texture loaded now, it does not change when the image is reloaded
+    pub fn load_sprite_sheet(
+        &self,
+        image: &str,
+        descriptor: &str,
+        fallback_image: &'static [u8],
+        fallback_descriptor: &'static str,
+    ) -> Result<SpriteSheet, GuiError> {
+        let texture = self.texture(self.load_texture(image, fallback_image)?);
+        Ok(SpriteSheet::parse(&self.assets.text(descriptor, fallback_descriptor), texture)?)
+    }
+    /// Loads an image from the assets to be packed with an [AtlasBuilder](super::atlas::AtlasBuilder), with the
+    /// embedded `fallback` used when the file is missing
+    ///
+    /// The atlas keeps the image loaded now, the file is returned by [Interface::reload_assets] when it changes so
+    /// the atlas can be built again
+    pub fn load_image(&self, path: &str, fallback: &'static [u8]) -> Result<RgbaImage, GuiError> {
+        self.assets.image(path, fallback)
+    }
+    /// Reads a text file from the assets, like the descriptor of a sprite sheet, with the embedded `fallback` used
+    /// when the file is missing
+    ///
+    /// The file is returned by [Interface::reload_assets] when it changes
+    pub fn load_text(&self, path: &str, fallback: &'static str) -> String {
+        self.assets.text(path, fallback)
+    }
+    /// The font of a family and style, falling back to the regular style and then to the default font
+    pub fn font(&self, family: &str, style: FontStyle) -> FontId {
+        self.systems.text_system.fonts.get(family, style)
+    }
+    /// Loads a font from the assets and registers it, `None` if the file is missing or is not a font
+    pub fn load_font(&mut self, family: &str, style: FontStyle, path: &str) -> Option<FontId> {
+        let font = rusttype::Font::try_from_vec(self.assets.read(path)?)?;
+        Some(self.systems.text_system.fonts.register(family, style, font))
+    }
+    /// Compiles the shader of a material, so [MaterialObject](super::systems::MaterialObject)s can be drawn with it
+    ///
+    /// To reload the shader when its files change, take its source from [Assets::shader] with the name of the material
+    pub fn register_material(&mut self, material: Material) -> Result<MaterialId, ShaderError> {
+        self.systems.material_system.register(&self.backend, material)
+    }
+    /// The region of the world covered by a text, to place it before drawing
+    pub fn measure_text(&self, object: &TextObject) -> Rect {
+        self.systems.text_system.measure(object)
+    }
+    /// Reloads the textures and shaders whose files changed, only in debug builds
+    ///
+    /// Returns the files read with [Interface::load_image] and [Interface::load_text] that changed, relative to the
+    /// directory of the assets, for what was built from them to be built again
+    pub fn reload_assets(&mut self) -> Vec<String> {
+        let changes = self.assets.poll(&self.backend);
+        for (name, source) in changes.shaders {
+            self.systems.reload_shader(&self.backend, name, &source);
+        }
+        changes.files
+    }
     /// Extract the data from datasource and wrap in a [Rc]
-    pub fn create_texture<'a, T>(&self, source: T) -> Rc<glium::texture::SrgbTexture2d>
+    pub fn create_texture<'a, T>(&self, source: T) -> Result<Rc<glium::texture::SrgbTexture2d>, GuiError>
     where
         T: Texture2dDataSource<'a>,
     {
-        Rc::new(glium::texture::SrgbTexture2d::new(&self.display, source).unwrap())
+        Ok(Rc::new(glium::texture::SrgbTexture2d::new(&self.backend, source)?))
+    }
+
+}
+
+
+/// The camera of a viewport, the main camera if there is no viewport with the id
+fn viewport_camera<'c>(main: &'c Camera, viewports: &'c [Viewport], id: ViewportId) -> &'c Camera {
+    match id.index().and_then(|index| viewports.get(index)) {
+        Some(viewport) => &viewport.camera,
+        None => main,
     }
+}
+
+/// What a batch of objects is drawn with, consecutive objects with the same kind are drawn together
+enum BatchKind {
+    Color,
+    Image(Rc<SrgbTexture2d>),
+    Text,
+    Material(MaterialId, Vec<(String, MaterialUniform)>),
+}
 
+impl BatchKind {
+    fn same(&self, other: &BatchKind) -> bool {
+        match (self, other) {
+            (BatchKind::Color, BatchKind::Color) | (BatchKind::Text, BatchKind::Text) => true,
+            (BatchKind::Image(a), BatchKind::Image(b)) => Rc::ptr_eq(a, b),
+            (BatchKind::Material(a, a_uniforms), BatchKind::Material(b, b_uniforms)) => a == b && a_uniforms == b_uniforms,
+            _ => false,
+        }
+    }
 }
 
+/// Objects of the same target, system, texture and blending next to each other once sorted, drawn with a single draw call
+struct Batch {
+    target: DrawTarget,
+    kind: BatchKind,
+    blend: BlendMode,
+    /// Indices of the objects in their system
+    objects: Range<usize>,
+}
 
 /// `Canvas` struct is used for drawing objects on the `Interface`.
+///
+/// The objects are collected during the frame and only drawn in [Canvas::finish], sorted by viewport, layer and
+/// [ObjectWrapper::batch_order], in batches
 pub struct Canvas<'a> {
-    /// Represents the frame where the objects will be drawn.
-    pub target: Frame,
     /// Represents the interface where the objects will be drawn.
     pub interface: &'a mut Interface,
+    /// Color that fills the frame before the objects are drawn
+    clear_color: Option<Rgba>,
+    /// The objects of the frame with where they are drawn, in the order they were drawn
+    objects: Vec<(DrawTarget, ObjectWrapper)>,
+    /// Where the objects drawn next go
+    target: DrawTarget,
 }
 
 impl<'a> Canvas<'a> {
     /// Draws an object on the canvas.
     ///
-    /// This function takes a reference to an object and draws it on the canvas.
-    /// It uses the transformation of the camera to determine the position of the object.
+    /// This function takes a reference to an object and adds it to the frame.
+    /// When the canvas is finished, it is drawn over the objects of lower layers and the ones of its layer drawn before it
+    /// with the same system, texture and blending.
     ///
     /// # Examples
     ///
@@ -135,30 +465,291 @@ impl<'a> Canvas<'a> {
     /// let object = Object::new(...);
     /// canvas.draw_obj(&object);
     /// ```
-
     pub fn draw<T : Into<ObjectWrapper>>(&mut  self, source: T) {
-        let camera_transform: transform::Transform =
-            self.interface.camera.transformation();
-        let systems = &mut self.interface.systems;
-        let wrapper = Into::<ObjectWrapper>::into(source);
-        match wrapper {
-            ObjectWrapper::SolidColorObject(object) => systems.color_system.draw(
-                &mut self.target,
-                &self.interface.display,
-                camera_transform,
-                object,
-            ),
-            ObjectWrapper::ImageObject(object) => systems.image_system.draw(
-                &mut self.target,
-                &self.interface.display,
-                camera_transform,
-                object,
-            ),
-            ObjectWrapper::TextObject(object) =>
-                systems.text_system.draw(&mut self.target, &self.interface.display,&self.interface.camera, object)
+        self.objects.push((self.target, source.into()));
+    }
+
+    /// Draws the next objects in a viewport, with its camera and cut to its region
+    ///
+    /// Every viewport is drawn over the ones added before it, the canvas starts in [ViewportId::MAIN]
+    pub fn set_viewport(&mut self, viewport: ViewportId) {
+        self.target = DrawTarget::Viewport(viewport);
+    }
+
+    /// Draws the next objects in a render target or a viewport
+    ///
+    /// The render targets are drawn before the window, so their textures can be drawn in the viewports
+    pub fn set_target<T: Into<DrawTarget>>(&mut self, target: T) {
+        self.target = target.into();
+    }
+
+    /// The region of the world covered by a text, see [Interface::measure_text]
+    pub fn measure_text(&self, object: &TextObject) -> Rect {
+        self.interface.measure_text(object)
+    }
+
+    /// Fills the whole frame with a color, under every object
+    pub fn clear(&mut self, color: Rgba) {
+        self.clear_color = Some(color);
+    }
+
+    /// Draws the objects of the frame and presents it.
+    ///
+    /// The objects are sorted by layer and grouped in batches, the vertices of each system are uploaded once,
+    /// then each batch is a single draw call.
+    /// The render targets are drawn first, then the window, in a texture when there are post process effects
+    /// that are applied to it on the way to the window.
+    /// The counters of the frame are kept in [Interface::frame_stats].
+    ///
+    /// The frame asked with [Interface::capture_next_frame] and the ones of the recording are read
+    /// before the frame is presented.
+    ///
+    /// The objects are removed even when the frame fails, so the next one starts empty.
+    pub fn finish(mut self) -> Result<(), GuiError> {
+        let batches = self.batches();
+        let mut stats = FrameStats::default();
+        let now = Instant::now();
+        let record = self.interface.recorder.as_ref().is_some_and(|recorder| recorder.wants_frame(now));
+        let capture = std::mem::take(&mut self.interface.capture_next);
+        if capture {
+            // A frame that fails or cannot be read leaves nothing, instead of the one kept before
+            self.interface.captured = None;
+        }
+        let result = self.render(&batches, &mut stats, capture || record);
+        let systems = &self.interface.systems;
+        stats.color.vertices = systems.color_system.vertex_count();
+        stats.image.vertices = systems.image_system.vertex_count();
+        stats.text.vertices = systems.text_system.vertex_count();
+        stats.material.vertices = systems.material_system.vertex_count();
+        stats.glyph_cache = systems.text_system.cache_stats();
+        self.interface.systems.clear();
+        self.interface.frame_stats = stats;
+        if let Some(image) = result? {
+            if capture {
+                self.interface.captured = Some(image.clone());
+            }
+            if let Some(recorder) = self.interface.recorder.as_mut().filter(|_| record) {
+                recorder.push(now, image);
+            }
+        }
+        Ok(())
+    }
+
+    /// Uploads the vertices of the frame and draws the batches on the render targets and the window
+    ///
+    /// Returns the pixels of the frame when `read` is set and they could be read
+    fn render(&mut self, batches: &[Batch], stats: &mut FrameStats, read: bool) -> Result<Option<RgbaImage>, GuiError> {
+        let Interface { backend, systems, camera, viewports, render_targets, post_process, .. } = &mut *self.interface;
+        systems.upload(backend)?;
+        for group in batches.chunk_by(|a, b| a.target == b.target) {
+            let DrawTarget::Texture(id) = group[0].target else {
+                continue;
+            };
+            let render_target = &render_targets[id.0];
+            let mut frame = SimpleFrameBuffer::new(&*backend, &*render_target.texture)?;
+            if let Some(color) = render_target.clear_color {
+                let [r, g, b, a] = color.to_array();
+                frame.clear_color(r, g, b, a);
+            }
+            let bar_color = render_target.clear_color.unwrap_or(Rgba::TRANSPARENT);
+            Self::draw_batches(&mut frame, systems, &render_target.camera, None, bar_color, group, stats)?;
+        }
+
+        let post = post_process.is_active()
+            && match systems.post_system.prepare(backend, backend.dimensions()) {
+                Ok(()) => true,
+                Err(err) => {
+                    eprintln!("could not create the textures of the post process, drawing without it: {err}");
+                    false
+                }
+            };
+        let post_process = post.then_some(&*post_process);
+        let (backend, systems, clear_color) = (&*backend, &*systems, self.clear_color);
+        match backend {
+            Backend::Window(display) => {
+                let mut frame = display.draw();
+                let drawn =
+                    Self::present(&mut frame, backend, systems, camera, viewports, post_process, clear_color, batches, stats);
+                // Read from the back buffer, the buffers are undefined once the frame is swapped
+                let image = (read && drawn.is_ok()).then(|| backend.read_frame(&frame)).flatten();
+                // The frame must be finished even when a draw call failed, dropping it unfinished panics
+                let finished = frame.finish();
+                drawn?;
+                finished?;
+                Ok(image)
+            }
+            Backend::Headless { renderer, target } => {
+                let mut frame = SimpleFrameBuffer::new(renderer, target)?;
+                Self::present(&mut frame, backend, systems, camera, viewports, post_process, clear_color, batches, stats)?;
+                Ok(read.then(|| backend.read_frame(&frame)).flatten())
+            }
         }
     }
 
+    /// Draws the viewports on the window, through the enabled passes of `post_process` if there is one
+    #[allow(clippy::too_many_arguments)]
+    fn present<S: Surface>(
+        frame: &mut S,
+        backend: &Backend,
+        systems: &Systems,
+        camera: &Camera,
+        viewports: &[Viewport],
+        post_process: Option<&PostProcess>,
+        clear_color: Option<Rgba>,
+        batches: &[Batch],
+        stats: &mut FrameStats,
+    ) -> Result<(), GuiError> {
+        match (post_process, systems.post_system.scene()) {
+            (Some(post_process), Some(scene)) => {
+                let mut scene = SimpleFrameBuffer::new(backend, scene)?;
+                Self::flush(&mut scene, systems, camera, viewports, clear_color, batches, stats)?;
+                let passes = systems.post_system.apply(frame, backend, post_process)?;
+                stats.draw_calls += passes;
+                stats.post = SystemStats { draw_calls: passes, vertices: passes * systems.post_system.quad_vertices() };
+                Ok(())
+            }
+            _ => Self::flush(frame, systems, camera, viewports, clear_color, batches, stats),
+        }
+    }
+
+    /// Sorts the objects of the frame by target, layer and [ObjectWrapper::batch_order] and gives them to their systems,
+    /// grouping the objects of the same target, system, texture and blending that end up next to each other
+    fn batches(&mut self) -> Vec<Batch> {
+        let mut objects = std::mem::take(&mut self.objects);
+        // The sort is stable, so the objects that share a draw call keep the order they were drawn in
+        objects.sort_by_key(|(target, object)| (*target, object.layer(), object.batch_order()));
+
+        let mut batches: Vec<Batch> = Vec::new();
+        let Interface { backend, systems, camera, viewports, render_targets, .. } = &mut *self.interface;
+        for (target, object) in objects {
+            let camera = match target {
+                DrawTarget::Texture(id) => &render_targets[id.0].camera,
+                DrawTarget::Viewport(id) => viewport_camera(camera, viewports, id),
+            };
+            let blend = object.blend();
+            let (kind, index) = match object {
+                ObjectWrapper::SolidColorObject(object) => (BatchKind::Color, systems.color_system.push(object)),
+                ObjectWrapper::ShapeObject(object) => (BatchKind::Color, systems.color_system.push_shape(&object)),
+                ObjectWrapper::ImageObject(object) => {
+                    (BatchKind::Image(object.texture.clone()), systems.image_system.push(&object))
+                }
+                ObjectWrapper::NineSliceObject(object) => {
+                    (BatchKind::Image(object.texture.clone()), systems.image_system.push_nine_slice(&object))
+                }
+                ObjectWrapper::TextObject(object) => (
+                    BatchKind::Text,
+                    systems.text_system.push(backend, camera, object),
+                ),
+                ObjectWrapper::MaterialObject(object) => {
+                    let index = systems.material_system.push(&object);
+                    (BatchKind::Material(object.material, object.uniforms), index)
+                }
+            };
+            match batches.last_mut() {
+                Some(batch)
+                    if batch.target == target
+                        && batch.kind.same(&kind)
+                        && batch.blend == blend
+                        && batch.objects.end == index =>
+                {
+                    batch.objects.end += 1
+                }
+                _ => batches.push(Batch {
+                    target,
+                    kind,
+                    blend,
+                    objects: index..index + 1,
+                }),
+            }
+        }
+        batches
+    }
+
+    /// Issues the draw calls of the batches of the window on the target, adding them to the counters of the frame
+    ///
+    /// The batches of each viewport are drawn with its camera and cut to its area
+    fn flush<S: Surface>(
+        target: &mut S,
+        systems: &Systems,
+        camera: &Camera,
+        viewports: &[Viewport],
+        clear_color: Option<Rgba>,
+        batches: &[Batch],
+        stats: &mut FrameStats,
+    ) -> Result<(), GuiError> {
+        if let Some(color) = clear_color {
+            let [r, g, b, a] = color.to_array();
+            target.clear_color(r, g, b, a);
+        }
+        let bar_color = clear_color.unwrap_or(Rgba::BLACK);
+        let dims = target.get_dimensions();
+        for group in batches.chunk_by(|a, b| a.target == b.target) {
+            let DrawTarget::Viewport(id) = group[0].target else {
+                continue;
+            };
+            // The main viewport covers the whole window, so it is not cut
+            let area = id.index().and_then(|index| viewports.get(index)).map(|viewport| viewport.area(dims));
+            Self::draw_batches(target, systems, viewport_camera(camera, viewports, id), area, bar_color, group, stats)?;
+        }
+        Ok(())
+    }
+
+    /// Issues the draw calls of batches with the same camera, then clears its letterbox bars with `bar_color`
+    fn draw_batches<S: Surface>(
+        target: &mut S,
+        systems: &Systems,
+        camera: &Camera,
+        area: Option<glium::Rect>,
+        bar_color: Rgba,
+        batches: &[Batch],
+        stats: &mut FrameStats,
+    ) -> Result<(), GuiError> {
+        let camera_transform: transform::Transform = camera.transformation();
+        for batch in batches {
+            let objects = batch.objects.clone();
+            stats.objects += objects.len();
+            stats.draw_calls += 1;
+            let mut parameters = batch.blend.draw_parameters();
+            parameters.viewport = area;
+            parameters.scissor = area;
+            let system = match batch.kind {
+                BatchKind::Color => &mut stats.color,
+                BatchKind::Image(_) => &mut stats.image,
+                BatchKind::Text => &mut stats.text,
+                BatchKind::Material(..) => &mut stats.material,
+            };
+            system.draw_calls += 1;
+            match &batch.kind {
+                BatchKind::Color => {
+                    systems.color_system.draw(target, camera_transform, batch.blend, &parameters, objects)?
+                }
+                BatchKind::Image(texture) => {
+                    systems.image_system.draw(target, camera_transform, texture, batch.blend, &parameters, objects)?
+                }
+                BatchKind::Text => {
+                    systems.text_system.draw(target, camera_transform, batch.blend, &parameters, objects)?
+                }
+                BatchKind::Material(material, uniforms) => systems.material_system.draw(
+                    target,
+                    camera_transform,
+                    *material,
+                    uniforms,
+                    batch.blend,
+                    &parameters,
+                    objects,
+                )?,
+            }
+        }
+        // The bars are cleared after drawing, hiding the objects outside the region of the camera
+        let [r, g, b, a] = bar_color.to_array();
+        let (left, bottom) = area.map_or((0, 0), |area| (area.left, area.bottom));
+        for bar in camera.letterbox() {
+            let bar = glium::Rect { left: bar.left + left, bottom: bar.bottom + bottom, ..bar };
+            target.clear(Some(&bar), Some((r, g, b, a)), false, None, None);
+        }
+        Ok(())
+    }
+
     /// Draws all objects of a iterator on the canvas.
     ///
     /// This function takes an iterator of objects and draws them on the canvas.
@@ -173,10 +764,158 @@ impl<'a> Canvas<'a> {
     /// let objects = vec![Object::new(...), Object::new(...)];
     /// canvas.draw_ider(objects.into_iter());
     /// ```
-
     pub fn draw_iter<I: Into<ObjectWrapper> ,T: IntoIterator<Item = I>>(&mut self, iter: T) {
         for object in iter.into_iter().collect::<Vec<I>>() {
             self.draw(object);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use glium::texture::{MipmapsOption, SrgbFormat};
+
+    use super::*;
+    use crate::gui::{
+        assets::ShaderSource,
+        systems::{ImageObject, MaterialObject, SolidColorObject, VertexLayout},
+    };
+
+    /// A material with the `tint` uniform and the shader `vertex`, whose vertices have only their position
+    fn tint_material(vertex: &str) -> Material {
+        Material {
+            name: "tint".to_owned(),
+            shader: ShaderSource {
+                vertex: vertex.to_owned(),
+                fragment: "#version 140\nout vec4 color;\nuniform vec4 tint;\nvoid main() { color = tint; }".to_owned(),
+            },
+            layout: VertexLayout::Position,
+            uniforms: vec![("tint".to_owned(), MaterialUniform::Color(Rgba::WHITE))],
+        }
+    }
+
+    const POSITION_SHADER: &str = "#version 140
+in vec2 position;
+uniform mat4 matrix;
+void main() { gl_Position = matrix * vec4(position, 0.0, 1.0); }";
+
+    #[test]
+    fn objects_of_a_layer_are_batched_by_system_and_texture() {
+        let mut interface = Interface::headless(8, 8).unwrap();
+        let texture = |interface: &Interface| {
+            let texture = SrgbTexture2d::empty_with_format(
+                &interface.backend,
+                SrgbFormat::U8U8U8U8,
+                MipmapsOption::NoMipmap,
+                1,
+                1,
+            );
+            Rc::new(texture.unwrap())
+        };
+        let (a, b) = (texture(&interface), texture(&interface));
+        let color = || SolidColorObject {
+            region: Rect::UNIT,
+            color: Rgba::WHITE,
+            rotation: 0.,
+            pivot: vec2!(0_f32, 0.),
+            layer: 0,
+            blend: BlendMode::Alpha,
+        };
+        let image = |texture: &Rc<SrgbTexture2d>| ImageObject {
+            region: Rect::UNIT,
+            texture: texture.clone(),
+            source: Rect::UNIT,
+            rotation: 0.,
+            pivot: vec2!(0_f32, 0.),
+            layer: 0,
+            tint: Rgba::WHITE,
+            blend: BlendMode::Alpha,
+        };
+
+        let mut canvas = interface.draw();
+        for _ in 0..3 {
+            canvas.draw(color());
+            canvas.draw(image(&a));
+            canvas.draw(image(&b));
+        }
+        canvas.finish().unwrap();
+        assert_eq!(interface.frame_stats.objects, 9);
+        assert_eq!(interface.frame_stats.draw_calls, 3);
+    }
+
+    #[test]
+    fn objects_of_a_material_are_batched_while_their_uniforms_are_equal() {
+        let mut interface = Interface::headless(8, 8).unwrap();
+        let material = interface.register_material(tint_material(POSITION_SHADER)).unwrap();
+        let object = |color: Rgba| MaterialObject {
+            material,
+            region: Rect::UNIT,
+            color: Rgba::WHITE,
+            rotation: 0.,
+            pivot: vec2!(0_f32, 0.),
+            uniforms: vec![("tint".to_owned(), MaterialUniform::Color(color))],
+            layer: 0,
+            blend: BlendMode::Alpha,
+        };
+
+        let mut canvas = interface.draw();
+        canvas.draw(object(Rgba::BLACK));
+        canvas.draw(object(Rgba::BLACK));
+        canvas.finish().unwrap();
+        assert_eq!(interface.frame_stats.objects, 2);
+        assert_eq!(interface.frame_stats.draw_calls, 1);
+
+        let mut canvas = interface.draw();
+        canvas.draw(object(Rgba::BLACK));
+        canvas.draw(object(Rgba::WHITE));
+        canvas.finish().unwrap();
+        assert_eq!(interface.frame_stats.objects, 2);
+        assert_eq!(interface.frame_stats.draw_calls, 2);
+    }
+
+    #[test]
+    fn a_material_with_an_attribute_out_of_its_layout_is_rejected() {
+        let mut interface = Interface::headless(8, 8).unwrap();
+        let vertex = "#version 140
+in vec2 position;
+in vec2 offset;
+uniform mat4 matrix;
+void main() { gl_Position = matrix * vec4(position + offset, 0.0, 1.0); }";
+        let err = interface.register_material(tint_material(vertex)).unwrap_err();
+        assert_eq!(err.name, "tint");
+        assert!(err.message.contains("offset"), "{}", err.message);
+    }
+
+    #[test]
+    fn a_material_that_does_not_compile_is_rejected() {
+        let mut interface = Interface::headless(8, 8).unwrap();
+        let err = interface.register_material(tint_material("#version 140\nvoid main() {")).unwrap_err();
+        assert_eq!(err.name, "tint");
+    }
+
+    #[test]
+    fn multiply_keeps_what_is_under_a_transparent_pixel() {
+        let mut interface = Interface::headless(8, 8).unwrap();
+        let grey = Rgba::new(200, 200, 200, 255);
+        let mut multiply = |color: Rgba| {
+            interface.capture_next_frame();
+            let mut canvas = interface.draw();
+            canvas.clear(grey);
+            canvas.draw(SolidColorObject {
+                region: Rect { center: vec2!(0_f32, 0.), size: vec2!(400_f32, 400.) },
+                color,
+                rotation: 0.,
+                pivot: vec2!(0_f32, 0.),
+                layer: 0,
+                blend: BlendMode::Multiply,
+            });
+            canvas.finish().unwrap();
+            interface.read_pixels().unwrap().get_pixel(4, 4).0[0]
+        };
+        assert_eq!(multiply(Rgba::new(255, 255, 255, 0)), 200);
+        assert_eq!(multiply(Rgba::new(255, 255, 255, 128)), 200);
+        let half = multiply(Rgba::new(0, 0, 0, 128));
+        assert!(half > 0 && half < 200, "half a black pixel gave {half}");
+        assert_eq!(multiply(Rgba::BLACK), 0);
+    }
+}
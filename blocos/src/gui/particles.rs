@@ -0,0 +1,373 @@
+//! Particles, many small objects thrown by emitters that live for a short time, for sparks, dust and debris
+//!
+//! The particles of an emitter share the layer, the blending and the texture, so the canvas draws them all
+//! in a single batch
+use std::{f32::consts::TAU, rc::Rc};
+
+use glium::texture::SrgbTexture2d;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{core::rgb::Rgba, vec2, vector2::Vec2};
+
+use super::{
+    interface::Canvas,
+    systems::{BlendMode, ImageObject, Layer, SolidColorObject},
+    Rect,
+};
+
+/// How the particles of an emitter look and move
+#[derive(Debug, Clone)]
+pub struct ParticleSettings {
+    /// Shortest time a particle lives, in seconds
+    pub min_lifetime: f32,
+    /// Longest time a particle lives, in seconds
+    pub max_lifetime: f32,
+    /// Smallest speed of a particle when it is emitted, in units of the world per second
+    pub min_speed: f32,
+    /// Largest speed of a particle when it is emitted, in units of the world per second
+    pub max_speed: f32,
+    /// Angle of the direction the particles are thrown in, in radians counterclockwise from the right
+    pub direction: f32,
+    /// The particles are thrown up to this angle away from the direction, [TAU] throws them everywhere
+    pub spread: f32,
+    /// Size of the region around the emitter where the particles appear
+    pub area: Vec2,
+    /// Acceleration of the particles, in units of the world per second squared
+    pub gravity: Vec2,
+    /// Fraction of the speed lost per second
+    pub drag: f32,
+    /// Color of a particle when it is emitted
+    pub start_color: Rgba,
+    /// Color of a particle when it dies, the color goes from the start one to it over the life of the particle
+    pub end_color: Rgba,
+    /// Size of a particle when it is emitted, in units of the world
+    pub start_size: f32,
+    /// Size of a particle when it dies
+    pub end_size: f32,
+    /// Largest speed a particle turns with, in radians per second
+    pub max_spin: f32,
+    /// Texture of the particles, multiplied by their color, or squares of their color with `None`
+    pub texture: Option<Rc<SrgbTexture2d>>,
+    /// The layer of the particles
+    pub layer: Layer,
+    /// How the particles are blended with what is under them
+    pub blend: BlendMode,
+}
+
+impl Default for ParticleSettings {
+    fn default() -> Self {
+        ParticleSettings {
+            min_lifetime: 0.5,
+            max_lifetime: 1.,
+            min_speed: 10.,
+            max_speed: 20.,
+            direction: 0.,
+            spread: TAU,
+            area: Vec2::ZERO,
+            gravity: Vec2::ZERO,
+            drag: 0.,
+            start_color: Rgba::WHITE,
+            end_color: Rgba::new(255, 255, 255, 0),
+            start_size: 1.,
+            end_size: 1.,
+            max_spin: 0.,
+            texture: None,
+            layer: 0,
+            blend: BlendMode::Alpha,
+        }
+    }
+}
+
+/// When an emitter throws its particles
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Emission {
+    /// Throws a number of particles at once in the first update, then only the ones of [Emitter::burst]
+    Burst(usize),
+    /// Throws particles all the time, a number per second
+    Continuous(f32),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Particle {
+    position: Vec2,
+    velocity: Vec2,
+    rotation: f32,
+    spin: f32,
+    /// Seconds since the particle was emitted
+    age: f32,
+    lifetime: f32,
+}
+
+/// Throws particles with the same settings from a position
+#[derive(Debug)]
+pub struct Emitter {
+    /// How the particles look and move
+    pub settings: ParticleSettings,
+    /// Center of the region where the particles appear
+    pub position: Vec2,
+    /// When the particles are thrown
+    pub emission: Emission,
+    /// Whether the emitter throws particles continuously, the particles already thrown live on when it stops
+    pub active: bool,
+    particles: Vec<Particle>,
+    /// Particles of the continuous emission owed by the last updates, less than one
+    owed: f32,
+    /// Whether the particles of [Emission::Burst] were thrown
+    burst_done: bool,
+    rng: StdRng,
+}
+
+impl Emitter {
+    /// Create an emitter at `position`, which throws its first particles in the next update
+    ///
+    /// The particles are thrown at random from `seed`, so the same seed throws them the same way
+    pub fn new(settings: ParticleSettings, position: Vec2, emission: Emission, seed: u64) -> Emitter {
+        Emitter {
+            settings,
+            position,
+            emission,
+            active: true,
+            particles: Vec::new(),
+            owed: 0.,
+            burst_done: false,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Throws `count` particles at once from the position of the emitter
+    pub fn burst(&mut self, count: usize) {
+        self.burst_at(self.position, count);
+    }
+
+    /// Throws `count` particles at once from a position, without moving the emitter
+    pub fn burst_at(&mut self, position: Vec2, count: usize) {
+        self.particles.reserve(count);
+        for _ in 0..count {
+            let particle = self.spawn(position);
+            self.particles.push(particle);
+        }
+    }
+
+    /// Number of particles alive
+    pub fn len(&self) -> usize {
+        self.particles.len()
+    }
+
+    /// Whether there are no particles alive
+    pub fn is_empty(&self) -> bool {
+        self.particles.is_empty()
+    }
+
+    /// Whether the emitter will not throw more particles by itself and all of its particles died
+    pub fn is_finished(&self) -> bool {
+        let emitting = match self.emission {
+            Emission::Burst(_) => !self.burst_done,
+            Emission::Continuous(_) => self.active,
+        };
+        !emitting && self.particles.is_empty()
+    }
+
+    /// Moves and ages the particles by `delta` seconds, removing the dead ones and throwing the new ones
+    pub fn update(&mut self, delta: f32) {
+        let ParticleSettings { gravity, drag, .. } = self.settings;
+        self.particles.retain_mut(|particle| {
+            particle.age += delta;
+            particle.velocity = (particle.velocity + gravity * delta) * (1. - drag * delta).max(0.);
+            particle.position += particle.velocity * delta;
+            particle.rotation += particle.spin * delta;
+            particle.age < particle.lifetime
+        });
+
+        match self.emission {
+            Emission::Burst(count) if !self.burst_done => {
+                self.burst_done = true;
+                self.burst(count);
+            }
+            Emission::Continuous(rate) if self.active => {
+                self.owed += rate * delta;
+                let count = self.owed.floor();
+                self.owed -= count;
+                self.burst(count as usize);
+            }
+            _ => (),
+        }
+    }
+
+    /// Draws the particles alive, all in the same batch of the canvas
+    pub fn draw(&self, canvas: &mut Canvas) {
+        let settings = &self.settings;
+        for particle in &self.particles {
+            let (size, color) = self.appearance(particle);
+            let region = Rect {
+                center: particle.position,
+                size: vec2!(size, size),
+            };
+            match &settings.texture {
+                Some(texture) => canvas.draw(ImageObject {
+                    region,
+                    texture: texture.clone(),
+                    source: Rect::UNIT,
+                    rotation: particle.rotation,
+                    pivot: Vec2::ZERO,
+                    layer: settings.layer,
+                    tint: color,
+                    blend: settings.blend,
+                }),
+                None => canvas.draw(SolidColorObject {
+                    region,
+                    color,
+                    rotation: particle.rotation,
+                    pivot: Vec2::ZERO,
+                    layer: settings.layer,
+                    blend: settings.blend,
+                }),
+            }
+        }
+    }
+
+    /// The size and the color of a particle, going from the start ones to the end ones over its life
+    fn appearance(&self, particle: &Particle) -> (f32, Rgba) {
+        let settings = &self.settings;
+        let life = particle.age / particle.lifetime;
+        let size = settings.start_size + (settings.end_size - settings.start_size) * life;
+        (size, settings.start_color.lerp(settings.end_color, life))
+    }
+
+    /// A new particle at a random point of the area around `position`
+    fn spawn(&mut self, position: Vec2) -> Particle {
+        let settings = &self.settings;
+        let rng = &mut self.rng;
+        let mut between = |min: f32, max: f32| min + (max - min) * rng.gen::<f32>();
+        let offset = vec2!(between(-0.5, 0.5) * settings.area.x, between(-0.5, 0.5) * settings.area.y);
+        let angle = settings.direction + between(-settings.spread, settings.spread) / 2.;
+        let speed = between(settings.min_speed, settings.max_speed);
+        Particle {
+            position: position + offset,
+            velocity: vec2!(angle.cos(), angle.sin()) * speed,
+            rotation: between(0., TAU),
+            spin: between(-settings.max_spin, settings.max_spin),
+            age: 0.,
+            lifetime: between(settings.min_lifetime, settings.max_lifetime).max(f32::EPSILON),
+        }
+    }
+}
+
+/// The emitters of a scene, removed when they finish
+#[derive(Debug, Default)]
+pub struct ParticleSystem {
+    /// The emitters, drawn in order
+    pub emitters: Vec<Emitter>,
+}
+
+impl ParticleSystem {
+    /// Create a system without emitters
+    pub fn new() -> ParticleSystem {
+        ParticleSystem::default()
+    }
+
+    /// Adds an emitter, returning it to throw more particles or stop it
+    pub fn add(&mut self, emitter: Emitter) -> &mut Emitter {
+        self.emitters.push(emitter);
+        self.emitters.last_mut().unwrap()
+    }
+
+    /// Updates the emitters by `delta` seconds, removing the ones that finished
+    pub fn update(&mut self, delta: f32) {
+        for emitter in &mut self.emitters {
+            emitter.update(delta);
+        }
+        self.emitters.retain(|emitter| !emitter.is_finished());
+    }
+
+    /// Draws the particles of every emitter
+    pub fn draw(&self, canvas: &mut Canvas) {
+        for emitter in &self.emitters {
+            emitter.draw(canvas);
+        }
+    }
+
+    /// Removes every emitter and their particles
+    pub fn clear(&mut self) {
+        self.emitters.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Particles that live exactly `lifetime` seconds
+    fn settings(lifetime: f32) -> ParticleSettings {
+        ParticleSettings {
+            min_lifetime: lifetime,
+            max_lifetime: lifetime,
+            ..ParticleSettings::default()
+        }
+    }
+
+    #[test]
+    fn a_burst_throws_its_particles_in_the_first_update_only() {
+        let mut emitter = Emitter::new(settings(5.), Vec2::ZERO, Emission::Burst(10), 1);
+        assert!(emitter.is_empty());
+        emitter.update(0.);
+        assert_eq!(emitter.len(), 10);
+        emitter.update(0.1);
+        assert_eq!(emitter.len(), 10);
+        emitter.burst(3);
+        assert_eq!(emitter.len(), 13);
+    }
+
+    #[test]
+    fn a_continuous_emission_throws_its_rate_per_second() {
+        let mut emitter = Emitter::new(settings(5.), Vec2::ZERO, Emission::Continuous(6.), 1);
+        for _ in 0..8 {
+            emitter.update(0.125);
+        }
+        assert_eq!(emitter.len(), 6);
+        emitter.active = false;
+        emitter.update(1.);
+        assert_eq!(emitter.len(), 6);
+    }
+
+    #[test]
+    fn the_particles_die_at_the_end_of_their_lifetime() {
+        let mut emitter = Emitter::new(settings(0.5), Vec2::ZERO, Emission::Burst(4), 1);
+        emitter.update(0.);
+        emitter.update(0.25);
+        assert_eq!(emitter.len(), 4);
+        assert!(!emitter.is_finished());
+        emitter.update(0.25);
+        assert!(emitter.is_empty());
+        assert!(emitter.is_finished());
+    }
+
+    #[test]
+    fn the_color_and_the_size_go_from_the_start_to_the_end_ones() {
+        let settings = ParticleSettings {
+            start_color: Rgba::new(0, 0, 0, 255),
+            end_color: Rgba::new(200, 100, 50, 55),
+            start_size: 1.,
+            end_size: 3.,
+            ..settings(2.)
+        };
+        let mut emitter = Emitter::new(settings, Vec2::ZERO, Emission::Burst(1), 1);
+        emitter.update(0.);
+        let particle = emitter.particles[0];
+        assert_eq!(emitter.appearance(&particle), (1., Rgba::new(0, 0, 0, 255)));
+        emitter.update(1.);
+        let particle = emitter.particles[0];
+        assert_eq!(emitter.appearance(&particle), (2., Rgba::new(100, 50, 25, 155)));
+    }
+
+    #[test]
+    fn the_same_seed_throws_the_same_particles() {
+        let positions = |seed: u64| {
+            let mut emitter = Emitter::new(settings(5.), Vec2::ZERO, Emission::Burst(5), seed);
+            emitter.update(0.);
+            emitter.update(0.5);
+            emitter.particles.iter().map(|particle| (particle.position.x, particle.position.y)).collect::<Vec<_>>()
+        };
+        assert_eq!(positions(3), positions(3));
+        assert_ne!(positions(3), positions(4));
+    }
+}
@@ -0,0 +1,72 @@
+//! Textures the canvas draws in, to be drawn later as images
+use std::rc::Rc;
+
+use glium::texture::{MipmapsOption, SrgbFormat, SrgbTexture2d, TextureCreationError};
+
+use crate::core::rgb::Rgba;
+
+use super::{backend::Backend, Camera, Rect, ViewportId};
+
+/// Identifies a render target of the [Interface](super::interface::Interface)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RenderTargetId(pub(super) usize);
+
+/// A texture the canvas draws in with its own camera
+///
+/// Its texture can be drawn by [ImageObject](super::systems::ImageObject)s of the same frame, but not of the
+/// objects drawn in it
+pub struct RenderTarget {
+    /// The texture drawn in, replaced only when the target is resized
+    pub texture: Rc<SrgbTexture2d>,
+    /// The camera of the objects drawn in the target, its viewport size is kept equal to the texture
+    pub camera: Camera,
+    /// Color that fills the texture before the objects of a frame are drawn, `None` draws over the last frame
+    ///
+    /// Frames with no objects in the target keep its texture as it is
+    pub clear_color: Option<Rgba>,
+}
+
+impl RenderTarget {
+    /// Creates a transparent target of `width` x `height` pixels showing `world`
+    pub fn new(display: &Backend, width: u32, height: u32, world: Rect) -> Result<RenderTarget, TextureCreationError> {
+        Ok(RenderTarget {
+            texture: Rc::new(Self::create_texture(display, width, height)?),
+            camera: Camera::new(world, (width, height)),
+            clear_color: Some(Rgba::TRANSPARENT),
+        })
+    }
+
+    /// Replaces the texture by a new one of `width` x `height` pixels, the objects drawn before are lost
+    pub fn resize(&mut self, display: &Backend, width: u32, height: u32) -> Result<(), TextureCreationError> {
+        self.texture = Rc::new(Self::create_texture(display, width, height)?);
+        self.camera.resize(width, height);
+        Ok(())
+    }
+
+    fn create_texture(display: &Backend, width: u32, height: u32) -> Result<SrgbTexture2d, TextureCreationError> {
+        SrgbTexture2d::empty_with_format(display, SrgbFormat::U8U8U8U8, MipmapsOption::NoMipmap, width, height)
+    }
+}
+
+/// Where the objects of the canvas are drawn, see [Canvas::set_target](super::interface::Canvas::set_target)
+///
+/// The render targets are drawn before the viewports, so the viewports can draw their textures
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DrawTarget {
+    /// A texture, drawn before the window
+    Texture(RenderTargetId),
+    /// A viewport of the window
+    Viewport(ViewportId),
+}
+
+impl From<RenderTargetId> for DrawTarget {
+    fn from(id: RenderTargetId) -> Self {
+        DrawTarget::Texture(id)
+    }
+}
+
+impl From<ViewportId> for DrawTarget {
+    fn from(id: ViewportId) -> Self {
+        DrawTarget::Viewport(id)
+    }
+}
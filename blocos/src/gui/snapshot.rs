@@ -0,0 +1,96 @@
+//! Comparison of rendered frames with reference images, allowing small differences between drivers
+use image::{Rgba, RgbaImage};
+
+/// Result of comparing a frame with a reference image
+#[derive(Debug)]
+pub struct Comparison {
+    /// Number of pixels with a channel that differs more than the tolerance
+    pub differing: usize,
+    /// Biggest difference of a channel found
+    pub max_difference: u8,
+    /// Image with the differing pixels in red over a darkened copy of the frame
+    pub diff: RgbaImage,
+}
+
+impl Comparison {
+    /// Whether every pixel is within the tolerance
+    pub fn matches(&self) -> bool {
+        self.differing == 0
+    }
+}
+
+/// Compares `actual` with `expected`, pixel by pixel
+///
+/// A pixel differs when any of its channels differs more than `tolerance`.
+/// Images of different sizes have every pixel differing
+pub fn compare(actual: &RgbaImage, expected: &RgbaImage, tolerance: u8) -> Comparison {
+    if actual.dimensions() != expected.dimensions() {
+        return Comparison {
+            differing: (actual.width() * actual.height()).max(expected.width() * expected.height()) as usize,
+            max_difference: u8::MAX,
+            diff: actual.clone(),
+        };
+    }
+    let mut differing = 0;
+    let mut max_difference = 0;
+    let mut diff = RgbaImage::new(actual.width(), actual.height());
+    for ((a, e), d) in actual.pixels().zip(expected.pixels()).zip(diff.pixels_mut()) {
+        let difference = a.0.iter().zip(e.0).map(|(a, e)| a.abs_diff(e)).max().unwrap_or(0);
+        max_difference = max_difference.max(difference);
+        *d = if difference > tolerance {
+            differing += 1;
+            Rgba([255, 0, 0, 255])
+        } else {
+            Rgba([a[0] / 4, a[1] / 4, a[2] / 4, 255])
+        };
+    }
+    Comparison { differing, max_difference, diff }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 2 x 2 image of a single color
+    fn filled(color: [u8; 4]) -> RgbaImage {
+        RgbaImage::from_pixel(2, 2, Rgba(color))
+    }
+
+    #[test]
+    fn equal_images_match() {
+        let comparison = compare(&filled([10, 20, 30, 255]), &filled([10, 20, 30, 255]), 0);
+        assert!(comparison.matches());
+        assert_eq!(comparison.max_difference, 0);
+    }
+
+    #[test]
+    fn differences_within_the_tolerance_match() {
+        let mut actual = filled([10, 20, 30, 255]);
+        actual.put_pixel(1, 0, Rgba([12, 20, 30, 255]));
+        let comparison = compare(&actual, &filled([10, 20, 30, 255]), 2);
+        assert!(comparison.matches());
+        assert_eq!(comparison.max_difference, 2);
+    }
+
+    #[test]
+    fn differences_past_the_tolerance_are_counted_and_marked() {
+        let mut actual = filled([10, 20, 30, 255]);
+        actual.put_pixel(1, 0, Rgba([10, 20, 30, 252]));
+        actual.put_pixel(0, 1, Rgba([0, 20, 30, 255]));
+        let comparison = compare(&actual, &filled([10, 20, 30, 255]), 2);
+        assert!(!comparison.matches());
+        assert_eq!(comparison.differing, 2);
+        assert_eq!(comparison.max_difference, 10);
+        assert_eq!(comparison.diff.get_pixel(1, 0), &Rgba([255, 0, 0, 255]));
+        assert_eq!(comparison.diff.get_pixel(0, 1), &Rgba([255, 0, 0, 255]));
+        assert_eq!(comparison.diff.get_pixel(0, 0), &Rgba([2, 5, 7, 255]));
+    }
+
+    #[test]
+    fn images_of_different_sizes_never_match() {
+        let comparison = compare(&filled([0, 0, 0, 255]), &RgbaImage::new(3, 2), u8::MAX);
+        assert!(!comparison.matches());
+        assert_eq!(comparison.differing, 6);
+        assert_eq!(comparison.max_difference, u8::MAX);
+    }
+}
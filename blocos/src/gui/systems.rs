@@ -4,13 +4,98 @@
 
 pub mod color_system;
 pub mod image_system;
+pub mod material_system;
+pub mod nine_slice;
+pub mod post_system;
+pub mod shapes;
+pub mod text_layout;
 pub mod text_system;
+use std::{error::Error, fmt, ops::Range, rc::Rc};
+
 pub use color_system::*;
-use glium::Display;
+use glium::{
+    vertex::VertexBufferSlice, Blend, Program, BlendingFunction, DrawParameters, LinearBlendingFactor, Vertex, VertexBuffer,
+};
+
+use super::{
+    assets::{Assets, EmbeddedShader, ShaderSource},
+    backend::Backend,
+    fonts::FontRegistry,
+    GuiError,
+};
 pub use image_system::*;
+pub use material_system::*;
+pub use nine_slice::*;
+pub use post_system::*;
+pub use shapes::*;
+pub use text_layout::*;
 pub use text_system::*;
 
-use crate::core::rgb::Rgb;
+use crate::{vec2, vector2::Vec2};
+
+use super::{transform::Transform, Rect};
+
+/// Order in which the objects are drawn, objects of a higher layer are drawn over the ones of lower layers
+///
+/// Objects in the same layer are grouped by system, texture and blending, see [ObjectWrapper::batch_order]: only the
+/// objects of the same group are drawn in the order of the calls to [Canvas::draw](super::interface::Canvas::draw),
+/// so objects that overlap and are drawn differently need different layers
+pub type Layer = i32;
+
+/// How the color of an object is combined with what is already drawn under it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum BlendMode {
+    /// Covers what is under it according to its alpha
+    #[default]
+    Alpha,
+    /// Adds its color, weighted by its alpha, making what is under it brighter
+    Additive,
+    /// Multiplies the color under it by its color, making it darker
+    ///
+    /// The shaders fold the alpha in the color with the uniform `multiply`, so a transparent pixel multiplies by white
+    /// and leaves what is under it unchanged
+    Multiply,
+}
+
+impl BlendMode {
+    /// Whether the shaders must fold the alpha in the color, the value of their uniform `multiply`
+    pub fn multiply(self) -> bool {
+        self == BlendMode::Multiply
+    }
+
+    /// The parameters of a draw call with this blending
+    pub fn draw_parameters(self) -> DrawParameters<'static> {
+        let blend = match self {
+            BlendMode::Alpha => Blend::alpha_blending(),
+            BlendMode::Additive => Blend {
+                color: BlendingFunction::Addition {
+                    source: LinearBlendingFactor::SourceAlpha,
+                    destination: LinearBlendingFactor::One,
+                },
+                alpha: BlendingFunction::Addition {
+                    source: LinearBlendingFactor::Zero,
+                    destination: LinearBlendingFactor::One,
+                },
+                constant_value: (0., 0., 0., 0.),
+            },
+            BlendMode::Multiply => Blend {
+                color: BlendingFunction::Addition {
+                    source: LinearBlendingFactor::DestinationColor,
+                    destination: LinearBlendingFactor::Zero,
+                },
+                alpha: BlendingFunction::Addition {
+                    source: LinearBlendingFactor::Zero,
+                    destination: LinearBlendingFactor::One,
+                },
+                constant_value: (0., 0., 0., 0.),
+            },
+        };
+        DrawParameters {
+            blend,
+            ..Default::default()
+        }
+    }
+}
 
 /// A enum wrapping differents types of objects to draw in screen.
 pub enum ObjectWrapper {
@@ -20,6 +105,12 @@ pub enum ObjectWrapper {
     ImageObject(ImageObject),
     /// Wrapper for a object of a text
     TextObject(TextObject),
+    /// Wrapper for a object drawn with a material
+    MaterialObject(MaterialObject),
+    /// Wrapper for a vector shape
+    ShapeObject(ShapeObject),
+    /// Wrapper for a nine slice panel
+    NineSliceObject(NineSliceObject),
 }
 
 macro_rules! wrap {
@@ -32,38 +123,254 @@ macro_rules! wrap {
     };
 }
 
+impl ObjectWrapper {
+    /// The layer of the object
+    pub fn layer(&self) -> Layer {
+        match self {
+            ObjectWrapper::SolidColorObject(object) => object.layer,
+            ObjectWrapper::ImageObject(object) => object.layer,
+            ObjectWrapper::TextObject(object) => object.layer,
+            ObjectWrapper::MaterialObject(object) => object.layer,
+            ObjectWrapper::ShapeObject(object) => object.layer,
+            ObjectWrapper::NineSliceObject(object) => object.layer,
+        }
+    }
+
+    /// How the object is blended with what is under it
+    pub fn blend(&self) -> BlendMode {
+        match self {
+            ObjectWrapper::SolidColorObject(object) => object.blend,
+            ObjectWrapper::ImageObject(object) => object.blend,
+            ObjectWrapper::TextObject(object) => object.blend,
+            ObjectWrapper::MaterialObject(object) => object.blend,
+            ObjectWrapper::ShapeObject(object) => object.blend,
+            ObjectWrapper::NineSliceObject(object) => object.blend,
+        }
+    }
+
+    /// Order of the object among the ones of its layer, so the ones that can share a draw call end up together
+    ///
+    /// The objects are ordered by system, with the colors under the images, the images under the texts and the
+    /// materials over them, then by texture or material and then by blending
+    pub fn batch_order(&self) -> (u8, usize, BlendMode) {
+        let (system, group) = match self {
+            ObjectWrapper::SolidColorObject(_) | ObjectWrapper::ShapeObject(_) => (0, 0),
+            ObjectWrapper::ImageObject(ImageObject { texture, .. })
+            | ObjectWrapper::NineSliceObject(NineSliceObject { texture, .. }) => (1, Rc::as_ptr(texture) as usize),
+            ObjectWrapper::TextObject(_) => (2, 0),
+            ObjectWrapper::MaterialObject(object) => (3, object.material.index()),
+        };
+        (system, group, self.blend())
+    }
+}
+
 wrap! {SolidColorObject}
 wrap! {ImageObject}
 wrap! {TextObject}
+wrap! {MaterialObject}
+wrap! {ShapeObject}
+wrap! {NineSliceObject}
 /// Systems for drawing elements on the screen
 pub struct Systems {
-	/// System do draw [SolidColorObject] in screen
+	/// System do draw [SolidColorObject] and [ShapeObject] in screen
     pub color_system: ColorSystem,
-	/// System do draw [ImageObject] in screen
+	/// System do draw [ImageObject] and [NineSliceObject] in screen
     pub image_system: ImageSystem,
 	/// System do draw [TextObject] in screen
     pub text_system: TextSystem<'static>,
+	/// System do draw [MaterialObject] in screen
+    pub material_system: MaterialSystem,
+	/// System to apply the [PostProcess] to the frame
+    pub post_system: PostSystem,
 }
 
 impl Systems {
-	/// Load each `System` and initialize `Systems`
-    pub fn new(display: &Display) -> Systems {
-        let color_system = ColorSystem::new(display);
-        let image_system = ImageSystem::new(display);
-        let text_system = TextSystem::new(display).unwrap();
-        Systems {
+	/// Load each `System` and initialize `Systems`, with the shaders and font of the assets
+    pub fn new(display: &Backend, assets: &mut Assets) -> Result<Systems, GuiError> {
+        let color_system = ColorSystem::new(display, &assets.shader(ColorSystem::SHADER))?;
+        let image_system = ImageSystem::new(display, &assets.shader(ImageSystem::SHADER))?;
+        let fonts = FontRegistry::from_assets(assets)?;
+        let text_system = TextSystem::new(display, &assets.shader(TextSystem::SHADER), fonts)?;
+        let post_system = PostSystem::new(display, &assets.shader(PostSystem::SHADER))?;
+        Ok(Systems {
             color_system,
             image_system,
             text_system,
+            material_system: MaterialSystem::new(),
+            post_system,
+        })
+    }
+
+	/// Recompiles the shader called `name` from a new source, keeping the current one if it has errors
+    pub fn reload_shader(&mut self, display: &Backend, name: &str, source: &ShaderSource) {
+        let result = compile_program(display, name, source).and_then(|program| {
+            if name == ColorSystem::SHADER.name {
+                self.color_system.set_program(program);
+            } else if name == ImageSystem::SHADER.name {
+                self.image_system.program = program;
+            } else if name == TextSystem::SHADER.name {
+                self.text_system.set_program(program);
+            } else if name == PostSystem::SHADER.name {
+                self.post_system.set_program(program);
+            } else {
+                return self.material_system.reload(name, program);
+            }
+            Ok(())
+        });
+        match result {
+            Ok(()) => println!("reloaded the shader `{name}`"),
+            Err(err) => eprintln!("could not reload the {err}"),
         }
     }
+
+	/// Sends the objects of the frame of each system to the GPU
+    pub fn upload(&mut self, display: &Backend) -> Result<(), GuiError> {
+        self.color_system.upload(display)?;
+        self.image_system.upload(display)?;
+        self.text_system.upload(display)?;
+        self.material_system.upload(display)
+    }
+
+	/// Removes the objects of the frame of each system
+    pub fn clear(&mut self) {
+        self.color_system.clear();
+        self.image_system.clear();
+        self.text_system.clear();
+        self.material_system.clear();
+    }
 }
 
-fn rgb_to_arr(rgb: Rgb) -> [f32; 4] {
-    [
-        rgb.r as f32 / 255.,
-        rgb.g as f32 / 255.,
-        rgb.b as f32 / 255.,
-        1.,
-    ]
+/// The vertices of the objects of a system in the current frame, uploaded to a vertex buffer reused between frames
+///
+/// Each object pushed gets an index, so a range of consecutive objects can be drawn with a single call
+pub struct VertexStream<V: Vertex> {
+    /// Vertices of the frame not uploaded yet
+    vertices: Vec<V>,
+    /// Index of the first vertex of each object
+    starts: Vec<usize>,
+    /// The buffer in the GPU, that only grows
+    buffer: Option<VertexBuffer<V>>,
+}
+
+impl<V: Vertex> Default for VertexStream<V> {
+    /// A stream without vertices, the buffer is only allocated in the first upload
+    fn default() -> Self {
+        VertexStream {
+            vertices: Vec::new(),
+            starts: Vec::new(),
+            buffer: None,
+        }
+    }
 }
+
+impl<V: Vertex> VertexStream<V> {
+    /// Create a stream without vertices, the buffer is only allocated in the first upload
+    pub fn new() -> VertexStream<V> {
+        VertexStream::default()
+    }
+
+    /// Adds the vertices of an object, returning its index
+    pub fn push<I: IntoIterator<Item = V>>(&mut self, vertices: I) -> usize {
+        self.starts.push(self.vertices.len());
+        self.vertices.extend(vertices);
+        self.starts.len() - 1
+    }
+
+    /// Writes the vertices of the frame to the buffer, only allocating a new one if they do not fit
+    pub fn upload(&mut self, display: &Backend) -> Result<(), GuiError> {
+        if self.vertices.is_empty() {
+            return Ok(());
+        }
+        let buffer = match self.buffer.take() {
+            Some(buffer) if buffer.len() >= self.vertices.len() => buffer,
+            _ => VertexBuffer::empty_dynamic(display, self.vertices.len().next_power_of_two())?,
+        };
+        if let Some(slice) = buffer.slice(0..self.vertices.len()) {
+            slice.write(&self.vertices);
+        }
+        self.buffer = Some(buffer);
+        Ok(())
+    }
+
+    /// The vertices of a range of objects already uploaded, `None` if they have no vertices
+    pub fn slice(&self, objects: Range<usize>) -> Option<VertexBufferSlice<'_, V>> {
+        let start = self.starts.get(objects.start).copied()?;
+        let end = self.starts.get(objects.end).copied().unwrap_or(self.vertices.len());
+        if start == end {
+            return None;
+        }
+        self.buffer.as_ref()?.slice(start..end)
+    }
+
+    /// Number of vertices of the objects of the frame
+    pub fn vertex_count(&self) -> usize {
+        self.vertices.len()
+    }
+
+    /// Removes the objects of the frame, keeping the buffer
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+        self.starts.clear();
+    }
+}
+
+/// A shader that could not be compiled or linked, or that does not fit how it is drawn
+#[derive(Debug, Clone)]
+pub struct ShaderError {
+    /// Name of the shader
+    pub name: String,
+    /// What is wrong, with the log of the driver for compilation errors
+    pub message: String,
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "shader `{}`: {}", self.name, self.message)
+    }
+}
+
+impl Error for ShaderError {}
+
+/// Compiles and links a shader
+fn compile_program(display: &Backend, name: &str, source: &ShaderSource) -> Result<Program, ShaderError> {
+    Program::from_source(display, &source.vertex, &source.fragment, None).map_err(|err| ShaderError {
+        name: name.to_owned(),
+        message: err.to_string(),
+    })
+}
+
+/// Compiles a shader loaded from the assets, falling back to the embedded one if it has errors
+fn compile(display: &Backend, source: &ShaderSource, embedded: EmbeddedShader) -> Result<Program, ShaderError> {
+    compile_program(display, embedded.name, source).or_else(|err| {
+        eprintln!("could not compile the {err}, using the embedded version");
+        let embedded_source = ShaderSource {
+            vertex: embedded.vertex.to_owned(),
+            fragment: embedded.fragment.to_owned(),
+        };
+        compile_program(display, embedded.name, &embedded_source)
+    })
+}
+
+/// The positions of the two triangles of a region rotated by `rotation` radians around its center plus `pivot`
+///
+/// The corners are in the order bottom left, bottom right, top right, top right, top left, bottom left
+fn quad(region: Rect, rotation: f32, pivot: Vec2) -> [Vec2; 6] {
+    let Rect { center, size } = region;
+    let half = size / 2.;
+    let corners = [
+        center - half,
+        center + vec2!(half.x, -half.y),
+        center + half,
+        center + half,
+        center + vec2!(-half.x, half.y),
+        center - half,
+    ];
+    if rotation == 0. {
+        return corners;
+    }
+    let pivot = center + pivot;
+    let transform = Transform::default().translate(-pivot).rotate(rotation).translate(pivot);
+    corners.map(|corner| transform.apply(corner))
+}
+
+
@@ -1,13 +1,19 @@
 //! Structs and process to draw a color in the screen
 
-use glium::{implement_vertex, uniform, Display, Frame, Program, Surface};
+use std::ops::Range;
+
+use glium::{implement_vertex, uniform, DrawParameters, Program, Surface};
 
 use crate::{
-    gui::{transform, Rect},
-    vec2, core::rgb::Rgb,
+    gui::{
+        assets::{EmbeddedShader, ShaderSource},
+        backend::Backend,
+        transform, GuiError, Rect,
+    },
+    core::rgb::Rgba, vector2::Vec2,
 };
 
-use super::rgb_to_arr;
+use super::{compile, quad, BlendMode, Layer, ShaderError, ShapeObject, VertexStream};
 
 /// A struct representing a vertex.
 #[derive(Copy, Clone)]
@@ -23,40 +29,81 @@ implement_vertex!(VertexColor, position, color);
 /// Store the program and logic to draw an object of solid color in the screen.
 pub struct ColorSystem {
     program: Program,
+    /// The objects of the frame
+    stream: VertexStream<VertexColor>,
 }
 impl ColorSystem {
+    /// The shaders of the system, embedded as fallback for the ones in the assets
+    pub const SHADER: EmbeddedShader = EmbeddedShader {
+        name: "solid_color",
+        vertex: include_str!("../../assets/shaders/solid_color.vert"),
+        fragment: include_str!("../../assets/shaders/solid_color.frag"),
+    };
+
     /// Create a a new Color Sytem
     ///
-    /// Load the shaders and initialize the color System
-    pub fn new(display: &Display) -> ColorSystem {
-        let vertex_shader_src = include_str!("./shaders/solid_color.vert");
-        let fragment_shader_src = include_str!("./shaders/solid_color.frag");
-        let program =
-            glium::Program::from_source(display, vertex_shader_src, fragment_shader_src, None)
-                .unwrap();
-        ColorSystem { program }
+    /// Compile the shaders and initialize the color System
+    pub fn new(display: &Backend, source: &ShaderSource) -> Result<ColorSystem, ShaderError> {
+        let program = compile(display, source, Self::SHADER)?;
+        Ok(ColorSystem {
+            program,
+            stream: VertexStream::new(),
+        })
+    }
+    /// Replaces the shaders of the system
+    pub fn set_program(&mut self, program: Program) {
+        self.program = program;
+    }
+    /// Adds a object of solid color to the frame, returning its index
+    pub fn push(&mut self, object: SolidColorObject) -> usize {
+        self.stream.push(object.to_vertex_arr())
+    }
+    /// Adds the triangles of a shape to the frame, returning its index
+    pub fn push_shape(&mut self, object: &ShapeObject) -> usize {
+        let color = object.color.to_array();
+        self.stream.push(object.shape.triangles().into_iter().map(|position| VertexColor {
+            position: position.into(),
+            color,
+        }))
     }
-    /// Draw a object of solid color in the frame
+    /// Sends the objects of the frame to the GPU
+    pub fn upload(&mut self, display: &Backend) -> Result<(), GuiError> {
+        self.stream.upload(display)
+    }
+    /// Number of vertices of the objects of the frame
+    pub fn vertex_count(&self) -> usize {
+        self.stream.vertex_count()
+    }
+    /// Removes the objects of the frame
+    pub fn clear(&mut self) {
+        self.stream.clear();
+    }
+    /// Draw a range of consecutive objects of solid color in the frame, with a single draw call and the same blending
     pub fn draw(
         &self,
-        target: &mut Frame,
-        display: &Display,
+        target: &mut impl Surface,
         camera_transform: transform::Transform,
-        object: SolidColorObject,
-    ) {
+        blend: BlendMode,
+        parameters: &DrawParameters,
+        objects: Range<usize>,
+    ) -> Result<(), GuiError> {
+        let Some(vertices) = self.stream.slice(objects) else {
+            return Ok(());
+        };
         let uniforms = uniform! {
             matrix: camera_transform.0,
+            multiply: blend.multiply(),
         };
 
         target
             .draw(
-                &glium::VertexBuffer::new(display, &object.to_vertex_arr()).unwrap(),
+                vertices,
                 glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList),
                 &self.program,
                 &uniforms,
-                &Default::default(),
-            )
-            .unwrap();
+                parameters,
+            )?;
+        Ok(())
     }
 }
 
@@ -65,38 +112,23 @@ pub struct SolidColorObject {
     /// The format of the object.
     pub region: Rect,
     /// The color of the object.
-    pub color: Rgb,
+    pub color: Rgba,
+    /// The rotation of the object in radians, counterclockwise.
+    pub rotation: f32,
+    /// The point the object rotates around, relative to the center of the region.
+    pub pivot: Vec2,
+    /// The layer of the object, objects of higher layers are drawn over it.
+    pub layer: Layer,
+    /// How the object is blended with what is under it.
+    pub blend: BlendMode,
 }
 
 impl SolidColorObject {
     fn to_vertex_arr(&self) -> [VertexColor;6] {
-        let color = rgb_to_arr(self.color); 
-        let Rect { center, size } = self.region;
-        [
-            VertexColor {
-                position: (center - size / 2.).into(),
-                color,
-            },
-            VertexColor {
-                position: (center + vec2!(size.x, -size.y) / 2.).into(),
-                color,
-            },
-            VertexColor {
-                position: (center + size / 2.).into(),
-                color,
-            },
-            VertexColor {
-                position: (center + size / 2.).into(),
-                color,
-            },
-            VertexColor {
-                position: (center + vec2!(-size.x, size.y) / 2.).into(),
-                color,
-            },
-            VertexColor {
-                position: (center - size / 2.).into(),
-                color,
-            },
-        ]
+        let color = self.color.to_array();
+        quad(self.region, self.rotation, self.pivot).map(|position| VertexColor {
+            position: position.into(),
+            color,
+        })
     }
 }
@@ -1,16 +1,23 @@
 //! Structs and processes for draw a image in the screen
 
-use std::rc::Rc;
+use std::{ops::Range, rc::Rc};
 
 use glium::{
-    implement_vertex, texture::SrgbTexture2d, uniform, uniforms, Display, Frame, Program, Surface,
+    implement_vertex, texture::SrgbTexture2d, uniform, uniforms, DrawParameters, Program, Surface,
 };
 
 use crate::{
-    gui::{transform, Rect},
-    vec2,
+    gui::{
+        assets::{EmbeddedShader, ShaderSource},
+        backend::Backend,
+        transform, GuiError, Rect,
+    },
+    core::rgb::Rgba,
+    vector2::Vec2,
 };
 
+use super::{compile, quad, BlendMode, Layer, NineSliceObject, ShaderError, VertexStream};
+
 /// Represents a vertex in an image.
 ///
 /// It contains the position and texture coordinates of a verte
@@ -18,8 +25,9 @@ use crate::{
 pub struct VertexImage {
     position: [f32; 2],
     tex_coords: [f32; 2],
+    tint: [f32; 4],
 }
-implement_vertex!(VertexImage, position, tex_coords);
+implement_vertex!(VertexImage, position, tex_coords, tint);
 /// Represents an image object that can be drawn on the screen.
 ///
 pub struct ImageObject {
@@ -27,69 +35,105 @@ pub struct ImageObject {
     pub region: Rect,
     /// The texture of the image to draw
     pub texture: Rc<SrgbTexture2d>,
+    /// The part of the texture drawn, in texture coordinates from 0 to 1, [Rect::UNIT] is the whole texture
+    pub source: Rect,
+    /// The rotation of the image in radians, counterclockwise
+    pub rotation: f32,
+    /// The point the image rotates around, relative to the center of the region
+    pub pivot: Vec2,
+    /// The layer of the image, objects of higher layers are drawn over it
+    pub layer: Layer,
+    /// Color multiplied with the texture, [Rgba::WHITE] keeps the image as it is
+    pub tint: Rgba,
+    /// How the image is blended with what is under it
+    pub blend: BlendMode,
 }
 
 impl ImageObject {
     fn to_vertex_arr(&self) -> [VertexImage; 6] {
-        let Rect { center, size } = self.region;
-        [
-            VertexImage {
-                position: (center - size / 2.).into(),
-                tex_coords: [0.0, 0.0],
-            },
-            VertexImage {
-                position: (center + vec2!(size.x, -size.y) / 2.).into(),
-                tex_coords: [1.0, 0.0],
-            },
-            VertexImage {
-                position: (center + size / 2.).into(),
-                tex_coords: [1.0, 1.0],
-            },
-            VertexImage {
-                position: (center + size / 2.).into(),
-                tex_coords: [1.0, 1.0],
-            },
-            VertexImage {
-                position: (center + vec2!(-size.x, size.y) / 2.).into(),
-                tex_coords: [0.0, 1.0],
-            },
-            VertexImage {
-                position: (center - size / 2.).into(),
-                tex_coords: [0.0, 0.0],
-            },
-        ]
+        image_vertices(self.region, self.source, self.rotation, self.pivot, self.tint)
     }
 }
 
+/// The vertices of a region showing the `source` part of a texture
+fn image_vertices(region: Rect, source: Rect, rotation: f32, pivot: Vec2, tint: Rgba) -> [VertexImage; 6] {
+    let (u0, v0, u1, v1) = (source.left(), source.bottom(), source.right(), source.top());
+    let tex_coords = [[u0, v0], [u1, v0], [u1, v1], [u1, v1], [u0, v1], [u0, v0]];
+    let positions = quad(region, rotation, pivot);
+    let tint = tint.to_array();
+    [0, 1, 2, 3, 4, 5].map(|i| VertexImage {
+        position: positions[i].into(),
+        tex_coords: tex_coords[i],
+        tint,
+    })
+}
+
 /// Store the program and logic to draw a image in the screen.
 ///
 /// It contains the program for drawing images.
 pub struct ImageSystem {
     /// The shaders for drawing images
     pub program: Program,
+    /// The objects of the frame
+    stream: VertexStream<VertexImage>,
 }
 impl ImageSystem {
+    /// The shaders of the system, embedded as fallback for the ones in the assets
+    pub const SHADER: EmbeddedShader = EmbeddedShader {
+        name: "image",
+        vertex: include_str!("../../assets/shaders/image.vert"),
+        fragment: include_str!("../../assets/shaders/image.frag"),
+    };
+
     /// Create a new Image system
     ///
-    /// Load the shaders and create the Image System
-    pub fn new(display: &Display) -> ImageSystem {
-        let vertex_shader_src: &str = include_str!("./shaders/image.vert");
-        let fragment_shader_src = include_str!("./shaders/image.frag");
-        let program =
-            glium::Program::from_source(display, vertex_shader_src, fragment_shader_src, None)
-                .unwrap();
-        ImageSystem { program }
+    /// Compile the shaders and create the Image System
+    pub fn new(display: &Backend, source: &ShaderSource) -> Result<ImageSystem, ShaderError> {
+        let program = compile(display, source, Self::SHADER)?;
+        Ok(ImageSystem {
+            program,
+            stream: VertexStream::new(),
+        })
+    }
+    /// Adds an image object to the frame, returning its index
+    pub fn push(&mut self, object: &ImageObject) -> usize {
+        self.stream.push(object.to_vertex_arr())
     }
-    /// Draws an image object on the screen.
+    /// Adds the parts of a nine slice panel to the frame as a single object, returning its index
+    pub fn push_nine_slice(&mut self, object: &NineSliceObject) -> usize {
+        let vertices = object
+            .pieces(object.texture.dimensions())
+            .into_iter()
+            .flat_map(|(region, source)| image_vertices(region, source, 0., Vec2::ZERO, object.tint));
+        self.stream.push(vertices)
+    }
+    /// Sends the objects of the frame to the GPU
+    pub fn upload(&mut self, display: &Backend) -> Result<(), GuiError> {
+        self.stream.upload(display)
+    }
+    /// Number of vertices of the objects of the frame
+    pub fn vertex_count(&self) -> usize {
+        self.stream.vertex_count()
+    }
+    /// Removes the objects of the frame
+    pub fn clear(&mut self) {
+        self.stream.clear();
+    }
+    /// Draws a range of consecutive image objects on the screen, all with the same texture and blending.
     ///
-    /// It calculates the uniforms for drawing the image object, creates a vertex buffer, and draws the image object using the program of the image system.
+    /// It calculates the uniforms for drawing the image objects, and draws them using the program of the image system with a single draw call.
     pub fn draw(
         &self,
-        target: &mut Frame,
-        display: &Display,
+        target: &mut impl Surface,
         camera_transform: transform::Transform,
-        object: ImageObject,
-    ) {
+        texture: &SrgbTexture2d,
+        blend: BlendMode,
+        parameters: &DrawParameters,
+        objects: Range<usize>,
+    ) -> Result<(), GuiError> {
+        let Some(vertices) = self.stream.slice(objects) else {
+            return Ok(());
+        };
         let behavior: uniforms::SamplerBehavior = uniforms::SamplerBehavior {
             minify_filter: uniforms::MinifySamplerFilter::NearestMipmapLinear,
             magnify_filter: uniforms::MagnifySamplerFilter::Nearest,
@@ -97,16 +141,17 @@ impl ImageSystem {
         };
         let uniforms = uniform! {
             matrix:  camera_transform.0,
-            tex:  uniforms::Sampler(&*object.texture, behavior),
+            tex:  uniforms::Sampler(texture, behavior),
+            multiply: blend.multiply(),
         };
         target
             .draw(
-                &glium::VertexBuffer::new(display, &object.to_vertex_arr()).unwrap(),
+                vertices,
                 glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList),
                 &self.program,
                 &uniforms,
-                &Default::default(),
-            )
-            .unwrap();
+                parameters,
+            )?;
+        Ok(())
     }
 }
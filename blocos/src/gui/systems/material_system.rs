@@ -0,0 +1,304 @@
+//! Structs and processes to draw objects with shaders registered by the game
+
+use std::{ops::Range, rc::Rc, time::Instant};
+
+use glium::{
+    implement_vertex,
+    texture::SrgbTexture2d,
+    uniforms::{UniformValue, Uniforms},
+    DrawParameters, Program, Surface,
+};
+
+use crate::{
+    core::rgb::Rgba,
+    gui::{assets::ShaderSource, backend::Backend, transform, GuiError, Rect},
+    vector2::Vec2,
+};
+
+use super::{compile_program, quad, BlendMode, Layer, ShaderError, VertexStream};
+
+/// A vertex of a [MaterialObject], the attributes the shader uses are chosen by the [VertexLayout] of the material
+#[derive(Copy, Clone)]
+pub struct VertexMaterial {
+    position: [f32; 2],
+    tex_coords: [f32; 2],
+    color: [f32; 4],
+}
+implement_vertex!(VertexMaterial, position, tex_coords, color);
+
+/// The attributes of the vertices given to the shader of a material
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VertexLayout {
+    /// `vec2 position`, in the world
+    Position,
+    /// `vec2 position` and `vec2 tex_coords`, from (0, 0) at the bottom left of the region to (1, 1) at the top right
+    PositionTexCoords,
+    /// `vec2 position`, `vec2 tex_coords` and `vec4 color`, the color of the object
+    PositionTexCoordsColor,
+}
+
+impl VertexLayout {
+    /// Names of the attributes of the layout
+    pub fn attributes(self) -> &'static [&'static str] {
+        match self {
+            VertexLayout::Position => &["position"],
+            VertexLayout::PositionTexCoords => &["position", "tex_coords"],
+            VertexLayout::PositionTexCoordsColor => &["position", "tex_coords", "color"],
+        }
+    }
+}
+
+/// The value of a uniform of a material
+#[derive(Debug, Clone)]
+pub enum MaterialUniform {
+    /// `float`
+    Float(f32),
+    /// `vec2`
+    Vec2(Vec2),
+    /// `vec3`
+    Vec3([f32; 3]),
+    /// `vec4`
+    Vec4([f32; 4]),
+    /// `vec4` with the components of the color from 0 to 1
+    Color(Rgba),
+    /// `sampler2D`
+    Texture(Rc<SrgbTexture2d>),
+    /// `float` with the seconds since the interface was created, to animate the shader
+    Time,
+}
+
+impl PartialEq for MaterialUniform {
+    fn eq(&self, other: &Self) -> bool {
+        use MaterialUniform::*;
+        match (self, other) {
+            (Float(a), Float(b)) => a == b,
+            (Vec2(a), Vec2(b)) => a.x == b.x && a.y == b.y,
+            (Vec3(a), Vec3(b)) => a == b,
+            (Vec4(a), Vec4(b)) => a == b,
+            (Color(a), Color(b)) => a == b,
+            (Texture(a), Texture(b)) => Rc::ptr_eq(a, b),
+            (Time, Time) => true,
+            _ => false,
+        }
+    }
+}
+
+impl MaterialUniform {
+    fn value(&self, time: f32) -> UniformValue<'_> {
+        match self {
+            MaterialUniform::Float(value) => UniformValue::Float(*value),
+            MaterialUniform::Vec2(value) => UniformValue::Vec2((*value).into()),
+            MaterialUniform::Vec3(value) => UniformValue::Vec3(*value),
+            MaterialUniform::Vec4(value) => UniformValue::Vec4(*value),
+            MaterialUniform::Color(color) => UniformValue::Vec4(color.to_array()),
+            MaterialUniform::Texture(texture) => UniformValue::SrgbTexture2d(texture, None),
+            MaterialUniform::Time => UniformValue::Float(time),
+        }
+    }
+}
+
+/// The uniforms of a draw call: the camera, the ones of the objects and the defaults of the material
+struct MaterialUniforms<'a> {
+    matrix: [[f32; 4]; 4],
+    time: f32,
+    multiply: bool,
+    defaults: &'a [(String, MaterialUniform)],
+    overrides: &'a [(String, MaterialUniform)],
+}
+
+impl Uniforms for MaterialUniforms<'_> {
+    fn visit_values<'b, F: FnMut(&str, UniformValue<'b>)>(&'b self, mut visit: F) {
+        visit("matrix", UniformValue::Mat4(self.matrix));
+        visit("multiply", UniformValue::Bool(self.multiply));
+        let defaults = self
+            .defaults
+            .iter()
+            .filter(|(name, _)| self.overrides.iter().all(|(other, _)| other != name));
+        for (name, value) in defaults.chain(self.overrides) {
+            visit(name, value.value(self.time));
+        }
+    }
+}
+
+/// A shader registered by the game, drawn by the [MaterialObject]s with its [MaterialId]
+pub struct Material {
+    /// Name of the material, shown in the errors and used to reload its shader from the assets
+    pub name: String,
+    /// The source of the shader, the vertex shader receives the uniform `mat4 matrix` with the camera
+    /// and the fragment shader can read `bool multiply`, see [BlendMode::Multiply]
+    pub shader: ShaderSource,
+    /// The attributes of the vertices the shader receives
+    pub layout: VertexLayout,
+    /// Values of the uniforms of the shader, used when the object does not set them
+    pub uniforms: Vec<(String, MaterialUniform)>,
+}
+
+/// Identifies a material registered in the [MaterialSystem]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MaterialId(usize);
+
+impl MaterialId {
+    /// The position of the material among the ones registered, in the order they were registered
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+struct CompiledMaterial {
+    name: String,
+    program: Program,
+    layout: VertexLayout,
+    uniforms: Vec<(String, MaterialUniform)>,
+}
+
+/// An object drawn with the shader of a material
+pub struct MaterialObject {
+    /// The material of the object
+    pub material: MaterialId,
+    /// The region of the world covered by the object
+    pub region: Rect,
+    /// Color given to the vertices, with [VertexLayout::PositionTexCoordsColor]
+    pub color: Rgba,
+    /// The rotation of the object in radians, counterclockwise
+    pub rotation: f32,
+    /// The point the object rotates around, relative to the center of the region
+    pub pivot: Vec2,
+    /// Uniforms of this object, replacing the ones of the material with the same name
+    ///
+    /// Consecutive objects of the same material are only drawn in a single call when their uniforms are equal
+    pub uniforms: Vec<(String, MaterialUniform)>,
+    /// The layer of the object, objects of higher layers are drawn over it
+    pub layer: Layer,
+    /// How the object is blended with what is under it
+    pub blend: BlendMode,
+}
+
+impl MaterialObject {
+    fn to_vertex_arr(&self) -> [VertexMaterial; 6] {
+        let tex_coords = [[0., 0.], [1., 0.], [1., 1.], [1., 1.], [0., 1.], [0., 0.]];
+        let positions = quad(self.region, self.rotation, self.pivot);
+        let color = self.color.to_array();
+        [0, 1, 2, 3, 4, 5].map(|i| VertexMaterial {
+            position: positions[i].into(),
+            tex_coords: tex_coords[i],
+            color,
+        })
+    }
+}
+
+/// Store the materials registered by the game and the logic to draw objects with them
+pub struct MaterialSystem {
+    materials: Vec<CompiledMaterial>,
+    /// The objects of the frame
+    stream: VertexStream<VertexMaterial>,
+    /// When the system was created, the origin of [MaterialUniform::Time]
+    start: Instant,
+}
+
+impl Default for MaterialSystem {
+    /// A system without materials
+    fn default() -> Self {
+        MaterialSystem {
+            materials: Vec::new(),
+            stream: VertexStream::new(),
+            start: Instant::now(),
+        }
+    }
+}
+
+impl MaterialSystem {
+    /// Create a system without materials
+    pub fn new() -> MaterialSystem {
+        MaterialSystem::default()
+    }
+
+    /// Compiles the shader of a material and registers it
+    ///
+    /// Fails if the shader has errors or uses attributes that are not in the layout of the material
+    pub fn register(&mut self, display: &Backend, material: Material) -> Result<MaterialId, ShaderError> {
+        let program = compile_program(display, &material.name, &material.shader)?;
+        check_layout(&material.name, &program, material.layout)?;
+        self.materials.push(CompiledMaterial {
+            name: material.name,
+            program,
+            layout: material.layout,
+            uniforms: material.uniforms,
+        });
+        Ok(MaterialId(self.materials.len() - 1))
+    }
+
+    /// Replaces the shader of the material called `name`, if there is one
+    ///
+    /// The current shader is kept if the new one uses attributes that are not in the layout of the material
+    pub fn reload(&mut self, name: &str, program: Program) -> Result<(), ShaderError> {
+        if let Some(material) = self.materials.iter_mut().find(|material| material.name == name) {
+            check_layout(name, &program, material.layout)?;
+            material.program = program;
+        }
+        Ok(())
+    }
+
+    /// Adds an object to the frame, returning its index
+    pub fn push(&mut self, object: &MaterialObject) -> usize {
+        self.stream.push(object.to_vertex_arr())
+    }
+    /// Sends the objects of the frame to the GPU
+    pub fn upload(&mut self, display: &Backend) -> Result<(), GuiError> {
+        self.stream.upload(display)
+    }
+    /// Number of vertices of the objects of the frame
+    pub fn vertex_count(&self) -> usize {
+        self.stream.vertex_count()
+    }
+    /// Removes the objects of the frame
+    pub fn clear(&mut self) {
+        self.stream.clear();
+    }
+
+    /// Draws a range of consecutive objects of the same material, uniforms and blending with a single draw call
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw(
+        &self,
+        target: &mut impl Surface,
+        camera_transform: transform::Transform,
+        material: MaterialId,
+        uniforms: &[(String, MaterialUniform)],
+        blend: BlendMode,
+        parameters: &DrawParameters,
+        objects: Range<usize>,
+    ) -> Result<(), GuiError> {
+        let Some(vertices) = self.stream.slice(objects) else {
+            return Ok(());
+        };
+        let material = &self.materials[material.0];
+        let uniforms = MaterialUniforms {
+            matrix: camera_transform.0,
+            time: self.start.elapsed().as_secs_f32(),
+            multiply: blend.multiply(),
+            defaults: &material.uniforms,
+            overrides: uniforms,
+        };
+        target
+            .draw(
+                vertices,
+                glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList),
+                &material.program,
+                &uniforms,
+                parameters,
+            )
+            // A uniform of the wrong type is the usual cause, so the message names the material
+            .map_err(|err| GuiError::Draw(format!("the material `{}`: {err}", material.name).into()))
+    }
+}
+
+/// Fails if the program has an attribute that is not in the layout
+fn check_layout(name: &str, program: &Program, layout: VertexLayout) -> Result<(), ShaderError> {
+    let attributes = layout.attributes();
+    match program.attributes().find(|(attribute, _)| !attributes.contains(&attribute.as_str())) {
+        Some((missing, _)) => Err(ShaderError {
+            name: name.to_owned(),
+            message: format!("the attribute `{missing}` is not in the layout {layout:?}"),
+        }),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,251 @@
+//! Panels drawn from a texture cut in nine parts, which scale to any size keeping the corners as they are
+//!
+//! The corners are drawn with their size, the edges are stretched or tiled along their side and the center in
+//! both directions. The [ImageSystem](super::ImageSystem) draws the parts as images of the same texture
+
+use std::rc::Rc;
+
+use glium::texture::SrgbTexture2d;
+
+use crate::{core::rgb::Rgba, gui::Rect, vec2};
+
+use super::{BlendMode, Layer};
+
+/// Width of the borders of a texture, in pixels of the texture
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Insets {
+    /// Width of the left border
+    pub left: u32,
+    /// Width of the right border
+    pub right: u32,
+    /// Height of the top border
+    pub top: u32,
+    /// Height of the bottom border
+    pub bottom: u32,
+}
+
+impl Insets {
+    /// Borders of the same width on every side
+    pub const fn uniform(width: u32) -> Insets {
+        Insets {
+            left: width,
+            right: width,
+            top: width,
+            bottom: width,
+        }
+    }
+}
+
+/// How the edges and the center of a [NineSliceObject] cover their size
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EdgeMode {
+    /// Stretched to cover it
+    #[default]
+    Stretch,
+    /// Repeated with their size, the last copy cut where it ends
+    Tile,
+}
+
+/// A panel, like the background of a button, drawn from a texture with borders
+pub struct NineSliceObject {
+    /// The region of the world covered by the panel
+    pub region: Rect,
+    /// The texture, with the corners and the edges around it
+    pub texture: Rc<SrgbTexture2d>,
+    /// The part of the texture with the panel, in texture coordinates, [Rect::UNIT] is the whole texture
+    pub source: Rect,
+    /// Width of the borders of the texture
+    pub insets: Insets,
+    /// Size in the world of a pixel of the texture, so the borders keep their size in any region
+    ///
+    /// The borders are made smaller when they do not fit in the region
+    pub pixel_size: f32,
+    /// How the edges and the center cover their size
+    pub edges: EdgeMode,
+    /// Whether the center is drawn, `false` draws only a frame
+    pub fill: bool,
+    /// Color multiplied with the texture, [Rgba::WHITE] keeps it as it is
+    pub tint: Rgba,
+    /// The layer of the panel, objects of higher layers are drawn over it
+    pub layer: Layer,
+    /// How the panel is blended with what is under it
+    pub blend: BlendMode,
+}
+
+impl NineSliceObject {
+    /// The parts of the panel, each one as its region in the world and its source in texture coordinates,
+    /// with the texture of `texture_width` by `texture_height` pixels
+    pub fn pieces(&self, (texture_width, texture_height): (u32, u32)) -> Vec<(Rect, Rect)> {
+        // The size of the source in pixels, which the insets are measured in
+        let source = self.source;
+        let width = (source.size.x * texture_width as f32).round() as u32;
+        let height = (source.size.y * texture_height as f32).round() as u32;
+        if width == 0 || height == 0 {
+            return Vec::new();
+        }
+        let Insets { left, right, top, bottom } = self.insets;
+        let (left, right) = (left.min(width), right.min(width - left.min(width)));
+        let (top, bottom) = (top.min(height), bottom.min(height - top.min(height)));
+
+        // The borders shrink together when the region is too small for them
+        let region = self.region;
+        let fit = |first: u32, second: u32, size: f32| {
+            let borders = (first + second) as f32 * self.pixel_size;
+            if borders > size && borders > 0. {
+                size / borders * self.pixel_size
+            } else {
+                self.pixel_size
+            }
+        };
+        let (horizontal, vertical) = (fit(left, right, region.size.x), fit(bottom, top, region.size.y));
+
+        // Edges of the columns and rows, in the world and in texture coordinates, from the left and the bottom
+        // The textures are uploaded with the rows reversed, so the bottom of the image is at v = 0
+        let xs = [
+            region.left(),
+            region.left() + left as f32 * horizontal,
+            region.right() - right as f32 * horizontal,
+            region.right(),
+        ];
+        let ys = [
+            region.bottom(),
+            region.bottom() + bottom as f32 * vertical,
+            region.top() - top as f32 * vertical,
+            region.top(),
+        ];
+        let us = [0, left, width - right, width].map(|x| source.left() + x as f32 / width as f32 * source.size.x);
+        let vs = [0, bottom, height - top, height].map(|y| source.bottom() + y as f32 / height as f32 * source.size.y);
+        // Size in the world of a copy of the middle of the texture when tiled
+        let tile = vec2!((width - left - right) as f32 * horizontal, (height - top - bottom) as f32 * vertical);
+
+        let mut pieces = Vec::new();
+        for row in 0..3 {
+            for column in 0..3 {
+                if row == 1 && column == 1 && !self.fill {
+                    continue;
+                }
+                let tile_x = column == 1 && self.edges == EdgeMode::Tile;
+                let tile_y = row == 1 && self.edges == EdgeMode::Tile;
+                for (x0, x1, u0, u1) in split(xs[column], xs[column + 1], us[column], us[column + 1], tile_x, tile.x) {
+                    for (y0, y1, v0, v1) in split(ys[row], ys[row + 1], vs[row], vs[row + 1], tile_y, tile.y) {
+                        pieces.push((
+                            Rect::from_corners(vec2!(x0, y0), vec2!(x1, y1)),
+                            Rect::from_corners(vec2!(u0, v0), vec2!(u1, v1)),
+                        ));
+                    }
+                }
+            }
+        }
+        pieces
+    }
+}
+
+/// Splits the span from `start` to `end` of the world, showing the texture from `from` to `to`, in copies of
+/// `tile` with `tiled`, or in a single stretched part otherwise
+///
+/// Returns the spans of the world and of the texture of each part, without the empty ones
+fn split(start: f32, end: f32, from: f32, to: f32, tiled: bool, tile: f32) -> Vec<(f32, f32, f32, f32)> {
+    if end - start <= 0. || to - from <= 0. {
+        return Vec::new();
+    }
+    if !tiled || tile <= 0. {
+        return vec![(start, end, from, to)];
+    }
+    let mut parts = Vec::new();
+    let mut position = start;
+    while position < end {
+        let next = (position + tile).min(end);
+        parts.push((position, next, from, from + (to - from) * (next - position) / tile));
+        position = next;
+    }
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use glium::texture::{MipmapsOption, SrgbFormat};
+
+    use super::*;
+    use crate::{gui::backend::Backend, vector2::Vec2};
+
+    /// Size in pixels of the texture the pieces are cut from
+    const TEXTURE: (u32, u32) = (16, 16);
+
+    /// A panel covering `size` around the origin, with borders of 4 pixels of half a unit of the world
+    fn panel(size: Vec2, edges: EdgeMode, fill: bool) -> NineSliceObject {
+        let backend = Backend::headless(1, 1).unwrap();
+        let texture =
+            SrgbTexture2d::empty_with_format(&backend, SrgbFormat::U8U8U8U8, MipmapsOption::NoMipmap, 1, 1).unwrap();
+        NineSliceObject {
+            region: Rect { center: Vec2::ZERO, size },
+            texture: Rc::new(texture),
+            source: Rect::UNIT,
+            insets: Insets::uniform(4),
+            pixel_size: 0.5,
+            edges,
+            fill,
+            tint: Rgba::WHITE,
+            layer: 0,
+            blend: BlendMode::Alpha,
+        }
+    }
+
+    /// Asserts that `rect` goes from `from` at the bottom left to `to` at the top right
+    fn assert_corners(rect: Rect, from: (f32, f32), to: (f32, f32)) {
+        let close = |a: f32, b: f32| (a - b).abs() < 1e-5;
+        let corners = [(rect.left(), from.0), (rect.bottom(), from.1), (rect.right(), to.0), (rect.top(), to.1)];
+        assert!(corners.iter().all(|&(a, b)| close(a, b)), "{rect:?} is not from {from:?} to {to:?}");
+    }
+
+    #[test]
+    fn the_corners_keep_the_size_of_their_pixels() {
+        let pieces = panel(vec2!(20_f32, 10.), EdgeMode::Stretch, true).pieces(TEXTURE);
+        assert_eq!(pieces.len(), 9);
+        assert_corners(pieces[0].0, (-10., -5.), (-8., -3.));
+        assert_corners(pieces[0].1, (0., 0.), (0.25, 0.25));
+        assert_corners(pieces[8].0, (8., 3.), (10., 5.));
+        assert_corners(pieces[8].1, (0.75, 0.75), (1., 1.));
+        // The center is stretched over the rest
+        assert_corners(pieces[4].0, (-8., -3.), (8., 3.));
+        assert_corners(pieces[4].1, (0.25, 0.25), (0.75, 0.75));
+    }
+
+    #[test]
+    fn the_borders_shrink_together_when_they_do_not_fit() {
+        // The left and right borders need 4 units, so they are made half as wide to fit in 2
+        let pieces = panel(vec2!(2_f32, 10.), EdgeMode::Stretch, true).pieces(TEXTURE);
+        assert_eq!(pieces.len(), 6);
+        assert_corners(pieces[0].0, (-1., -5.), (0., -3.));
+        assert_corners(pieces[1].0, (0., -5.), (1., -3.));
+        assert_corners(pieces[1].1, (0.75, 0.), (1., 0.25));
+    }
+
+    #[test]
+    fn the_last_tile_shows_the_part_of_the_texture_that_fits() {
+        // The middle of the texture is 8 pixels, 4 units, so the 10 units of the middle are 2.5 copies
+        let pieces = panel(vec2!(14_f32, 4.), EdgeMode::Tile, false).pieces(TEXTURE);
+        let bottom: Vec<_> = pieces.iter().filter(|(region, _)| region.bottom() == -2.).collect();
+        assert_eq!(bottom.len(), 5);
+        assert_corners(bottom[1].0, (-5., -2.), (-1., 0.));
+        assert_corners(bottom[1].1, (0.25, 0.), (0.75, 0.25));
+        assert_corners(bottom[3].0, (3., -2.), (5., 0.));
+        assert_corners(bottom[3].1, (0.25, 0.), (0.5, 0.25));
+    }
+
+    #[test]
+    fn without_fill_only_the_center_is_left_out() {
+        for edges in [EdgeMode::Stretch, EdgeMode::Tile] {
+            let mut filled = panel(vec2!(20_f32, 10.), edges, true).pieces(TEXTURE);
+            let frame = panel(vec2!(20_f32, 10.), edges, false).pieces(TEXTURE);
+            let inside = |region: &Rect| {
+                region.left() >= -8. && region.right() <= 8. && region.bottom() >= -3. && region.top() <= 3.
+            };
+            filled.retain(|(region, _)| !inside(region));
+            let corners = |pieces: &[(Rect, Rect)]| {
+                let corners = |rect: Rect| [rect.left(), rect.bottom(), rect.right(), rect.top()];
+                pieces.iter().map(|&(region, source)| (corners(region), corners(source))).collect::<Vec<_>>()
+            };
+            assert_eq!(corners(&frame), corners(&filled));
+        }
+    }
+}
@@ -0,0 +1,268 @@
+//! Full screen effects applied to the frame after its objects are drawn
+
+use glium::{
+    implement_vertex,
+    texture::{MipmapsOption, SrgbFormat, SrgbTexture2d, TextureCreationError},
+    uniform, uniforms, Program, Surface, VertexBuffer,
+};
+
+use crate::{
+    core::rgb::Rgba,
+    gui::{
+        assets::{EmbeddedShader, ShaderSource},
+        backend::Backend,
+        GuiError,
+    },
+};
+
+use super::compile;
+
+/// A vertex of the quad covering the whole frame
+#[derive(Copy, Clone)]
+pub struct VertexPost {
+    position: [f32; 2],
+    tex_coords: [f32; 2],
+}
+implement_vertex!(VertexPost, position, tex_coords);
+
+/// An effect applied to the whole frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PostEffect {
+    /// Dark lines between the rows of pixels and the screen bent like an old tube
+    Crt {
+        /// How dark the lines are, from 0 to 1
+        scanlines: f32,
+        /// How much the screen is bent, 0 keeps it flat
+        curvature: f32,
+    },
+    /// Bright parts of the frame glow over what is around them
+    Bloom {
+        /// Brightness from 0 to 1 above which a pixel glows
+        threshold: f32,
+        /// How strong the glow is
+        intensity: f32,
+        /// How far the glow spreads, in pixels
+        radius: f32,
+    },
+    /// Changes the colors of the whole frame
+    ColorGrading {
+        /// Multiplies the colors, 1 keeps them
+        brightness: f32,
+        /// Spreads the colors away from the middle gray, 1 keeps them
+        contrast: f32,
+        /// 0 is gray, 1 keeps the colors, more makes them stronger
+        saturation: f32,
+        /// Multiplied with the colors at the end
+        tint: Rgba,
+    },
+    /// Darkens the borders of the frame
+    Vignette {
+        /// Distance from the center where the darkening starts, 1 is the corners
+        radius: f32,
+        /// Distance over which it goes from no darkening to the full one
+        softness: f32,
+        /// How dark the corners get, from 0 to 1
+        intensity: f32,
+    },
+    /// Shows the frame with bigger pixels, animated to make transitions between scenes
+    Pixelate {
+        /// Size of the pixels shown, in pixels of the frame, 1 keeps the frame as it is
+        size: f32,
+    },
+}
+
+impl PostEffect {
+    /// Name of the effect, to find it in the [PostProcess]
+    pub fn name(&self) -> &'static str {
+        match self {
+            PostEffect::Crt { .. } => "crt",
+            PostEffect::Bloom { .. } => "bloom",
+            PostEffect::ColorGrading { .. } => "color_grading",
+            PostEffect::Vignette { .. } => "vignette",
+            PostEffect::Pixelate { .. } => "pixelate",
+        }
+    }
+
+    /// The number of the effect in the shader and its parameters
+    fn uniforms(&self) -> (i32, [f32; 4], [f32; 4]) {
+        const WHITE: [f32; 4] = [1., 1., 1., 1.];
+        match *self {
+            PostEffect::Crt { scanlines, curvature } => (0, [scanlines, curvature, 0., 0.], WHITE),
+            PostEffect::Bloom { threshold, intensity, radius } => (1, [threshold, intensity, radius, 0.], WHITE),
+            PostEffect::ColorGrading { brightness, contrast, saturation, tint } => {
+                (2, [brightness, contrast, saturation, 0.], tint.to_array())
+            }
+            PostEffect::Vignette { radius, softness, intensity } => (3, [radius, softness, intensity, 0.], WHITE),
+            PostEffect::Pixelate { size } => (4, [size, 0., 0., 0.], WHITE),
+        }
+    }
+}
+
+/// An effect of the [PostProcess] and whether it is applied
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PostPass {
+    /// The effect
+    pub effect: PostEffect,
+    /// Whether the effect is applied, the disabled passes cost nothing
+    pub enabled: bool,
+}
+
+/// The chain of effects applied to the frame, in order
+#[derive(Debug, Clone, PartialEq)]
+pub struct PostProcess {
+    /// The passes, each one applied to the result of the previous
+    pub passes: Vec<PostPass>,
+}
+
+impl Default for PostProcess {
+    /// Every built-in effect, disabled, in an order where each one looks right over the previous
+    fn default() -> Self {
+        let effects = [
+            PostEffect::Bloom { threshold: 0.7, intensity: 0.8, radius: 6. },
+            PostEffect::ColorGrading {
+                brightness: 1.05,
+                contrast: 1.1,
+                saturation: 1.2,
+                tint: Rgba::WHITE,
+            },
+            PostEffect::Pixelate { size: 1. },
+            PostEffect::Crt { scanlines: 0.25, curvature: 0.08 },
+            PostEffect::Vignette { radius: 0.6, softness: 0.5, intensity: 0.6 },
+        ];
+        PostProcess {
+            passes: effects.into_iter().map(|effect| PostPass { effect, enabled: false }).collect(),
+        }
+    }
+}
+
+impl PostProcess {
+    /// The first pass with an effect called `name`, see [PostEffect::name]
+    pub fn pass_mut(&mut self, name: &str) -> Option<&mut PostPass> {
+        self.passes.iter_mut().find(|pass| pass.effect.name() == name)
+    }
+
+    /// Enables or disables the passes with an effect called `name`
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) {
+        for pass in self.passes.iter_mut().filter(|pass| pass.effect.name() == name) {
+            pass.enabled = enabled;
+        }
+    }
+
+    /// Whether a pass is enabled, so the frame must be drawn in a texture first
+    pub fn is_active(&self) -> bool {
+        self.passes.iter().any(|pass| pass.enabled)
+    }
+}
+
+/// Store the program and the textures to apply the [PostProcess] to the frame
+pub struct PostSystem {
+    /// The shader of every effect
+    program: Program,
+    /// The quad covering the whole frame
+    quad: VertexBuffer<VertexPost>,
+    /// Where the frame is drawn and the passes before the last one, used in turns, with the size of the frame
+    textures: Option<[SrgbTexture2d; 2]>,
+}
+
+impl PostSystem {
+    /// The shaders of the system, embedded as fallback for the ones in the assets
+    pub const SHADER: EmbeddedShader = EmbeddedShader {
+        name: "post",
+        vertex: include_str!("../../assets/shaders/post.vert"),
+        fragment: include_str!("../../assets/shaders/post.frag"),
+    };
+
+    /// Create the post process system, the textures are only created in the first frame with effects
+    pub fn new(display: &Backend, source: &ShaderSource) -> Result<PostSystem, GuiError> {
+        let program = compile(display, source, Self::SHADER)?;
+        let vertices = [[-1., -1.], [1., -1.], [1., 1.], [1., 1.], [-1., 1.], [-1., -1.]].map(|[x, y]| VertexPost {
+            position: [x, y],
+            tex_coords: [(x + 1.) / 2., (y + 1.) / 2.],
+        });
+        let quad = VertexBuffer::new(display, &vertices)?;
+        Ok(PostSystem { program, quad, textures: None })
+    }
+
+    /// Changes the program
+    pub fn set_program(&mut self, program: Program) {
+        self.program = program;
+    }
+
+    /// Creates the textures of the passes again if the frame changed size
+    pub fn prepare(&mut self, display: &Backend, (width, height): (u32, u32)) -> Result<(), TextureCreationError> {
+        if self.textures.as_ref().is_some_and(|[texture, _]| texture.dimensions() == (width, height)) {
+            return Ok(());
+        }
+        let texture =
+            || SrgbTexture2d::empty_with_format(display, SrgbFormat::U8U8U8U8, MipmapsOption::NoMipmap, width, height);
+        self.textures = Some([texture()?, texture()?]);
+        Ok(())
+    }
+
+    /// The texture where the frame is drawn before the passes, `None` before [PostSystem::prepare]
+    pub fn scene(&self) -> Option<&SrgbTexture2d> {
+        self.textures.as_ref().map(|[scene, _]| scene)
+    }
+
+    /// Applies the enabled passes to the frame drawn in [PostSystem::scene], the last one drawing on the target
+    ///
+    /// Returns the number of draw calls
+    pub fn apply(
+        &self,
+        target: &mut impl Surface,
+        display: &Backend,
+        post_process: &PostProcess,
+    ) -> Result<usize, GuiError> {
+        let Some(textures) = &self.textures else {
+            return Ok(0);
+        };
+        let passes: Vec<&PostPass> = post_process.passes.iter().filter(|pass| pass.enabled).collect();
+        for (i, pass) in passes.iter().enumerate() {
+            let source = &textures[i % 2];
+            if i + 1 == passes.len() {
+                self.draw(target, source, pass.effect)?;
+            } else {
+                let mut next = glium::framebuffer::SimpleFrameBuffer::new(display, &textures[(i + 1) % 2])?;
+                self.draw(&mut next, source, pass.effect)?;
+            }
+        }
+        Ok(passes.len())
+    }
+
+    /// Number of vertices drawn by each pass
+    pub fn quad_vertices(&self) -> usize {
+        self.quad.len()
+    }
+
+    /// Draws the quad with an effect applied to `source`
+    fn draw(&self, target: &mut impl Surface, source: &SrgbTexture2d, effect: PostEffect) -> Result<(), GuiError> {
+        let (effect, params, tint) = effect.uniforms();
+        let (width, height) = source.dimensions();
+        let behavior = uniforms::SamplerBehavior {
+            minify_filter: uniforms::MinifySamplerFilter::Linear,
+            magnify_filter: uniforms::MagnifySamplerFilter::Linear,
+            wrap_function: (
+                uniforms::SamplerWrapFunction::Clamp,
+                uniforms::SamplerWrapFunction::Clamp,
+                uniforms::SamplerWrapFunction::Clamp,
+            ),
+            ..Default::default()
+        };
+        let uniforms = uniform! {
+            tex: uniforms::Sampler(source, behavior),
+            resolution: [width as f32, height as f32],
+            effect: effect,
+            params: params,
+            tint: tint,
+        };
+        target
+            .draw(
+                &self.quad,
+                glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList),
+                &self.program,
+                &uniforms,
+                &Default::default(),
+            )?;
+        Ok(())
+    }
+}
@@ -1,10 +0,0 @@
-#version 140
-
-in vec2 v_tex_coords;
-out vec4 color;
-
-uniform sampler2D tex;
-
-void main() {
-    color = texture(tex, v_tex_coords);
-}
\ No newline at end of file
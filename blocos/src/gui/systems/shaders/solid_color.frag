@@ -1,8 +0,0 @@
-#version 140
-
-in vec4 vertex_color;
-out vec4 color;
-
-void main() {
-    color = vertex_color;    
-}
\ No newline at end of file
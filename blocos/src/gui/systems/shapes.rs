@@ -0,0 +1,287 @@
+//! Vector shapes drawn by the [ColorSystem](super::ColorSystem): lines, circles, polygons and their outlines
+//!
+//! The shapes are converted to triangles in the world, strokes are centered on the path with bevelled joins
+
+use std::f32::consts::{FRAC_PI_2, TAU};
+
+use crate::{core::rgb::Rgba, gui::Rect, vec2, vector2::Vec2};
+
+use super::{BlendMode, Layer};
+
+/// The geometry of a [ShapeObject], in units of the world
+///
+/// The shapes with an `outline` are filled when it is `None`, else only their edge is drawn with that thickness
+#[derive(Debug, Clone)]
+pub enum Shape {
+    /// A segment from `from` to `to`
+    Line { from: Vec2, to: Vec2, thickness: f32 },
+    /// Segments through the points, back to the first one if `closed`
+    Polyline { points: Vec<Vec2>, thickness: f32, closed: bool },
+    /// An ellipse with the radii along the x and y axes
+    Ellipse { center: Vec2, radii: Vec2, outline: Option<f32> },
+    /// A polygon with the points in any order around it, convex or concave but not crossing itself
+    Polygon { points: Vec<Vec2>, outline: Option<f32> },
+    /// A rectangle with the corners rounded by `radius`, limited to half of its smallest side
+    RoundedRect { region: Rect, radius: f32, outline: Option<f32> },
+}
+
+impl Shape {
+    /// A circle, an ellipse with both radii equal
+    pub fn circle(center: Vec2, radius: f32, outline: Option<f32>) -> Shape {
+        Shape::Ellipse { center, radii: vec2!(radius, radius), outline }
+    }
+
+    /// The triangles of the shape, three points each
+    pub fn triangles(&self) -> Vec<Vec2> {
+        match self {
+            Shape::Line { from, to, thickness } => stroke(&[*from, *to], *thickness, false),
+            Shape::Polyline { points, thickness, closed } => stroke(points, *thickness, *closed),
+            Shape::Ellipse { center, radii, outline: None } => {
+                fill_convex(&ellipse(*center, *radii, arc_segments(radii.x.max(radii.y), TAU)))
+            }
+            Shape::Ellipse { center, radii, outline: Some(thickness) } => ring(*center, *radii, *thickness),
+            Shape::Polygon { points, outline: None } => triangulate(points),
+            Shape::Polygon { points, outline: Some(thickness) } => stroke(points, *thickness, true),
+            Shape::RoundedRect { region, radius, outline: None } => fill_convex(&rounded_rect(*region, *radius)),
+            Shape::RoundedRect { region, radius, outline: Some(thickness) } => {
+                stroke(&rounded_rect(*region, *radius), *thickness, true)
+            }
+        }
+    }
+}
+
+/// A shape of a single color
+pub struct ShapeObject {
+    /// The geometry of the shape
+    pub shape: Shape,
+    /// The color of the shape
+    pub color: Rgba,
+    /// The layer of the shape, objects of higher layers are drawn over it
+    pub layer: Layer,
+    /// How the shape is blended with what is under it
+    pub blend: BlendMode,
+}
+
+/// Number of segments of an arc, more for larger arcs so they stay round
+fn arc_segments(radius: f32, angle: f32) -> usize {
+    ((radius.max(0.).sqrt() * angle * 3.).ceil() as usize).clamp(3, 128)
+}
+
+/// Points of an ellipse in counterclockwise order
+fn ellipse(center: Vec2, radii: Vec2, segments: usize) -> Vec<Vec2> {
+    (0..segments)
+        .map(|i| {
+            let angle = TAU * i as f32 / segments as f32;
+            center + vec2!(radii.x * angle.cos(), radii.y * angle.sin())
+        })
+        .collect()
+}
+
+/// Points of a rectangle with rounded corners in counterclockwise order
+fn rounded_rect(region: Rect, radius: f32) -> Vec<Vec2> {
+    let radius = radius.clamp(0., region.size.x.min(region.size.y) / 2.);
+    let corners = [
+        vec2!(region.right() - radius, region.top() - radius),
+        vec2!(region.left() + radius, region.top() - radius),
+        vec2!(region.left() + radius, region.bottom() + radius),
+        vec2!(region.right() - radius, region.bottom() + radius),
+    ];
+    if radius == 0. {
+        return corners.to_vec();
+    }
+    let segments = arc_segments(radius, FRAC_PI_2);
+    let mut points = Vec::new();
+    for (i, corner) in corners.into_iter().enumerate() {
+        let start = FRAC_PI_2 * i as f32;
+        points.extend((0..=segments).map(|j| {
+            let angle = start + FRAC_PI_2 * j as f32 / segments as f32;
+            corner + vec2!(angle.cos(), angle.sin()) * radius
+        }));
+    }
+    points
+}
+
+/// Pushes the two triangles of the quad `a b c d`
+fn push_quad(triangles: &mut Vec<Vec2>, a: Vec2, b: Vec2, c: Vec2, d: Vec2) {
+    triangles.extend([a, b, c, c, d, a]);
+}
+
+/// Triangles of a convex polygon, as a fan from its first point
+fn fill_convex(points: &[Vec2]) -> Vec<Vec2> {
+    let mut triangles = Vec::new();
+    for i in 1..points.len().saturating_sub(1) {
+        triangles.extend([points[0], points[i], points[i + 1]]);
+    }
+    triangles
+}
+
+/// Triangles of the outline of an ellipse, between the ellipses `thickness / 2` inside and outside it
+fn ring(center: Vec2, radii: Vec2, thickness: f32) -> Vec<Vec2> {
+    let half = vec2!(thickness, thickness) / 2.;
+    let segments = arc_segments(radii.x.max(radii.y) + half.x, TAU);
+    let outer = ellipse(center, radii + half, segments);
+    let inner_radii = radii - half;
+    let inner = ellipse(center, vec2!(inner_radii.x.max(0.), inner_radii.y.max(0.)), segments);
+    let mut triangles = Vec::new();
+    for i in 0..segments {
+        let next = (i + 1) % segments;
+        push_quad(&mut triangles, inner[i], outer[i], outer[next], inner[next]);
+    }
+    triangles
+}
+
+/// Triangles of segments `thickness` wide through the points, with bevelled joins
+fn stroke(points: &[Vec2], thickness: f32, closed: bool) -> Vec<Vec2> {
+    let count = points.len();
+    let mut triangles = Vec::new();
+    if count < 2 {
+        return triangles;
+    }
+    let half = thickness / 2.;
+    let normal = |from: Vec2, to: Vec2| (to - from).normalized().perpendicular() * half;
+
+    let segments = if closed && count > 2 { count } else { count - 1 };
+    for i in 0..segments {
+        let (a, b) = (points[i], points[(i + 1) % count]);
+        let n = normal(a, b);
+        push_quad(&mut triangles, a - n, b - n, b + n, a + n);
+    }
+
+    let joins = if closed && count > 2 { 0..count } else { 1..count - 1 };
+    for i in joins {
+        let (previous, point, next) = (points[(i + count - 1) % count], points[i], points[(i + 1) % count]);
+        let (before, after) = (normal(previous, point), normal(point, next));
+        triangles.extend([point, point + before, point + after, point, point - after, point - before]);
+    }
+    triangles
+}
+
+/// Triangles of a simple polygon, convex or concave, by ear clipping
+///
+/// Polygons that cross themselves are only partially filled
+fn triangulate(points: &[Vec2]) -> Vec<Vec2> {
+    let mut triangles = Vec::new();
+    if points.len() < 3 {
+        return triangles;
+    }
+    let area: f32 = (0..points.len())
+        .map(|i| points[i].cross(points[(i + 1) % points.len()]))
+        .sum();
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    if area < 0. {
+        remaining.reverse();
+    }
+
+    while remaining.len() > 3 {
+        let count = remaining.len();
+        let corner = |i: usize| {
+            (
+                points[remaining[(i + count - 1) % count]],
+                points[remaining[i]],
+                points[remaining[(i + 1) % count]],
+            )
+        };
+        let is_ear = |i: usize| {
+            let (a, b, c) = corner(i);
+            (b - a).cross(c - b) > 0.
+                && (0..count)
+                    .filter(|j| (*j + 1) % count != i && *j != i && *j != (i + 1) % count)
+                    .all(|j| !in_triangle(points[remaining[j]], a, b, c))
+        };
+        if let Some(ear) = (0..count).find(|i| is_ear(*i)) {
+            let (a, b, c) = corner(ear);
+            triangles.extend([a, b, c]);
+            remaining.remove(ear);
+        } else if let Some(flat) = (0..count).find(|i| {
+            let (a, b, c) = corner(*i);
+            (b - a).cross(c - b).abs() <= f32::EPSILON
+        }) {
+            // A point in the middle of a straight edge is not an ear, but can be dropped without changing the polygon
+            remaining.remove(flat);
+        } else {
+            return triangles;
+        }
+    }
+    let (a, b, c) = (points[remaining[0]], points[remaining[1]], points[remaining[2]]);
+    if (b - a).cross(c - b) > 0. {
+        triangles.extend([a, b, c]);
+    }
+    triangles
+}
+
+/// Whether `point` is inside or on the edges of the counterclockwise triangle `a b c`
+fn in_triangle(point: Vec2, a: Vec2, b: Vec2, c: Vec2) -> bool {
+    (b - a).cross(point - a) >= 0. && (c - b).cross(point - b) >= 0. && (a - c).cross(point - c) >= 0.
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The area of the polygon, positive when it is counterclockwise
+    fn signed_area(points: &[Vec2]) -> f32 {
+        (0..points.len()).map(|i| points[i].cross(points[(i + 1) % points.len()])).sum::<f32>() / 2.
+    }
+
+    /// An L with the corner at the origin, counterclockwise, whose area is 3
+    fn l_shape() -> Vec<Vec2> {
+        vec![
+            vec2!(0_f32, 0.),
+            vec2!(2_f32, 0.),
+            vec2!(2_f32, 1.),
+            vec2!(1_f32, 1.),
+            vec2!(1_f32, 2.),
+            vec2!(0_f32, 2.),
+        ]
+    }
+
+    #[test]
+    fn a_concave_polygon_is_split_in_triangles_covering_it_in_both_orders() {
+        let counterclockwise = l_shape();
+        let clockwise: Vec<Vec2> = counterclockwise.iter().rev().copied().collect();
+        for points in [counterclockwise, clockwise] {
+            let triangles = triangulate(&points);
+            assert_eq!(triangles.len(), 3 * (points.len() - 2));
+            let areas: Vec<f32> = triangles.chunks(3).map(signed_area).collect();
+            assert!(areas.iter().all(|area| *area > 0.), "{areas:?}");
+            assert!((areas.iter().sum::<f32>() - signed_area(&points).abs()).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn collinear_and_repeated_points_are_dropped() {
+        let mut points = l_shape();
+        points.insert(1, vec2!(1_f32, 0.));
+        points.insert(3, vec2!(2_f32, 0.));
+        let triangles = triangulate(&points);
+        let area: f32 = triangles.chunks(3).map(signed_area).sum();
+        assert!((area - 3.).abs() < 1e-5, "{area}");
+
+        let line = [vec2!(0_f32, 0.), vec2!(1_f32, 0.), vec2!(2_f32, 0.), vec2!(3_f32, 0.)];
+        assert!(triangulate(&line).is_empty());
+        let point = [vec2!(1_f32, 1.); 4];
+        assert!(triangulate(&point).is_empty());
+    }
+
+    #[test]
+    fn a_stroke_through_repeated_points_has_finite_vertices() {
+        let points = [vec2!(0_f32, 0.), vec2!(0_f32, 0.), vec2!(1_f32, 0.), vec2!(1_f32, 0.)];
+        for closed in [false, true] {
+            let triangles = stroke(&points, 0.5, closed);
+            assert!(!triangles.is_empty());
+            assert!(triangles.iter().all(|point| point.x.is_finite() && point.y.is_finite()));
+        }
+        let ring = ring(vec2!(0_f32, 0.), vec2!(0_f32, 0.), 1.);
+        assert!(ring.iter().all(|point| point.x.is_finite() && point.y.is_finite()));
+    }
+
+    #[test]
+    fn a_polyline_with_less_than_two_points_has_no_vertices() {
+        for points in [vec![], vec![vec2!(1_f32, 1.)]] {
+            for closed in [false, true] {
+                let shape = Shape::Polyline { points: points.clone(), thickness: 1., closed };
+                assert!(shape.triangles().is_empty());
+            }
+        }
+    }
+}
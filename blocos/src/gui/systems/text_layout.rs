@@ -0,0 +1,305 @@
+//! Layout of texts in the world: measurement, alignment, line breaks and truncation
+//!
+//! The layout is done in units of the world, a font of size `n` has lines `n` units high
+
+use rusttype::Scale;
+
+use crate::{
+    gui::{
+        fonts::{FontId, FontRegistry},
+        Rect,
+    },
+    vec2,
+    vector2::Vec2,
+};
+
+/// Where the lines of a text are placed relative to the x of its position
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HorizontalAlign {
+    /// The lines start at the position
+    #[default]
+    Left,
+    /// The lines are centered on the position
+    Center,
+    /// The lines end at the position
+    Right,
+}
+
+/// Where the lines of a text are placed relative to the y of its position
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VerticalAlign {
+    /// The top of the first line is at the position
+    #[default]
+    Top,
+    /// The middle of all the lines is at the position
+    Middle,
+    /// The baseline of the first line is at the position
+    Baseline,
+}
+
+/// How a text is laid out around its position
+///
+/// To align a text in a box, put the position at the left, center or right of the box, and use the width of the box
+/// as `max_width`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextLayout {
+    /// Horizontal alignment of each line
+    pub horizontal: HorizontalAlign,
+    /// Vertical alignment of the whole text
+    pub vertical: VerticalAlign,
+    /// Maximum width of the lines, in units of the world, `None` for no limit
+    pub max_width: Option<f32>,
+    /// Whether the lines longer than `max_width` are broken between words, else they are truncated with an ellipsis
+    pub wrap: bool,
+    /// Maximum number of lines, at least one, the last line shown ends with an ellipsis when there are more
+    pub max_lines: Option<usize>,
+    /// Distance between the baselines of two lines, relative to the height of a line of the font
+    pub line_spacing: f32,
+}
+
+impl Default for TextLayout {
+    fn default() -> Self {
+        TextLayout {
+            horizontal: HorizontalAlign::Left,
+            vertical: VerticalAlign::Top,
+            max_width: None,
+            wrap: true,
+            max_lines: None,
+            line_spacing: 1.,
+        }
+    }
+}
+
+impl TextLayout {
+    /// A layout centered both horizontally and vertically on the position, used for labels of buttons
+    pub fn centered() -> TextLayout {
+        TextLayout {
+            horizontal: HorizontalAlign::Center,
+            vertical: VerticalAlign::Middle,
+            ..Default::default()
+        }
+    }
+}
+
+/// A line of a text after the layout
+#[derive(Debug, Clone)]
+pub struct LaidOutLine {
+    /// The characters of the line, without the line break
+    pub text: String,
+    /// The start of the baseline of the line, in the world
+    pub origin: Vec2,
+    /// The width of the line, in units of the world
+    pub width: f32,
+}
+
+/// A text after the layout
+#[derive(Debug, Clone)]
+pub struct LaidOutText {
+    /// The lines of the text, from the top to the bottom
+    pub lines: Vec<LaidOutLine>,
+    /// The region covered by the lines, from the ascent of the first one to the descent of the last one
+    pub bounds: Rect,
+}
+
+/// Breaks a text in lines and places them around `position` according to `layout`
+///
+/// `\n`, `\r\n` and `\r` all start a new line.
+/// The vertical metrics are the ones of `font`, even for the characters taken from its fallbacks
+pub fn layout(
+    fonts: &FontRegistry,
+    font: FontId,
+    text: &str,
+    font_size: f32,
+    position: Vec2,
+    layout: &TextLayout,
+) -> LaidOutText {
+    let scale = Scale::uniform(font_size);
+    let measure = |line: &str| line_width(fonts, font, scale, line);
+    let ellipsis = ellipsis(fonts, font);
+
+    let mut lines = Vec::new();
+    for paragraph in text.split("\r\n").flat_map(|part| part.split(['\n', '\r'])) {
+        match layout.max_width {
+            Some(width) if layout.wrap => lines.extend(wrap(paragraph, width, &measure)),
+            Some(width) if measure(paragraph) > width => lines.push(truncate(paragraph, ellipsis, width, &measure)),
+            _ => lines.push(paragraph.to_owned()),
+        }
+    }
+    // At least one line is shown, so a limit of zero lines is the same as one
+    let max_lines = layout.max_lines.map(|max_lines| max_lines.max(1));
+    if let Some(max_lines) = max_lines.filter(|max_lines| lines.len() > *max_lines) {
+        lines.truncate(max_lines);
+        let last = lines.pop().unwrap();
+        let width = layout.max_width.unwrap_or(f32::INFINITY);
+        lines.push(truncate(&format!("{last}{ellipsis}"), ellipsis, width, &measure));
+    }
+
+    let v_metrics = fonts.font(font).v_metrics(scale);
+    let advance = (v_metrics.ascent - v_metrics.descent + v_metrics.line_gap) * layout.line_spacing;
+    let height = v_metrics.ascent - v_metrics.descent + advance * (lines.len() - 1) as f32;
+    let top = match layout.vertical {
+        VerticalAlign::Top => position.y,
+        VerticalAlign::Middle => position.y + height / 2.,
+        VerticalAlign::Baseline => position.y + v_metrics.ascent,
+    };
+
+    let lines: Vec<LaidOutLine> = lines
+        .into_iter()
+        .enumerate()
+        .map(|(i, text)| {
+            let width = measure(&text);
+            let x = match layout.horizontal {
+                HorizontalAlign::Left => position.x,
+                HorizontalAlign::Center => position.x - width / 2.,
+                HorizontalAlign::Right => position.x - width,
+            };
+            LaidOutLine {
+                origin: vec2!(x, top - v_metrics.ascent - advance * i as f32),
+                width,
+                text,
+            }
+        })
+        .collect();
+
+    let left = lines.iter().map(|line| line.origin.x).fold(f32::INFINITY, f32::min);
+    let right = lines.iter().map(|line| line.origin.x + line.width).fold(f32::NEG_INFINITY, f32::max);
+    LaidOutText {
+        bounds: Rect::from_corners(vec2!(left, top - height), vec2!(right, top)),
+        lines,
+    }
+}
+
+/// Width of a line of text, the sum of the advances of its glyphs with the kerning of consecutive glyphs of the same font
+pub fn line_width(fonts: &FontRegistry, font: FontId, scale: Scale, line: &str) -> f32 {
+    let mut width = 0.;
+    let mut last = None;
+    for c in line.chars() {
+        let (id, glyph) = fonts.glyph(font, c);
+        if let Some((last_id, last_glyph)) = last {
+            if last_id == id {
+                width += fonts.font(id).pair_kerning(scale, last_glyph, glyph.id());
+            }
+        }
+        last = Some((id, glyph.id()));
+        width += glyph.scaled(scale).h_metrics().advance_width;
+    }
+    width
+}
+
+/// Breaks a paragraph between words so each line is at most `width` wide
+///
+/// A word wider than a line is broken between its characters
+fn wrap(paragraph: &str, width: f32, measure: &impl Fn(&str) -> f32) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in paragraph.split_whitespace() {
+        let candidate = if line.is_empty() { word.to_owned() } else { format!("{line} {word}") };
+        if measure(&candidate) <= width {
+            line = candidate;
+            continue;
+        }
+        if !line.is_empty() {
+            lines.push(std::mem::take(&mut line));
+        }
+        for c in word.chars() {
+            line.push(c);
+            if measure(&line) > width && line.chars().count() > 1 {
+                line.pop();
+                lines.push(std::mem::replace(&mut line, c.to_string()));
+            }
+        }
+    }
+    lines.push(line);
+    lines
+}
+
+/// Removes characters from the end of a line until it fits in `width` with an ellipsis after it
+fn truncate(line: &str, ellipsis: &str, width: f32, measure: &impl Fn(&str) -> f32) -> String {
+    let mut line = line.strip_suffix(ellipsis).unwrap_or(line).to_owned();
+    loop {
+        let candidate = format!("{}{ellipsis}", line.trim_end());
+        if line.is_empty() || measure(&candidate) <= width {
+            return candidate;
+        }
+        line.pop();
+    }
+}
+
+/// The ellipsis character, or three dots if no font of the fallback chain has it
+fn ellipsis(fonts: &FontRegistry, font: FontId) -> &'static str {
+    if fonts.has_glyph(font, '…') {
+        "…"
+    } else {
+        "..."
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rusttype::Font;
+
+    use super::*;
+
+    /// Width of a text of the tests, one unit per character
+    fn chars(text: &str) -> f32 {
+        text.chars().count() as f32
+    }
+
+    /// A registry with only the embedded monospaced font
+    fn fonts() -> FontRegistry {
+        FontRegistry::new(Font::try_from_bytes(FontRegistry::DEFAULT_FONT).unwrap())
+    }
+
+    fn lines(text: &str, layout: &TextLayout) -> Vec<String> {
+        let fonts = fonts();
+        super::layout(&fonts, FontId::DEFAULT, text, 2., Vec2::ZERO, layout)
+            .lines
+            .into_iter()
+            .map(|line| line.text)
+            .collect()
+    }
+
+    #[test]
+    fn wraps_between_words() {
+        assert_eq!(wrap("the quick brown fox", 10., &chars), ["the quick", "brown fox"]);
+        assert_eq!(wrap("", 10., &chars), [""]);
+    }
+
+    #[test]
+    fn breaks_a_word_longer_than_a_line() {
+        assert_eq!(wrap("abcdefghij", 4., &chars), ["abcd", "efgh", "ij"]);
+        assert_eq!(wrap("to abcdefgh", 4., &chars), ["to", "abcd", "efgh"]);
+    }
+
+    #[test]
+    fn truncates_with_an_ellipsis() {
+        assert_eq!(truncate("hello world", "...", 8., &chars), "hello...");
+        assert_eq!(truncate("hello", "...", 2., &chars), "...");
+    }
+
+    #[test]
+    fn every_line_break_starts_a_line() {
+        assert_eq!(lines("a\r\nb\rc\nd", &TextLayout::default()), ["a", "b", "c", "d"]);
+        assert_eq!(lines("a\n\nb", &TextLayout::default()), ["a", "", "b"]);
+    }
+
+    #[test]
+    fn the_last_line_shown_ends_with_an_ellipsis() {
+        let ellipsis = ellipsis(&fonts(), FontId::DEFAULT);
+        let one = TextLayout { max_lines: Some(1), ..TextLayout::default() };
+        assert_eq!(lines("one\ntwo\nthree", &one), [format!("one{ellipsis}")]);
+        let none = TextLayout { max_lines: Some(0), ..TextLayout::default() };
+        assert_eq!(lines("one\ntwo", &none), [format!("one{ellipsis}")]);
+        assert_eq!(lines("one", &none), ["one"]);
+    }
+
+    #[test]
+    fn lines_too_wide_are_truncated_when_not_wrapped() {
+        let fonts = fonts();
+        let ellipsis = ellipsis(&fonts, FontId::DEFAULT);
+        let width = line_width(&fonts, FontId::DEFAULT, Scale::uniform(2.), &format!("abc{ellipsis}"));
+        let layout = TextLayout { max_width: Some(width), wrap: false, ..TextLayout::default() };
+        assert_eq!(lines("abcdefgh", &layout), [format!("abc{ellipsis}")]);
+        assert_eq!(lines("abc", &layout), ["abc"]);
+    }
+}
@@ -1,18 +1,24 @@
 //! Structs and processes for draw a text in the screen
 
 use std::borrow::Cow;
+use std::collections::HashSet;
+use std::ops::Range;
 use std::rc::Rc;
 
-use glium::texture::{self, Texture2d};
-use glium::{implement_vertex, uniform, Display, Frame, Program, Surface};
+use glium::texture::{self, Texture2d, TextureCreationError};
+use glium::{implement_vertex, uniform, DrawParameters, Program, Surface};
 
-use rusttype::gpu_cache::Cache;
-use rusttype::{point, vector, Font, PositionedGlyph, Scale};
-use std::error::Error;
-use transform::Camera;
+use rusttype::gpu_cache::{Cache, CacheWriteErr};
+use rusttype::{point, PositionedGlyph, Scale};
+use crate::gui::Camera;
 
-use crate::core::rgb::Rgb;
-use crate::gui::systems::rgb_to_arr;
+use crate::core::rgb::Rgba;
+use crate::gui::assets::{EmbeddedShader, ShaderSource};
+use crate::gui::backend::Backend;
+use crate::gui::GuiError;
+use crate::gui::fonts::{FontId, FontRegistry};
+use crate::gui::systems::{compile, layout, BlendMode, LaidOutText, Layer, TextLayout, VertexStream};
+use crate::gui::Rect;
 use crate::vector2::Vec2;
 use crate::gui::transform;
 
@@ -27,90 +33,176 @@ implement_vertex!(Vertex, position, tex_coords, colour);
 
 /// An object representing a text on the screen
 pub struct TextObject {
-    /// The starting position of the text (top left corner)
+    /// The position of the text, where it is placed according to `layout`
     pub position: Vec2,
     /// Color of text
-    pub color: Rgb,
+    pub color: Rgba,
     /// the text
     pub text: String,
     /// size of font
     pub font_size: f32,
+    /// The font of the text, from the [FontRegistry] of the text system
+    pub font: FontId,
+    /// The layer of the text, objects of higher layers are drawn over it
+    pub layer: Layer,
+    /// How the text is blended with what is under it
+    pub blend: BlendMode,
+    /// Alignment, line breaks and truncation of the text
+    pub layout: TextLayout,
 }
 
 
+/// A text of the frame waiting for its glyphs to be cached
+struct PendingText<'a> {
+    /// Glyphs positioned in pixels, with the origin of the world at (0, 0) and y going down, with their fonts
+    glyphs: Vec<(FontId, PositionedGlyph<'a>)>,
+    colour: [f32; 4],
+    /// Number of pixels of the glyphs in a unit of the world
+    pixels_per_unit: f32,
+}
+
+/// How the texture of the glyph cache of the [TextSystem] was used in the last frame
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GlyphCacheStats {
+    /// Width of the texture in pixels
+    pub width: u32,
+    /// Height of the texture in pixels
+    pub height: u32,
+    /// Number of different glyphs of the texts of the frame
+    pub glyphs: usize,
+    /// Pixels of the texture covered by the glyphs of the frame
+    pub used_pixels: u32,
+    /// Number of glyphs written to the texture in the frame, because they were not in the cache
+    pub uploaded: usize,
+}
+
+impl GlyphCacheStats {
+    /// Fraction of the texture covered by the glyphs of the frame, from 0 to 1
+    pub fn usage(&self) -> f32 {
+        let total = self.width as f32 * self.height as f32;
+        if total > 0. {
+            self.used_pixels as f32 / total
+        } else {
+            0.
+        }
+    }
+}
+
 /// System used to draw text on the screen
 pub struct TextSystem<'a> {
     program: Program,
     cache: Cache<'a>,
     cache_tex: Rc<Texture2d>,
-    font: Rc<Font<'a>>,
+    /// The fonts of the texts, sharing the cache
+    pub fonts: FontRegistry,
+    /// Texts of the frame
+    pending: Vec<PendingText<'a>>,
+    /// Vertices of the texts of the frame
+    stream: VertexStream<Vertex>,
+    /// Usage of the cache in the last frame uploaded
+    cache_stats: GlyphCacheStats,
 }
 
 impl<'a> TextSystem<'a> {
-    /// Create a new `TextSystem`
-    pub fn new(display: &Display) -> Result<TextSystem<'static>, Box<dyn Error>> {
-        let vertex_shader_src = include_str!("./shaders/text.vert");
-        let fragment_shader_src = include_str!("./shaders/text.frag");
-        let program =
-            glium::Program::from_source(display, vertex_shader_src, fragment_shader_src, None)
-                .unwrap();
-        let dpi_factor = display.gl_window().window().scale_factor();
+    /// The shaders of the system, embedded as fallback for the ones in the assets
+    pub const SHADER: EmbeddedShader = EmbeddedShader {
+        name: "text",
+        vertex: include_str!("../../assets/shaders/text.vert"),
+        fragment: include_str!("../../assets/shaders/text.frag"),
+    };
+
+    /// Largest side of the glyph cache in pixels, the cache grows up to it when the glyphs of a frame do not fit
+    const MAX_CACHE_SIZE: u32 = 4096;
+
+    /// Create a new `TextSystem` drawing with the fonts of a registry
+    pub fn new(display: &Backend, source: &ShaderSource, fonts: FontRegistry) -> Result<TextSystem<'static>, GuiError> {
+        let program = compile(display, source, Self::SHADER)?;
+        let dpi_factor = display.scale_factor();
         let (cache_width, cache_height) =
             ((512.0 * dpi_factor) as u32, (512.0 * dpi_factor) as u32);
         let cache = Cache::builder()
             .dimensions(cache_width, cache_height)
             .build();
 
-        let cache_tex = Rc::new(texture::Texture2d::with_format(
-            display,
-            texture::RawImage2d {
-                data: Cow::Owned(vec![128u8; cache_width as usize * cache_height as usize]),
-                width: cache_width,
-                height: cache_height,
-                format: texture::ClientFormat::U8,
-            },
-            texture::UncompressedFloatFormat::U8,
-            texture::MipmapsOption::NoMipmap,
-        )?);
-
-        let font_data = include_bytes!("../../assets/UbuntuMono-R.ttf");
-        let Some(font) = Font::try_from_bytes(font_data as &[u8]) else {
-			panic!();
-		};
+        let cache_tex = Rc::new(cache_texture(display, cache_width, cache_height)?);
 
         Ok(TextSystem {
             program,
             cache,
             cache_tex,
-            font: Rc::new(font),
+            fonts,
+            pending: Vec::new(),
+            stream: VertexStream::new(),
+            cache_stats: GlyphCacheStats::default(),
         })
     }
-    /// draw a text in the screen
-    pub fn draw(
+    /// Replaces the shaders of the system
+    pub fn set_program(&mut self, program: Program) {
+        self.program = program;
+    }
+    /// Adds a text to the frame, returning its index
+    ///
+    /// The glyphs are only put in the cache when the frame is uploaded, so the texts of a frame can not evict each other
+    pub fn push(
         &mut self,
-        target: &mut Frame,
-        display: &Display,
+        display: &Backend,
         camera: &Camera,
         object: TextObject
-    )  {
-        
-        let camera_transform = camera.transformation();
-		let dpi_factor = display.gl_window().window().scale_factor();
-        let (width, height): (u32, u32) = display
-        .gl_window()
-        .window()
-        .inner_size()
-        .into();
-        let factor_word_to_screen = (height as f32)/camera.world.size.y; 
-        let dpi_factor = dpi_factor  as f32 ;
-		let glyphs = self.gliphs(&object, dpi_factor, factor_word_to_screen, (width as f32 * factor_word_to_screen) as i32 );
-        for glyph in &glyphs {
-            let new = glyph.clone();
-            self.cache.queue_glyph(0, new);
+    ) -> usize {
+        let (_, height) = display.dimensions();
+        let pixels_per_unit = height as f32 * camera.scale().y;
+        let laid_out = layout(&self.fonts, object.font, &object.text, object.font_size, object.position, &object.layout);
+        self.pending.push(PendingText {
+            glyphs: self.glyphs(&laid_out, object.font, object.font_size, pixels_per_unit),
+            colour: object.color.to_array(),
+            pixels_per_unit,
+        });
+        self.pending.len() - 1
+    }
+
+    /// The region of the world covered by a text, without drawing it
+    pub fn measure(&self, object: &TextObject) -> Rect {
+        layout(&self.fonts, object.font, &object.text, object.font_size, object.position, &object.layout).bounds
+    }
+
+    /// Puts the glyphs of the texts of the frame in the cache and sends their vertices to the GPU
+    ///
+    /// When the glyphs do not fit, the cache grows and they are cached again
+    pub fn upload(&mut self, display: &Backend) -> Result<(), GuiError> {
+        let mut uploaded = 0;
+        while let Err(err) = self.cache_glyphs(&mut uploaded) {
+            let (width, height) = self.cache.dimensions();
+            if width >= Self::MAX_CACHE_SIZE || height >= Self::MAX_CACHE_SIZE {
+                return Err(GuiError::GlyphCacheOverflow { width, height });
+            }
+            let (width, height) = ((width * 2).min(Self::MAX_CACHE_SIZE), (height * 2).min(Self::MAX_CACHE_SIZE));
+            eprintln!("{err}, growing the glyph cache to {width}x{height} pixels");
+            self.cache.to_builder().dimensions(width, height).rebuild(&mut self.cache);
+            self.cache.clear_queue();
+            self.cache_tex = Rc::new(cache_texture(display, width, height)?);
         }
+
+        for text in &self.pending {
+            self.stream.push(Self::vertices(&self.cache, text));
+        }
+        self.stream.upload(display)?;
+        self.cache_stats = self.measure_cache(uploaded);
+        Ok(())
+    }
+
+    /// Queues the glyphs of the texts of the frame and writes the ones not cached yet to the texture,
+    /// adding how many were written to `uploaded`
+    fn cache_glyphs(&mut self, uploaded: &mut usize) -> Result<(), CacheWriteErr> {
+        for text in &self.pending {
+            for (font, glyph) in &text.glyphs {
+                self.cache.queue_glyph(font.index(), glyph.clone());
+            }
+        }
+        let cache_tex = &self.cache_tex;
         self.cache
             .cache_queued(|rect, data| {
-                self.cache_tex.main_level().write(
+                *uploaded += 1;
+                cache_tex.main_level().write(
                     glium::Rect {
                         left: rect.min.x,
                         bottom: rect.min.y,
@@ -125,58 +217,91 @@ impl<'a> TextSystem<'a> {
                     },
                 );
             })
-            .unwrap();
+            .map(|_| ())
+    }
 
-		let vertex_buffer = self.vertex_buffer(display, camera, &object, glyphs);
+    /// Usage of the glyph cache by the texts of the frame, after they were cached
+    fn measure_cache(&self, uploaded: usize) -> GlyphCacheStats {
+        let (width, height) = self.cache.dimensions();
+        let mut rects = HashSet::new();
+        for text in &self.pending {
+            for (font, glyph) in &text.glyphs {
+                if let Ok(Some((uv, _))) = self.cache.rect_for(font.index(), glyph) {
+                    let corner = |x: f32, y: f32| ((x * width as f32).round() as u32, (y * height as f32).round() as u32);
+                    rects.insert((corner(uv.min.x, uv.min.y), corner(uv.max.x, uv.max.y)));
+                }
+            }
+        }
+        GlyphCacheStats {
+            width,
+            height,
+            glyphs: rects.len(),
+            used_pixels: rects.iter().map(|((x0, y0), (x1, y1))| (x1 - x0) * (y1 - y0)).sum(),
+            uploaded,
+        }
+    }
+
+    /// How the glyph cache was used in the last frame uploaded
+    pub fn cache_stats(&self) -> GlyphCacheStats {
+        self.cache_stats
+    }
+
+    /// Number of vertices of the texts of the frame
+    pub fn vertex_count(&self) -> usize {
+        self.stream.vertex_count()
+    }
+
+    /// Removes the texts of the frame
+    pub fn clear(&mut self) {
+        self.pending.clear();
+        self.stream.clear();
+    }
+
+    /// draw a range of consecutive texts in the screen, with a single draw call and the same blending
+    pub fn draw(
+        &self,
+        target: &mut impl Surface,
+        camera_transform: transform::Transform,
+        blend: BlendMode,
+        parameters: &DrawParameters,
+        objects: Range<usize>,
+    ) -> Result<(), GuiError> {
+        let Some(vertices) = self.stream.slice(objects) else {
+            return Ok(());
+        };
 		let uniforms = uniform! {
             matrix:  camera_transform.0,
-            tex: self.cache_tex.sampled().magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest)
+            tex: self.cache_tex.sampled().magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest),
+            multiply: blend.multiply(),
         };
         target
             .draw(
-                &vertex_buffer,
+                vertices,
                 glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList),
                 &self.program,
                 &uniforms,
-                &glium::DrawParameters {
-                    blend: glium::Blend::alpha_blending(),
-                    ..Default::default()
-                },
-            )
-            .unwrap();
+                parameters,
+            )?;
+        Ok(())
     }
 
-    fn vertex_buffer(
-        &mut self,
-        display: &Display,
-        camera: &Camera,
-        object: &TextObject,
-        glyphs: Vec<PositionedGlyph<'_>>,
-    ) -> glium::VertexBuffer<Vertex> {
-        let scale = camera.scale();
-        let colour =  rgb_to_arr(object.color);
-        let (screen_width, screen_height) = {
-            let (w, h) = display.get_framebuffer_dimensions();
-            (w as f32, h as f32)
-        };
-
-        let origin = point(object.position.x, object.position.y);
-        let vertices: Vec<Vertex> = glyphs
+    fn vertices(cache: &Cache<'a>, text: &PendingText<'a>) -> Vec<Vertex> {
+        let PendingText { glyphs, colour, pixels_per_unit } = text;
+        let (colour, pixels_per_unit) = (*colour, *pixels_per_unit);
+        glyphs
             .iter()
-            .flat_map(|g| self.cache.rect_for(0, g).ok())
+            .flat_map(|(font, g)| cache.rect_for(font.index(), g).ok())
             .flatten()
             .flat_map(|(uv_rect, screen_rect)| {
                 let gl_rect = rusttype::Rect {
-                    min: origin
-                        + vector(
-                            screen_rect.min.x as f32 / (screen_width * scale.x),
-                            -screen_rect.min.y as f32 / (screen_height * scale.y),
-                        ),
-                    max: origin
-                        + vector(
-                            screen_rect.max.x as f32 / (screen_width * scale.x),
-                            -screen_rect.max.y as f32 / (screen_height * scale.y),
-                        ),
+                    min: point(
+                        screen_rect.min.x as f32 / pixels_per_unit,
+                        -screen_rect.min.y as f32 / pixels_per_unit,
+                    ),
+                    max: point(
+                        screen_rect.max.x as f32 / pixels_per_unit,
+                        -screen_rect.max.y as f32 / pixels_per_unit,
+                    ),
                 };
                 [
                     Vertex {
@@ -211,58 +336,52 @@ impl<'a> TextSystem<'a> {
                     },
                 ]
             })
-            .collect();
-        glium::VertexBuffer::new(display, &vertices).unwrap()
+            .collect()
     }
-    fn gliphs(
+    /// Positions the glyphs of the lines of a text, rasterized with `pixels_per_unit` pixels in a unit of the world
+    ///
+    /// Each character takes its glyph from the first font of the fallback chain of `font` that has it
+    fn glyphs(
         &self,
-        object: &TextObject,
-        dpi_factor: f32,
-        factor_word_to_screen: f32,
-        width: i32,
-    ) -> Vec<PositionedGlyph<'a>> {
-        let font = &self.font;
-        let scale = Scale::uniform(object.font_size * dpi_factor * factor_word_to_screen);
-        let text: &str = &object.text;
+        text: &LaidOutText,
+        font: FontId,
+        font_size: f32,
+        pixels_per_unit: f32,
+    ) -> Vec<(FontId, PositionedGlyph<'a>)> {
+        let scale = Scale::uniform(font_size * pixels_per_unit);
         let mut result = Vec::new();
-        let v_metrics = font.v_metrics(scale);
-        let advance_height = v_metrics.ascent - v_metrics.descent + v_metrics.line_gap;
-        let mut caret = point(0., v_metrics.ascent);
-        let mut last_glyph_id = None;
-        for c in text.chars() {
-            if c.is_control() {
-                match c {
-                    '\r' => {
-                        caret = point(0.0, caret.y + advance_height);
+        for line in &text.lines {
+            let mut caret = point(line.origin.x * pixels_per_unit, -line.origin.y * pixels_per_unit);
+            let mut last_glyph = None;
+            for c in line.text.chars().filter(|c| !c.is_control()) {
+                let (id, base_glyph) = self.fonts.glyph(font, c);
+                if let Some((last_id, last_glyph_id)) = last_glyph {
+                    if last_id == id {
+                        caret.x += self.fonts.font(id).pair_kerning(scale, last_glyph_id, base_glyph.id());
                     }
-                    '\n' => {}
-                    _ => {}
                 }
-                continue;
+                last_glyph = Some((id, base_glyph.id()));
+                let glyph = base_glyph.scaled(scale);
+                let advance = glyph.h_metrics().advance_width;
+                result.push((id, glyph.positioned(caret)));
+                caret.x += advance;
             }
-            let mut first_of_line = false;
-            let base_glyph = font.glyph(c);
-            if let Some(id) = last_glyph_id.take() {
-                caret.x += font.pair_kerning(scale, id, base_glyph.id());
-            } else {
-                first_of_line = true;
-            }
-            last_glyph_id = Some(base_glyph.id());
-            let glyph = base_glyph.scaled(scale);
-            if first_of_line {
-                caret.x += glyph.h_metrics().left_side_bearing;
-            }
-            let mut glyph = glyph.positioned(caret);
-            if let Some(bb) = glyph.pixel_bounding_box() {
-                if bb.max.x > width {
-                    caret = point(0.0, caret.y + advance_height);
-                    glyph.set_position(caret);
-                    last_glyph_id = None;
-                }
-            }
-            caret.x += glyph.unpositioned().h_metrics().advance_width;
-            result.push(glyph);
         }
         result
     }
 }
+
+/// A texture of `width` x `height` pixels for the glyph cache, with one channel
+fn cache_texture(display: &Backend, width: u32, height: u32) -> Result<Texture2d, TextureCreationError> {
+    Texture2d::with_format(
+        display,
+        texture::RawImage2d {
+            data: Cow::Owned(vec![128u8; width as usize * height as usize]),
+            width,
+            height,
+            format: texture::ClientFormat::U8,
+        },
+        texture::UncompressedFloatFormat::U8,
+        texture::MipmapsOption::NoMipmap,
+    )
+}
@@ -1,6 +1,5 @@
 use crate::{vector2::Vec2, vec2};
 use std::ops::Deref;
-use super::Rect;
 
 /// A 4x4 matrix of floating point numbers.
 ///
@@ -44,6 +43,7 @@ fn mult_matrix<const A: usize, const B: usize, const C: usize>(
 ///
 /// This struct represents a transformation matrix. It is a wrapper around a 4x4 matrix of floating point numbers.
 /// It provides methods to apply transformations such as translation and scaling.
+#[derive(Debug, Clone, Copy)]
 pub struct Transform(pub Matrix);
 
 impl Deref for Transform {
@@ -90,6 +90,24 @@ impl Transform {
     pub fn scale(self, scale: Vec2) -> Self {
         self.apply_trasnformation(&transform_funcs::get_scale(scale))
     }
+
+    /// Applies a rotation transformation.
+    ///
+    /// This method takes an angle in radians and rotates counterclockwise around the origin.
+    pub fn rotate(self, angle: f32) -> Self {
+        self.apply_trasnformation(&transform_funcs::get_rotation(angle))
+    }
+
+    /// Applies the transformation to a point.
+    ///
+    /// This method is used to transform the vertices in the CPU, so objects with different transformations can share a draw call.
+    pub fn apply(&self, point: Vec2) -> Vec2 {
+        let m = &self.0;
+        vec2!(
+            m[0][0] * point.x + m[1][0] * point.y + m[3][0],
+            m[0][1] * point.x + m[1][1] * point.y + m[3][1]
+        )
+    }
 }
 
 /// A module containing helper functions for transformations.
@@ -126,60 +144,17 @@ pub mod transform_funcs {
             [0., 0., 0., 1.],
         ]
     }
-}
 
-/// A camera.
-///
-/// This struct represents a camera. It has a world rectangle and a target rectangle.
-/// The world rectangle represents the area of the world that the camera can see.
-/// The target rectangle represents the area of the screen where the world will be rendered.
-pub struct Camera {
-    /// The world rectangle.
-    ///
-    /// This field represents the area of the world that the camera can see.
-    /// It is a rectangle that defines the boundaries of the world in the game world coordinates.
-    pub world: Rect,
-
-    /// The target rectangle.
-    ///
-    /// This field represents the area of the screen where the world will be rendered.
-    /// It is a rectangle that defines the boundaries of the screen in the screen coordinates.
-    pub target: Rect,
-}
-impl Camera {
-    /// Returns a transformation matrix for the camera.
+    /// Returns a rotation matrix.
     ///
-    /// This method takes a 2D vector and returns a transformation matrix that represents the camera's transformation.
-    /// The transformation is calculated based on the camera's world and target rectangles.
-    pub fn transformation(&self) -> Transform {
-        let scale = self.scale();
-        Transform::default()
-            .translate(-self.world.center)
-            .scale(scale)
-            .translate(self.target.center)
-    }
-
-    /// The element-wise ratio of target to word size
-    pub fn scale(&self) -> Vec2 {
-        Vec2 {
-            x: self.target.size.x / self.world.size.x,
-            y: self.target.size.y / self.world.size.y,
-        }
-    }
-    /// transforms a position from the world to the target
-    pub fn world_to_target(&self, mut pos : Vec2) -> Vec2 {
-        let scale = self.scale();
-        pos -= self.world.center;
-        pos = vec2!(pos.x * scale.x, pos.y * scale.y);
-        pos += self.target.center;
-        pos
-    }
-    /// transforms a position from the target to the world 
-    pub fn target_to_world(&self, mut pos: Vec2)-> Vec2  {
-        let scale = self.scale();
-        pos -= self.target.center;
-        pos = vec2!(pos.x / scale.x, pos.y / scale.y);
-        pos += self.world.center;
-        pos
+    /// This function takes an angle in radians and returns a 4x4 matrix that represents a counterclockwise rotation around the origin.
+    pub fn get_rotation(angle: f32) -> Matrix {
+        let (sin, cos) = angle.sin_cos();
+        [
+            [cos, sin, 0., 0.],
+            [-sin, cos, 0., 0.],
+            [0., 0., 1., 0.],
+            [0., 0., 0., 1.],
+        ]
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,145 @@
+//! Configuration of the window of the interface, used when it is created and applied again when it changes
+//!
+//! Vsync and multisampling belong to the OpenGL context, so they only change when the window is created again,
+//! the rest is applied to the open window
+use std::{error::Error, path::PathBuf};
+
+use glium::glutin::{
+    self,
+    dpi::PhysicalSize,
+    monitor::MonitorHandle,
+    window::{Fullscreen, Icon, Window, WindowBuilder},
+    NotCurrent,
+};
+
+/// How the window covers the monitor
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FullscreenMode {
+    /// A window with decorations, of the size of the config
+    #[default]
+    Windowed,
+    /// A window without decorations covering the monitor, keeping its video mode
+    Borderless,
+    /// Takes the monitor, in its largest video mode
+    Exclusive,
+}
+
+/// How the window of the [Interface](super::interface::Interface) looks and behaves
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WindowConfig {
+    /// Text in the title bar
+    pub title: String,
+    /// Size of the inside of the window in pixels, `None` maximizes it
+    pub size: Option<(u32, u32)>,
+    /// Smallest size the window can be resized to, in pixels
+    pub min_size: Option<(u32, u32)>,
+    /// Whether the player can resize the window
+    pub resizable: bool,
+    /// How the window covers the monitor
+    pub fullscreen: FullscreenMode,
+    /// Whether the presentation of each frame waits the vertical synchronization of the monitor
+    pub vsync: bool,
+    /// Samples per pixel of multisample anti-aliasing, 0 disables it
+    pub samples: u16,
+    /// PNG file shown as the icon of the window
+    pub icon: Option<PathBuf>,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        WindowConfig {
+            title: "blocos".to_owned(),
+            size: None,
+            min_size: None,
+            resizable: false,
+            fullscreen: FullscreenMode::Windowed,
+            vsync: false,
+            samples: 0,
+            icon: None,
+        }
+    }
+}
+
+impl WindowConfig {
+    /// The builder of a window with this config, in `monitor` when fullscreen
+    pub(crate) fn window_builder(&self, monitor: Option<MonitorHandle>) -> WindowBuilder {
+        let mut builder = WindowBuilder::new()
+            .with_title(&self.title)
+            .with_decorations(true)
+            .with_resizable(self.resizable)
+            .with_always_on_top(false)
+            .with_window_icon(self.load_icon())
+            .with_fullscreen(self.fullscreen(monitor));
+        builder = match self.size {
+            Some((width, height)) => builder.with_inner_size(PhysicalSize::new(width, height)),
+            None => builder.with_maximized(true),
+        };
+        if let Some((width, height)) = self.min_size {
+            builder = builder.with_min_inner_size(PhysicalSize::new(width, height));
+        }
+        builder
+    }
+
+    /// The builder of the OpenGL context, with the vsync and the multisampling of the config
+    pub(crate) fn context_builder(&self) -> glutin::ContextBuilder<'static, NotCurrent> {
+        glutin::ContextBuilder::new().with_vsync(self.vsync).with_multisampling(self.samples)
+    }
+
+    /// Applies to an open window the changes from `old`, the config it was created with
+    ///
+    /// Returns whether some change, of the vsync or the multisampling, only applies when the window is created again
+    pub(crate) fn apply(&self, old: &WindowConfig, window: &Window) -> bool {
+        if self.title != old.title {
+            window.set_title(&self.title);
+        }
+        if self.resizable != old.resizable {
+            window.set_resizable(self.resizable);
+        }
+        if self.min_size != old.min_size {
+            window.set_min_inner_size(self.min_size.map(|(width, height)| PhysicalSize::new(width, height)));
+        }
+        if self.icon != old.icon {
+            window.set_window_icon(self.load_icon());
+        }
+        if self.fullscreen != old.fullscreen {
+            window.set_fullscreen(self.fullscreen(window.current_monitor()));
+        }
+        // The size of a fullscreen window is the one of the monitor, it is applied when it goes back to a window
+        if self.fullscreen == FullscreenMode::Windowed && (self.size != old.size || self.fullscreen != old.fullscreen) {
+            match self.size {
+                Some((width, height)) => {
+                    window.set_maximized(false);
+                    window.set_inner_size(PhysicalSize::new(width, height));
+                }
+                None => window.set_maximized(true),
+            }
+        }
+        self.vsync != old.vsync || self.samples != old.samples
+    }
+
+    /// The fullscreen of the mode in `monitor`, `None` for a window or when there is no video mode to take
+    fn fullscreen(&self, monitor: Option<MonitorHandle>) -> Option<Fullscreen> {
+        match self.fullscreen {
+            FullscreenMode::Windowed => None,
+            FullscreenMode::Borderless => Some(Fullscreen::Borderless(monitor)),
+            FullscreenMode::Exclusive => {
+                let mode = monitor?.video_modes().max_by_key(|mode| {
+                    let size = mode.size();
+                    (size.width * size.height, mode.refresh_rate_millihertz())
+                })?;
+                Some(Fullscreen::Exclusive(mode))
+            }
+        }
+    }
+
+    /// The icon of the config, `None` without one or when the file cannot be read
+    fn load_icon(&self) -> Option<Icon> {
+        let path = self.icon.as_ref()?;
+        let icon = || -> Result<Icon, Box<dyn Error>> {
+            let image = image::open(path)?.to_rgba8();
+            let (width, height) = image.dimensions();
+            Ok(Icon::from_rgba(image.into_raw(), width, height)?)
+        };
+        icon().map_err(|err| eprintln!("could not load the icon {}: {err}", path.display())).ok()
+    }
+}
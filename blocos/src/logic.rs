@@ -2,19 +2,34 @@
 mod level_scene;
 mod home_scene;
 mod bag;
+mod board;
+mod finesse;
+mod layer;
+#[cfg(test)]
+mod golden;
+pub mod net;
+mod save;
+mod settings;
+mod stats;
+mod versus_scene;
 use glium::glutin::event::VirtualKeyCode;
 use home_scene::HomeScene;
 use level_scene::LevelScene;
+use net::Role;
+use settings::Settings;
+use versus_scene::VersusScene;
 
 use crate::{gui::{
     interface::{Canvas, Interface},
-    Rect, systems::SolidColorObject,
-}, vector2::Vec2, core::rgb};
+    debug::DebugInfo, fonts::FontId, GuiError, Rect,
+    systems::{BlendMode, PostProcess, TextLayout, TextObject}, window::WindowConfig,
+}, vector2::Vec2, core::rgb::Rgba};
 #[derive(PartialEq, Eq)]
 
 pub enum Scene {
     HomeScene,
     LevelScene,
+    VersusScene,
 }
 
 /// The state of all game logic
@@ -22,56 +37,203 @@ pub struct GameState {
     actual_scene: Scene,
     level_scene: LevelScene,
     home_scene: HomeScene,
+    versus_scene: VersusScene,
+    settings: Settings,
+    /// Microseconds left of the transition to the current scene
+    transition: u128,
+    /// Message shown over the scene and the microseconds left to show it
+    notice: Option<(String, u128)>,
+    /// Keys pressed since the last tick, applied at the start of the next one
+    keys: Vec<VirtualKeyCode>,
 }
 
 impl GameState {
+    /// Duration of the transition between scenes, in microseconds
+    const TRANSITION_MICROS: u128 = 400_000;
+    /// How long a notice stays on the screen, in microseconds
+    const NOTICE_MICROS: u128 = 4_000_000;
+
     /// Create a new GameState
-    pub fn new(interface: &Interface) -> GameState {
-        GameState {
+    pub fn new(interface: &Interface) -> Result<GameState, GuiError> {
+        let mut home_scene = HomeScene::new(interface)?;
+        home_scene.has_save = LevelScene::has_save();
+        let level_scene = LevelScene::new(interface)?;
+        Ok(GameState {
             actual_scene: Scene::HomeScene,
-            level_scene: LevelScene::new(interface),
-            home_scene: HomeScene::new(interface)
-        }
+            versus_scene: VersusScene::new(level_scene.block()),
+            level_scene,
+            home_scene,
+            settings: Settings::load(),
+            transition: 0,
+            notice: None,
+            keys: Vec::new(),
+        })
+    }
+    /// Hosts or joins an online versus and shows it
+    pub fn start_versus(&mut self, role: Role) {
+        self.versus_scene.start(role);
+        self.update_scene(Scene::VersusScene);
     }
     fn update_scene(&mut self, new_scene: Scene){
+        if self.actual_scene == Scene::LevelScene && new_scene == Scene::HomeScene {
+            self.save_level();
+        }
+        // The opponent would wait forever for the inputs of a session that is not updated anymore
+        if self.actual_scene == Scene::VersusScene && new_scene != Scene::VersusScene {
+            self.versus_scene.leave();
+        }
+        if self.actual_scene != new_scene {
+            self.transition = Self::TRANSITION_MICROS;
+        }
         self.actual_scene = new_scene;        
     }
+    /// Saves the game in progress, so the home can offer to continue it
+    fn save_level(&mut self) {
+        match self.level_scene.save() {
+            Ok(()) => self.home_scene.has_save = LevelScene::has_save(),
+            Err(err) => eprintln!("could not save the game: {err}"),
+        }
+    }
+    /// Builds again what was made from the files of the assets in `changed`, reported by
+    /// [Interface::reload_assets]
+    pub fn reload_assets(&mut self, interface: &Interface, changed: &[String]) {
+        match self.level_scene.reload_skin(interface, changed) {
+            Ok(true) => {
+                self.versus_scene.set_block(self.level_scene.block());
+                println!("rebuilt the atlas of the level");
+            }
+            Ok(false) => {}
+            Err(err) => eprintln!("could not rebuild the atlas of the level: {err}"),
+        }
+    }
+    /// Called when the window is closing, saves the game in progress and leaves the versus
+    pub fn on_exit(&mut self) {
+        self.save_level();
+        self.versus_scene.leave();
+    }
     /// Returns the region in the world being shown
     pub fn world_region(&self) -> Rect {
         match self.actual_scene {
             Scene::HomeScene => self.home_scene.world_region(),
             Scene::LevelScene => self.level_scene.world_region(),
+            Scene::VersusScene => self.versus_scene.world_region(),
+        }
+    }
+    /// What the debug overlay shows of the game, with the mouse at `mouse` in the world
+    pub fn debug_info(&self, mouse: Vec2) -> DebugInfo {
+        let (scene, regions) = match self.actual_scene {
+            Scene::HomeScene => ("home", self.home_scene.ui_regions()),
+            Scene::LevelScene => ("level", self.level_scene.ui_regions()),
+            Scene::VersusScene => ("versus", Vec::new()),
+        };
+        DebugInfo { scene, mouse, regions }
+    }
+    /// Returns how hard the camera should shake for what happened in the scene since the last call
+    pub fn take_trauma(&mut self) -> f32 {
+        match self.actual_scene {
+            Scene::LevelScene => self.level_scene.take_trauma(),
+            Scene::HomeScene | Scene::VersusScene => 0.,
         }
     }
-    /// Receives the keypress event
+    /// Enables the effects of the settings and animates the transition between scenes
+    pub fn apply_effects(&self, post_process: &mut PostProcess) {
+        let progress = self.transition as f32 / Self::TRANSITION_MICROS as f32;
+        self.settings.apply(post_process, progress);
+    }
+    /// The config of the window with the settings applied, the rest as in `config`
+    pub fn window_config(&self, config: &WindowConfig) -> WindowConfig {
+        self.settings.window_config(config)
+    }
+    /// Receives the keypress event, queueing the key to the next tick
+    ///
+    /// The keys are applied with the simulation, so the game responds the same at any number of frames per second
     pub fn key_down(&mut self, key: VirtualKeyCode) {
+        self.keys.push(key);
+    }
+    /// Applies a key pressed
+    ///
+    /// The keys F1 to F6 toggle the effects and the fullscreen of the settings in any scene
+    fn apply_key(&mut self, key: VirtualKeyCode) {
+        if self.settings.key_down(key) {
+            self.settings.save();
+            return;
+        }
         let new_scene = match self.actual_scene {
             Scene::HomeScene => self.home_scene.key_down(key),
             Scene::LevelScene => self.level_scene.key_down(key),
+            Scene::VersusScene => self.versus_scene.key_down(key),
         };
         self.update_scene(new_scene);
         match key {
-            VirtualKeyCode::Key1 => self.actual_scene = Scene::HomeScene,
-            VirtualKeyCode::Key2 => self.actual_scene = Scene::LevelScene,
+            VirtualKeyCode::Key1 => self.update_scene(Scene::HomeScene),
+            VirtualKeyCode::Key2 => self.update_scene(Scene::LevelScene),
             _ => (),
         }
     }
 
     /// Recives the mouse click event
     pub fn on_click(&mut self, position: Vec2){
-        let new_scene = match self.actual_scene {
+        let mut new_scene = match self.actual_scene {
             Scene::HomeScene => self.home_scene.on_click(position),
             Scene::LevelScene => self.level_scene.on_click(position),
+            Scene::VersusScene => self.versus_scene.on_click(position),
         };
+        if self.home_scene.take_continue() {
+            if let Err(err) = self.level_scene.resume() {
+                // The save was moved away if it was invalid, so the home stops offering it
+                self.show_notice(&format!("could not continue the saved game: {err}"));
+                self.home_scene.has_save = LevelScene::has_save();
+                new_scene = Scene::HomeScene;
+            }
+        }
         self.update_scene(new_scene);
     }
 
-    /// Updates the game state and draws on the table
-    pub fn update(&mut self, canvas: &mut Canvas, delta_t: u128) {
+    /// Advances the game state by one tick of the simulation, of `delta_t` microseconds
+    pub fn tick(&mut self, delta_t: u128) {
+        for key in std::mem::take(&mut self.keys) {
+            self.apply_key(key);
+        }
+        self.transition = self.transition.saturating_sub(delta_t);
+        self.notice = self.notice.take().and_then(|(text, left)| (left > delta_t).then(|| (text, left - delta_t)));
         let new_scene =match self.actual_scene {
-            Scene::HomeScene => self.home_scene.update(canvas, delta_t),
-            Scene::LevelScene => self.level_scene.update(canvas, delta_t),
+            Scene::HomeScene => self.home_scene.update(delta_t),
+            Scene::LevelScene => self.level_scene.update(delta_t),
+            Scene::VersusScene => self.versus_scene.update(),
         };
         self.update_scene(new_scene);
     }
+
+    /// Draws the actual scene on the canvas
+    ///
+    /// `alpha` is how far the real time is between the last tick and the next one, from 0 to 1
+    pub fn draw(&self, canvas: &mut Canvas, alpha: f32) {
+        match self.actual_scene {
+            Scene::HomeScene => self.home_scene.draw(canvas),
+            Scene::LevelScene => self.level_scene.draw(canvas, alpha),
+            Scene::VersusScene => self.versus_scene.draw(canvas, alpha),
+        }
+        if let Some((text, _)) = &self.notice {
+            let region = self.world_region();
+            canvas.draw(TextObject {
+                text: text.clone(),
+                position: vec2!(region.left() + region.size.x * 0.05, region.top() - region.size.y * 0.05),
+                color: Rgba::WHITE,
+                font_size: region.size.y / 40.,
+                font: FontId::DEFAULT,
+                layer: layer::HUD,
+                blend: BlendMode::Alpha,
+                layout: TextLayout {
+                    max_width: Some(region.size.x * 0.9),
+                    ..Default::default()
+                },
+            });
+        }
+    }
+
+    /// Shows `text` over every scene for a few seconds
+    pub fn show_notice(&mut self, text: &str) {
+        eprintln!("{text}");
+        self.notice = Some((text.to_owned(), Self::NOTICE_MICROS));
+    }
 }
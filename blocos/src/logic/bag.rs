@@ -1,26 +1,31 @@
-use rand::rngs::ThreadRng;
 use rand::seq::SliceRandom;
-use rand::thread_rng;
+use rand::SeedableRng;
+use rand_chacha::ChaCha12Rng;
 
 use crate::core::rgb::Rgb;
 use crate::vector2::Vec2;
 
-use super::level_scene::Tetramino;
+use super::{
+    board::{Tetramino, TetraminoKind},
+    save::{SaveError, SaveReader, SaveWriter},
+};
 
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 /// Template to create a new tetramino
 struct TetraminoTemplate {
     /// Binary number for the blocks, first four represent top row, last four represent bottom
     blocks: i16,
     /// The color of the tetramino
     color: Rgb,
+    /// The type of the tetramino
+    kind: TetraminoKind,
 }
 
 impl TetraminoTemplate {
     /// Create a new tetramino based in a template
     fn build(self) -> Tetramino {
-        let mut block_positions: [Option<Vec2>; 4] = [Some(vec2!(0.0, 0.0)); 4];
+        let mut block_positions: [Option<Vec2>; 4] = [Some(vec2!(0.0_f32, 0.0)); 4];
         let mut i = 0;
         for x in 0..4 {
             for y in 0..2 {
@@ -32,6 +37,7 @@ impl TetraminoTemplate {
         }
 
         Tetramino {
+            kind: self.kind,
             color: self.color,
             block_positions,
         }
@@ -43,30 +49,37 @@ const TETRAMINO_TEMPLATES: [TetraminoTemplate; 7] = [
     TetraminoTemplate {
         blocks: 0b11001100,
         color: Rgb::new(241, 196, 15),
+        kind: TetraminoKind::Square,
     }, // Square
     TetraminoTemplate {
         blocks: 0b11100100,
         color: Rgb::new(142, 68, 173),
+        kind: TetraminoKind::T,
     }, // T
     TetraminoTemplate {
         blocks: 0b00101110,
         color: Rgb::new(230, 126, 34),
+        kind: TetraminoKind::L,
     }, // L
     TetraminoTemplate {
         blocks: 0b10001110,
         color: Rgb::new(41, 128, 185),
+        kind: TetraminoKind::ReverseL,
     }, // Reverse L
     TetraminoTemplate {
         blocks: 0b11110000,
         color: Rgb::new(93, 173, 226),
+        kind: TetraminoKind::Straight,
     }, // Straight
     TetraminoTemplate {
         blocks: 0b11000110,
         color: Rgb::new(231, 76, 60),
+        kind: TetraminoKind::Z,
     }, // Z
     TetraminoTemplate {
         blocks: 0b01101100,
         color: Rgb::new(46, 204, 113),
+        kind: TetraminoKind::S,
     }, // S
 ];
 
@@ -75,16 +88,21 @@ const TETRAMINO_TEMPLATES: [TetraminoTemplate; 7] = [
 #[derive(Debug)]
 pub struct Bag {
     pub list: Vec<Tetramino>,
-    /// The random number generator  
-    pub rng: ThreadRng,
+    /// The random number generator, the algorithm of `StdRng` but with a position that can be saved
+    pub rng: ChaCha12Rng,
+    /// The seed of the random number generator
+    seed: u64,
 }
 
 impl Bag {
-    /// Create unshuffled Bag
-    pub fn new() -> Self {
+    /// Create a Bag whose order of pieces is defined by `seed`
+    ///
+    /// Two bags with the same seed give the same sequence of pieces
+    pub fn with_seed(seed: u64) -> Self {
         let mut bag = Self {
             list: Vec::default(),
-            rng: thread_rng(),
+            rng: ChaCha12Rng::seed_from_u64(seed),
+            seed,
         };
         bag.populate();
         bag
@@ -105,7 +123,37 @@ impl Bag {
         self.list.remove(0)
     }
     pub fn next_tetraminos(&self) -> [&Tetramino; 3] {
-        let l =self.list.len();
         [&self.list[0], &self.list[1], &self.list[2]]
     }
+
+    /// Creates a tetramino of a type, in its initial rotation
+    pub fn tetramino(kind: TetraminoKind) -> Tetramino {
+        TETRAMINO_TEMPLATES[kind as usize].build()
+    }
+
+    /// Writes the pieces in the bag and the state of the generator to a save
+    pub fn save(&self, save: &mut SaveWriter) {
+        save.field("bag_seed", self.seed);
+        save.rng("bag_rng", &self.rng);
+        save.list("bag_list", self.list.iter().map(|tetramino| tetramino.kind));
+    }
+
+    /// Reads a bag from a save
+    ///
+    /// The generator is recreated from the seed and moved to the position it had
+    pub fn load(save: &SaveReader) -> Result<Bag, SaveError> {
+        let seed = save.field("bag_seed")?;
+        let rng = save.rng("bag_rng", seed)?;
+        // Pieces with an unknown name are rejected by the parse
+        let list: Vec<Tetramino> = save
+            .list::<TetraminoKind>("bag_list")?
+            .into_iter()
+            .map(Bag::tetramino)
+            .collect();
+        // The bag always keeps the next three pieces shown beside the board
+        if list.len() < 3 {
+            return Err(SaveError::Invalid("bag_list".to_owned()));
+        }
+        Ok(Bag { list, rng, seed })
+    }
 }
@@ -0,0 +1,900 @@
+//! The grid of the game and its rules, independent of the scene that shows it
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+    str::FromStr,
+    vec,
+};
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+
+use crate::{
+    core::rgb::{Rgb, Rgba},
+    gui::{
+        atlas::Sprite,
+        interface::Canvas,
+        systems::{BlendMode, ImageObject, Layer, SolidColorObject},
+        Rect,
+    },
+    vector2::{ToVec2, Vec2, Vector2},
+};
+
+use super::{
+    layer,
+    bag::Bag,
+    finesse::{self, Input},
+    save::{SaveError, SaveReader, SaveWriter},
+};
+
+/// The size of a tetramino in the map
+pub const SIZE: f32 = 5.;
+
+/// Representation of a block of a tetramino in the stack
+#[derive(Debug, Clone)]
+struct Block {
+    /// The color of the block
+    color: Rgb,
+}
+
+/// The types of tetramino
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TetraminoKind {
+    Square,
+    T,
+    L,
+    ReverseL,
+    Straight,
+    Z,
+    S,
+}
+
+impl TetraminoKind {
+    /// Number of types of tetramino
+    pub const COUNT: usize = 7;
+    /// Every type of tetramino, in the order of their discriminant
+    pub const ALL: [TetraminoKind; Self::COUNT] = [
+        TetraminoKind::Square,
+        TetraminoKind::T,
+        TetraminoKind::L,
+        TetraminoKind::ReverseL,
+        TetraminoKind::Straight,
+        TetraminoKind::Z,
+        TetraminoKind::S,
+    ];
+    /// Short name of the type
+    pub fn name(self) -> &'static str {
+        match self {
+            TetraminoKind::Square => "O",
+            TetraminoKind::T => "T",
+            TetraminoKind::L => "L",
+            TetraminoKind::ReverseL => "J",
+            TetraminoKind::Straight => "I",
+            TetraminoKind::Z => "Z",
+            TetraminoKind::S => "S",
+        }
+    }
+}
+
+impl fmt::Display for TetraminoKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for TetraminoKind {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        TetraminoKind::ALL.into_iter().find(|kind| kind.name() == s).ok_or(())
+    }
+}
+
+/// The tetramino in the game
+#[derive(Clone, Debug)]
+pub struct Tetramino {
+    /// Vector of positions of the tetramino in relation of the center of the tetramino
+    ///
+    /// The position can be a fractional value such as 0.25 to keep the center of mass stable,
+    /// however when converting to an integer it is necessary to apply the floor function
+    ///
+    /// **Warning**: Do not convert to integer by just applying ```as i16```, this is like applying `.trunc` where `-0.25` becomes `0` instead of `-1`, which can cause errors
+    pub block_positions: [Option<Vec2>; 4],
+    pub color: Rgb,
+    pub kind: TetraminoKind,
+}
+
+impl Tetramino {
+    /// Get tetramino center relative to its blocks
+    fn get_center(&self) -> Vec2 {
+        let mut center = Vec2::ZERO;
+        let mut block_count: i16 = 0;
+        self.block_positions.iter().flatten().for_each(|block| {
+            block_count += 1;
+            center.x += block.x;
+            center.y += block.y;
+        });
+        center /= block_count as f32;
+        center
+    }
+    pub(super) fn get_blocks(&self, position : Vector2<i16>) -> vec::IntoIter<Vector2<i16>>{
+        self.block_positions
+            .into_iter()
+            .flatten()
+            .map(|block| Vector2 {
+                x: (position.x as f32 + block.x).floor() as i16,
+                y: (position.y as f32 + block.y).floor() as i16,
+            })
+            .collect::<Vec<Vector2<i16>>>()
+            .into_iter()
+    }
+    /// Returns the tetramino rotated 90 degrees relative to its center
+    pub(super) fn rotated(&self) -> Tetramino {
+        let center = self.get_center();
+        let block_positions = self.block_positions.map(|op| {
+            op.map(|block| {
+                let relative_position = center - block;
+                center + vec2!(relative_position.y, -relative_position.x)
+            })
+        });
+        Tetramino {
+            block_positions,
+            ..self.clone()
+        }
+    }
+    /// The images of the blocks of the tetramino at `pos`, the `block` of the skin tinted with its color
+    pub(super) fn images(&self, pos: Vector2<i16>, block: &Sprite) -> Vec<ImageObject> {
+        self.get_blocks(pos)
+            .map(|position| block_image(block, position, self.color.into(), layer::PIECE))
+            .collect()
+    }
+}
+
+/// The informations of the player in the grid
+#[derive(Debug, Clone)]
+pub struct Player {
+    pub tetramino: Tetramino,
+    pub position: Vector2<i16>,
+}
+
+/// Get a object in the map based on the position in the grid and the color  
+fn grid_region(position: Vector2<i16>) -> Rect {
+    Rect {
+        center: (position.to_vec2() + vec2!(0.5_f32, 0.5)) * SIZE,
+        size: vec2!(SIZE, SIZE),
+    }
+}
+
+/// An image of a block in the grid, the sprite of the skin tinted with `color`
+fn block_image(block: &Sprite, position: Vector2<i16>, color: Rgba, layer: Layer) -> ImageObject {
+    ImageObject {
+        tint: color,
+        ..block.image(grid_region(position), layer)
+    }
+}
+
+impl Player {
+    /// Writes the player as a field of a save: the position, the type and the position of each block
+    fn save(&self, save: &mut SaveWriter, key: &str) {
+        let mut values = vec![
+            self.position.x.to_string(),
+            self.position.y.to_string(),
+            self.tetramino.kind.to_string(),
+        ];
+        values.extend(self.tetramino.block_positions.iter().flatten().flat_map(|b| [b.x.to_string(), b.y.to_string()]));
+        save.list(key, values);
+    }
+
+    /// Reads a player written by [Player::save]
+    fn load(save: &SaveReader, key: &str) -> Result<Player, SaveError> {
+        let invalid = || SaveError::Invalid(key.to_owned());
+        let values: Vec<String> = save.list(key)?;
+        if values.len() < 3 {
+            return Err(invalid());
+        }
+        let x: i16 = values[0].parse().map_err(|_| invalid())?;
+        let y: i16 = values[1].parse().map_err(|_| invalid())?;
+        let mut tetramino = Bag::tetramino(values[2].parse().map_err(|_| invalid())?);
+        let coordinates = values[3..]
+            .iter()
+            .map(|value| value.parse::<f32>().map_err(|_| invalid()))
+            .collect::<Result<Vec<f32>, SaveError>>()?;
+        if coordinates.len() != 8 {
+            return Err(invalid());
+        }
+        for (block, xy) in tetramino.block_positions.iter_mut().zip(coordinates.chunks(2)) {
+            *block = Some(vec2!(xy[0], xy[1]));
+        }
+        Ok(Player {
+            tetramino,
+            position: vec2!(x, y),
+        })
+    }
+
+    /// The images of the blocks of the tetramino, the `block` of the skin tinted with its color
+    pub(super) fn images(&self, block: &Sprite) -> Vec<ImageObject> {
+        self.tetramino.images(self.position, block)
+    }
+    /// Returns a vector containing each position of the tetramino blocks relative to the origin
+    pub(super) fn get_blocks(&self) -> vec::IntoIter<Vector2<i16>> {
+        self.tetramino.get_blocks(self.position)
+    }
+}
+/// Result of putting the player in the stack
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lock {
+    /// The piece was put in the stack, clearing some lines
+    Placed {
+        /// Number of lines cleared by the piece
+        lines_cleared: u32,
+    },
+    /// The piece did not fit in the grid, so the game is lost
+    ToppedOut,
+}
+
+/// The grid with the stack of blocks, the player and its rules
+#[derive(Debug)]
+pub struct Board {
+    /// Actual player in the game
+    pub player: Player,
+    /// The player as it was when it spawned
+    pub spawn: Player,
+    /// Position of the player before the last fall, used to interpolate the drawing
+    previous_position: Vector2<i16>,
+    /// Number of columns in the grid
+    pub columns: i16,
+    /// Number of rows in the grid
+    pub rows: i16,
+    /// Time between the last update that moved the player down and the current one
+    time: u128,
+    /// Time between player update and another
+    pub max_time: u128,
+    /// Vector of lines of blocks on the grid
+    stack: Vec<Vec<Option<Block>>>,
+    pub score: u32,
+    /// The score when the player spawned, given back when it is sent back to the spawn
+    spawn_score: u32,
+    pub bag: Bag,
+    /// The seed of the pieces and the garbage
+    seed: u64,
+    /// Generator of the columns of the holes in the garbage lines
+    garbage_rng: ChaCha12Rng,
+}
+
+impl Board {
+    /// Color of the garbage lines
+    const GARBAGE_COLOR: Rgb = Rgb::new(128, 128, 128);
+    /// Alpha of the piece showing where the player would land
+    const GHOST_ALPHA: u8 = 70;
+
+    /// Create a empty board, with the pieces and garbage generated from `seed`
+    pub fn new(seed: u64) -> Board {
+        let mut bag = Bag::with_seed(seed);
+        let columns = 10;
+        let rows = 20;
+        let tetramino = bag.pop();
+        let player = Player {
+            position: vec2!((columns as f32 / 2.).ceil() as i16 - 2, rows - 2),
+            tetramino,
+        };
+        Board {
+            spawn: player.clone(),
+            previous_position: player.position,
+            player,
+            columns,
+            rows,
+            time: 0,
+            max_time: 1000000,
+            stack: vec![],
+            score: 0,
+            spawn_score: 0,
+            bag,
+            seed,
+            garbage_rng: ChaCha12Rng::seed_from_u64(seed.wrapping_add(1)),
+        }
+    }
+
+    /// Generate the next player of the game
+    fn next_player(&mut self) -> Player {
+        let tetramino = self.bag.pop();
+
+        Player {
+            position: vec2!(
+                ((self.columns as f32 / 2.).ceil() as i16) - 2,
+                self.rows - 1
+            ),
+            tetramino,
+        }
+    }
+
+    /// Clears the stack and the score and generates a new player
+    pub fn clear(&mut self) {
+        self.stack = vec![];
+        self.player = self.next_player();
+        self.spawn = self.player.clone();
+        self.previous_position = self.player.position;
+        self.score = 0;
+        self.spawn_score = 0;
+        self.time = 0;
+    }
+
+    /// Puts the player back in the position where it spawned
+    ///
+    /// The points of a drop done since then are taken back, so a retried placement scores nothing
+    pub fn respawn(&mut self) {
+        self.player = self.spawn.clone();
+        self.previous_position = self.player.position;
+        self.score = self.spawn_score;
+        self.time = 0;
+    }
+
+    /// Checks whether a player block can be in the received position
+    ///
+    /// returns false if it is outside the sides,
+    /// or lower than it should be,
+    /// or in a stack block position otherise returns true
+    pub fn is_valid_player_position(&self, pos: Vector2<i16>) -> bool {
+        if 0 > pos.x || pos.x >= self.columns {
+            return false;
+        }
+        if 0 > pos.y {
+            return false;
+        }
+
+        if let Some(row) = self.stack.get(pos.y as usize) {
+            if row[pos.x as usize].is_some() {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Checks whether a tetramino fits in the received position
+    pub fn fits(&self, tetramino: &Tetramino, position: Vector2<i16>) -> bool {
+        tetramino
+            .get_blocks(position)
+            .all(|block| self.is_valid_player_position(block))
+    }
+
+    /// Move the posistion of the basead on delta if the new pos is valid
+    ///
+    /// It receives a difference (delta),
+    /// if each block in relation to the new position is in a valid position,
+    /// the position is replaced otherwise, nothing is done
+    /// returns whether it was moved or not
+    pub fn translate_player(&mut self, delta: Vector2<i16>) -> bool {
+        let can_move = self.fits(&self.player.tetramino, self.player.position + delta);
+        if can_move {
+            self.player.position += delta;
+        }
+        can_move
+    }
+
+    /// Rotates the player's tetramino blocks if is possible
+    /// Generates new tetramino positions, rotating 90 degrees relative to the center
+    /// if the new positions are invalid, do nothing, otherwise the position will be the rotated position
+    pub fn rotate_player(&mut self) {
+        let rotated = self.player.tetramino.rotated();
+        if self.fits(&rotated, self.player.position) {
+            self.player.tetramino = rotated;
+        }
+    }
+
+    /// Where the player would land with a hard drop
+    pub fn drop_position(&self) -> Vector2<i16> {
+        let mut position = self.player.position;
+        while self.fits(&self.player.tetramino, position + vec2!(0_i16, -1)) {
+            position.y -= 1;
+        }
+        position
+    }
+
+    /// Moves the player to the position where he fits,
+    /// going down until he finds a block or the floor
+    pub fn hard_drop(&mut self) {
+        let mut i = 0;
+        while self.translate_player(vec2!(0_i16, -1)) {
+            i += 1;
+        }
+        self.score += i * 2;
+    }
+
+    /// Applies an input to the player
+    ///
+    /// Returns true if the input is a hard drop, after which the player must be put in the stack
+    pub fn apply_input(&mut self, input: Input) -> bool {
+        match input {
+            Input::Left => {
+                self.translate_player(vec2!(-1_i16, 0));
+            }
+            Input::Right => {
+                self.translate_player(vec2!(1_i16, 0));
+            }
+            Input::Rotate => self.rotate_player(),
+            Input::SoftDrop => {
+                self.translate_player(vec2!(0_i16, -1));
+            }
+            Input::HardDrop => {
+                self.hard_drop();
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Advances the gravity of the player by `delta_t` microseconds
+    ///
+    /// Returns true when the player could not fall anymore, and must be put in the stack
+    pub fn fall(&mut self, delta_t: u128) -> bool {
+        self.previous_position = self.player.position;
+        self.time += delta_t;
+        let mut landed = false;
+        while self.time >= self.max_time {
+            self.time -= self.max_time;
+            if !self.translate_player(vec2!(0_i16, -1)) {
+                landed = true;
+                self.time = 0;
+            }
+        }
+        landed
+    }
+
+    /// The shortest sequence of inputs that would take the spawned player to where the player is now
+    ///
+    /// A player locked by gravity instead of a hard drop (`hard_dropped` false) needed no final drop,
+    /// so the sequence does not end with it
+    pub fn best_inputs(&self, hard_dropped: bool) -> Option<Vec<Input>> {
+        let placed: Vec<Vector2<i16>> = self.player.get_blocks().collect();
+        let mut inputs = finesse::best_inputs(
+            &self.spawn.tetramino,
+            self.spawn.position,
+            &placed,
+            |tetramino, position| self.fits(tetramino, position),
+        )?;
+        if !hard_dropped {
+            inputs.pop();
+        }
+        Some(inputs)
+    }
+
+    /// Method to add the player to the block stack
+    ///
+    /// checks if the player fits in the grid, if not, the game is lost,
+    /// and then adds each player's block to the stack, removes the lines where it is filled
+    /// and generates a new player
+    pub fn lock(&mut self) -> Lock {
+        let max_height = self.player.get_blocks().map(|b| b.y).max().unwrap();
+        if max_height >= self.rows {
+            return Lock::ToppedOut;
+        }
+
+        while self.stack.len() <= max_height as usize {
+            self.stack.push(vec![None; self.columns as usize]);
+        }
+        for block in self.player.get_blocks() {
+            self.stack[block.y as usize][block.x as usize] = Some(Block {
+                color: self.player.tetramino.color,
+            })
+        }
+        let mut i = 0;
+        let mut lines_cleared = 0;
+        while i < self.stack.len() {
+            if self.stack[i].iter().all(Option::is_some) {
+                self.stack.remove(i);
+                self.score += 100;
+                lines_cleared += 1;
+            } else {
+                i += 1;
+            }
+        }
+        self.player = self.next_player();
+        self.spawn = self.player.clone();
+        self.previous_position = self.player.position;
+        self.spawn_score = self.score;
+        Lock::Placed { lines_cleared }
+    }
+
+    /// Pushes `lines` lines of garbage to the bottom of the stack, each with one hole
+    ///
+    /// The player is moved up if the garbage overlaps it
+    pub fn add_garbage(&mut self, lines: u32) {
+        if lines == 0 {
+            return;
+        }
+        let hole = self.garbage_rng.gen_range(0..self.columns) as usize;
+        for _ in 0..lines {
+            let mut row = vec![Some(Block { color: Self::GARBAGE_COLOR }); self.columns as usize];
+            row[hole] = None;
+            self.stack.insert(0, row);
+        }
+        while !self.fits(&self.player.tetramino, self.player.position) {
+            self.player.position.y += 1;
+        }
+        self.previous_position = self.player.position;
+    }
+
+    /// Rows that the player would complete if it was put in the stack where it is, from the bottom
+    pub fn completed_rows(&self) -> Vec<i16> {
+        let mut rows: Vec<i16> = self.player.get_blocks().map(|block| block.y).collect();
+        rows.sort_unstable();
+        rows.dedup();
+        rows.retain(|&y| {
+            let in_stack = self
+                .stack
+                .get(y as usize)
+                .map_or(0, |row| row.iter().filter(|cell| cell.is_some()).count());
+            let in_player = self.player.get_blocks().filter(|block| block.y == y).count();
+            y >= 0 && in_stack + in_player == self.columns as usize
+        });
+        rows
+    }
+
+    /// Position and color of each block of the stack
+    pub fn stack_blocks(&self) -> Vec<(Vector2<i16>, Rgb)> {
+        let mut blocks = Vec::new();
+        for (y, row) in self.stack.iter().enumerate() {
+            for (x, cell) in row.iter().enumerate() {
+                if let Some(Block { color }) = cell {
+                    blocks.push((vec2!(x as i16, y as i16), *color));
+                }
+            }
+        }
+        blocks
+    }
+
+    /// Height of the highest filled row of the stack
+    pub fn stack_height(&self) -> i16 {
+        self.stack
+            .iter()
+            .rposition(|row| row.iter().any(Option::is_some))
+            .map_or(0, |i| i as i16 + 1)
+    }
+
+    /// Number of empty cells of the stack with a block above them
+    pub fn stack_holes(&self) -> u32 {
+        let mut holes = 0;
+        for x in 0..self.columns as usize {
+            let mut covered = false;
+            for row in self.stack.iter().rev() {
+                match row[x] {
+                    Some(_) => covered = true,
+                    None if covered => holes += 1,
+                    None => {}
+                }
+            }
+        }
+        holes
+    }
+
+    /// Writes the whole board to a save
+    pub fn save(&self, save: &mut SaveWriter) {
+        save.field("columns", self.columns);
+        save.field("rows", self.rows);
+        save.field("time", self.time);
+        save.field("max_time", self.max_time);
+        save.field("score", self.score);
+        save.field("seed", self.seed);
+        save.rng("garbage_rng", &self.garbage_rng);
+        self.player.save(save, "player");
+        self.spawn.save(save, "spawn");
+        for row in &self.stack {
+            save.list(
+                "row",
+                row.iter().map(|cell| cell.as_ref().map_or(".".to_owned(), |block| block.color.to_string())),
+            );
+        }
+        self.bag.save(save);
+    }
+
+    /// Reads a board written by [Board::save]
+    ///
+    /// Fails with [SaveError::Invalid] when the grid is empty, the fall has no time limit, the stack or the player
+    /// are out of the grid, a generator is past the end of its stream or the bag has less than the next three pieces
+    pub fn load(save: &SaveReader) -> Result<Board, SaveError> {
+        let columns: i16 = save.field("columns")?;
+        if columns <= 0 {
+            return Err(SaveError::Invalid("columns".to_owned()));
+        }
+        let rows: i16 = save.field("rows")?;
+        if rows <= 0 {
+            return Err(SaveError::Invalid("rows".to_owned()));
+        }
+        let seed: u64 = save.field("seed")?;
+        let garbage_rng = save.rng("garbage_rng", seed.wrapping_add(1))?;
+        let stack = save
+            .repeated::<String>("row")?
+            .into_iter()
+            .map(|row| {
+                if row.len() != columns as usize {
+                    return Err(SaveError::Invalid("row".to_owned()));
+                }
+                row.iter()
+                    .map(|cell| match cell.as_str() {
+                        "." => Ok(None),
+                        color => color
+                            .parse()
+                            .map(|color| Some(Block { color }))
+                            .map_err(|_| SaveError::Invalid("row".to_owned())),
+                    })
+                    .collect()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if stack.len() > rows as usize {
+            return Err(SaveError::Invalid("row".to_owned()));
+        }
+        let player = Player::load(save, "player")?;
+        // The fall repeats while the time passes the limit, it would never end with no limit
+        let max_time: u128 = save.field("max_time")?;
+        if max_time == 0 {
+            return Err(SaveError::Invalid("max_time".to_owned()));
+        }
+        let score = save.field("score")?;
+        let board = Board {
+            previous_position: player.position,
+            player,
+            spawn: Player::load(save, "spawn")?,
+            columns,
+            rows,
+            time: save.field("time")?,
+            max_time,
+            stack,
+            score,
+            // The score only changes with a hard drop, which locks or respawns the player right away
+            spawn_score: score,
+            bag: Bag::load(save)?,
+            seed,
+            garbage_rng,
+        };
+        for (key, player) in [("player", &board.player), ("spawn", &board.spawn)] {
+            if !board.fits(&player.tetramino, player.position) {
+                return Err(SaveError::Invalid(key.to_owned()));
+            }
+        }
+        Ok(board)
+    }
+
+    /// Feeds the state that affects the game (stack, player, score and next pieces) to `state`
+    pub fn hash_state<H: Hasher>(&self, state: &mut H) {
+        for row in &self.stack {
+            for cell in row {
+                cell.is_some().hash(state);
+            }
+        }
+        for block in self.player.get_blocks() {
+            (block.x, block.y).hash(state);
+        }
+        self.score.hash(state);
+        for tetramino in self.bag.next_tetraminos() {
+            (tetramino.kind as u8).hash(state);
+        }
+    }
+
+    /// A hash of the state of the board, used to detect when two copies of it diverge
+    ///
+    /// It is the same in every build and platform, as it is compared with the one of the opponent
+    pub fn hash(&self) -> u64 {
+        let mut hasher = Fnv1a::new();
+        self.hash_state(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Draws the grid, the stack, the player and the next tetraminos with the bottom left corner at `origin`
+    ///
+    /// The player is drawn between its previous and its current position according to `alpha`,
+    /// so the fall looks smooth even when there are more frames than ticks.
+    /// The blocks are drawn with the `block` sprite of the skin, tinted with their color
+    pub fn draw(&self, canvas: &mut Canvas, origin: Vec2, alpha: f32, block: &Sprite) {
+        let offset = |mut object: ImageObject| {
+            object.region.center += origin;
+            object
+        };
+        for i in 0..self.columns {
+            for j in 0..self.rows {
+                let mut object = SolidColorObject {
+                    region: grid_region(vec2!(i, j)),
+                    color: Rgba::new(64, 64, 64, 255),
+                    rotation: 0.,
+                    pivot: Vec2::ZERO,
+                    layer: layer::GRID,
+                    blend: BlendMode::Alpha,
+                };
+                object.region.size = object.region.size * 0.9;
+                object.region.center += origin;
+                canvas.draw(object);
+            }
+        }
+
+        for (i, row) in self.stack.iter().enumerate() {
+            for (j,  op) in  row.iter().enumerate() {
+                if let Some(Block {color})  = op{
+                    canvas.draw(offset(block_image(block, vec2!(j as i16, i as i16), (*color).into(), layer::STACK)));
+                }
+            }
+        };
+
+        let nexts = self.bag.next_tetraminos();
+        for (i, tetramino) in nexts.into_iter().enumerate() {
+            let pos = vec2!(52,  50 - i as i16 * 15 );
+            canvas.draw_iter(tetramino.images(vec2!(0_i16,0), block).into_iter().map(|mut obj| {
+                obj.region.center += pos;
+                offset(obj)
+            }));
+        }
+
+        let ghost = self.player.tetramino.images(self.drop_position(), block).into_iter().map(|mut obj| {
+            obj.tint.a = Self::GHOST_ALPHA;
+            obj.layer = layer::GHOST;
+            offset(obj)
+        });
+        canvas.draw_iter(ghost);
+
+        let lag = (self.previous_position - self.player.position).to_vec2() * SIZE * (1. - alpha.clamp(0., 1.));
+        canvas.draw_iter(self.player.images(block).into_iter().map(|mut obj| {
+            obj.region.center += lag;
+            offset(obj)
+        }));
+    }
+}
+
+/// The 64 bits FNV-1a hash, with the integers written in little endian
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    fn new() -> Fnv1a {
+        Fnv1a(Self::OFFSET_BASIS)
+    }
+}
+
+impl Hasher for Fnv1a {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(Self::PRIME);
+        }
+    }
+
+    fn write_u16(&mut self, value: u16) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, value: u32) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, value: usize) {
+        self.write_u64(value as u64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Saves a new board, replaces the line of `key` with `line` and loads it back
+    fn load_with(key: &str, line: &str) -> Result<Board, SaveError> {
+        let mut save = SaveWriter::new();
+        Board::new(7).save(&mut save);
+        let text: Vec<String> = save
+            .text()
+            .lines()
+            .map(|old| if old.split_whitespace().next() == Some(key) { line.to_owned() } else { old.to_owned() })
+            .collect();
+        Board::load(&SaveReader::parse(&text.join("\n"))?)
+    }
+
+    /// Saves `board` and loads it back
+    fn round_trip(board: &Board) -> Board {
+        let mut save = SaveWriter::new();
+        board.save(&mut save);
+        Board::load(&SaveReader::parse(&save.text()).unwrap()).unwrap()
+    }
+
+    /// Asserts that the boards have the same stack, player, pieces, generators, timers and score
+    fn assert_same(a: &Board, b: &Board) {
+        assert_eq!(format!("{:?}", a.stack), format!("{:?}", b.stack));
+        assert_eq!(format!("{:?}", a.player), format!("{:?}", b.player));
+        assert_eq!(format!("{:?}", a.spawn), format!("{:?}", b.spawn));
+        let kinds = |board: &Board| board.bag.list.iter().map(|tetramino| tetramino.kind).collect::<Vec<_>>();
+        assert_eq!(kinds(a), kinds(b));
+        assert_eq!(a.bag.rng.get_word_pos(), b.bag.rng.get_word_pos());
+        assert_eq!(a.garbage_rng.get_word_pos(), b.garbage_rng.get_word_pos());
+        assert_eq!((a.time, a.max_time), (b.time, b.max_time));
+        assert_eq!((a.score, a.spawn_score), (b.score, b.spawn_score));
+    }
+
+    #[test]
+    fn loads_what_it_saved() {
+        let mut board = Board::new(7);
+        for input in [Input::Left, Input::Left, Input::HardDrop, Input::Right, Input::Rotate, Input::HardDrop] {
+            if board.apply_input(input) {
+                board.lock();
+            }
+        }
+        board.add_garbage(1);
+        board.apply_input(Input::Right);
+        board.apply_input(Input::SoftDrop);
+        board.fall(board.max_time / 3);
+        assert!(board.time > 0 && board.score > 0 && !board.stack.is_empty());
+
+        let mut loaded = round_trip(&board);
+        assert_same(&board, &loaded);
+        // The generators continue where they were, so both boards go on with the same pieces and holes
+        for board in [&mut board, &mut loaded] {
+            for _ in 0..3 {
+                board.hard_drop();
+                board.lock();
+            }
+            board.add_garbage(1);
+        }
+        assert_same(&board, &loaded);
+    }
+
+    #[test]
+    fn hashes_with_fnv1a() {
+        let mut hasher = Fnv1a::new();
+        hasher.write(b"a");
+        assert_eq!(hasher.finish(), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[test]
+    fn the_hash_of_a_board_does_not_change() {
+        let mut board = Board::new(7);
+        board.hard_drop();
+        assert_eq!(board.hash(), 0x092f_68ea_25be_0d9c);
+    }
+
+    #[test]
+    fn a_piece_locked_by_gravity_needs_no_drop() {
+        let mut board = Board::new(7);
+        board.hard_drop();
+        assert_eq!(board.best_inputs(true), Some(vec![Input::HardDrop]));
+        assert_eq!(board.best_inputs(false), Some(vec![]));
+    }
+
+    #[test]
+    fn rejects_a_fall_with_no_time_limit() {
+        assert!(matches!(load_with("max_time", "max_time 0"), Err(SaveError::Invalid(key)) if key == "max_time"));
+    }
+
+    #[test]
+    fn continues_the_generators_where_they_were() {
+        let mut board = Board::new(7);
+        for _ in 0..4 {
+            board.hard_drop();
+            board.lock();
+        }
+        board.add_garbage(1);
+        let mut save = SaveWriter::new();
+        board.save(&mut save);
+        let mut loaded = Board::load(&SaveReader::parse(&save.text()).unwrap()).unwrap();
+        for _ in 0..10 {
+            assert_eq!(loaded.bag.pop().kind, board.bag.pop().kind);
+        }
+        assert_eq!(loaded.garbage_rng.gen_range(0..10), board.garbage_rng.gen_range(0..10));
+    }
+
+    #[test]
+    fn rejects_a_generator_past_the_end_of_its_stream() {
+        let oversized = (1_u128 << 68).to_string();
+        for key in ["bag_rng", "garbage_rng"] {
+            let line = format!("{key} {oversized}");
+            assert!(matches!(load_with(key, &line), Err(SaveError::Invalid(found)) if found == key));
+        }
+    }
+
+    #[test]
+    fn rejects_a_bag_without_the_next_pieces() {
+        assert!(matches!(load_with("bag_list", "bag_list I O"), Err(SaveError::Invalid(key)) if key == "bag_list"));
+        assert!(matches!(load_with("bag_list", "bag_list I O X"), Err(SaveError::Invalid(key)) if key == "bag_list"));
+    }
+}
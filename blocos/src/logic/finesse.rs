@@ -0,0 +1,284 @@
+//! Search of the fewest inputs needed to put a piece in a given spot, used by the finesse trainer
+use std::{
+    collections::{HashSet, VecDeque},
+    fmt,
+};
+
+use glium::glutin::event::VirtualKeyCode;
+
+use crate::vector2::Vector2;
+
+use super::board::Tetramino;
+
+/// An input that changes the piece
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Input {
+    Left,
+    Right,
+    Rotate,
+    SoftDrop,
+    HardDrop,
+}
+
+impl Input {
+    /// The input done by a key, if any
+    pub fn from_key(key: VirtualKeyCode) -> Option<Input> {
+        match key {
+            VirtualKeyCode::Left => Some(Input::Left),
+            VirtualKeyCode::Right => Some(Input::Right),
+            VirtualKeyCode::Up => Some(Input::Rotate),
+            VirtualKeyCode::Down => Some(Input::SoftDrop),
+            VirtualKeyCode::Space => Some(Input::HardDrop),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Input {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let key = match self {
+            Input::Left => "Left",
+            Input::Right => "Right",
+            Input::Rotate => "Up",
+            Input::SoftDrop => "Down",
+            Input::HardDrop => "Space",
+        };
+        f.write_str(key)
+    }
+}
+
+/// Formats a sequence of inputs as the keys to press, separated by spaces
+pub fn format_inputs(inputs: &[Input]) -> String {
+    inputs.iter().map(Input::to_string).collect::<Vec<_>>().join(" ")
+}
+
+/// A state of the piece during the search
+#[derive(Clone)]
+struct Node {
+    tetramino: Tetramino,
+    position: Vector2<i16>,
+    /// Number of rotations applied since the spawn, modulo 4
+    rotation: u8,
+    /// Inputs done to reach this state
+    inputs: Vec<Input>,
+}
+
+impl Node {
+    fn key(&self) -> (i16, i16, u8) {
+        (self.position.x, self.position.y, self.rotation)
+    }
+}
+
+/// Returns the sorted cells occupied by a tetramino in a position
+fn cells(tetramino: &Tetramino, position: Vector2<i16>) -> Vec<(i16, i16)> {
+    let mut cells: Vec<(i16, i16)> = tetramino.get_blocks(position).map(|b| (b.x, b.y)).collect();
+    cells.sort();
+    cells
+}
+
+/// Finds the shortest sequence of inputs (moves, rotations, soft drops and the final hard drop)
+/// that takes a piece from its spawn to the cells in `target`
+///
+/// It is a breadth-first search over the positions and rotations reachable from the spawn,
+/// `fits` tells if the tetramino can be in a position of the grid.
+/// Returns `None` if the spot cannot be reached
+pub fn best_inputs<F>(spawn: &Tetramino, spawn_position: Vector2<i16>, target: &[Vector2<i16>], fits: F) -> Option<Vec<Input>>
+where
+    F: Fn(&Tetramino, Vector2<i16>) -> bool,
+{
+    let mut target: Vec<(i16, i16)> = target.iter().map(|b| (b.x, b.y)).collect();
+    target.sort();
+
+    let start = Node {
+        tetramino: spawn.clone(),
+        position: spawn_position,
+        rotation: 0,
+        inputs: vec![],
+    };
+    let mut visited = HashSet::from([start.key()]);
+    let mut queue = VecDeque::from([start]);
+
+    while let Some(node) = queue.pop_front() {
+        let mut dropped = node.position;
+        while fits(&node.tetramino, dropped + vec2!(0_i16, -1)) {
+            dropped.y -= 1;
+        }
+        if cells(&node.tetramino, dropped) == target {
+            let mut inputs = node.inputs;
+            inputs.push(Input::HardDrop);
+            return Some(inputs);
+        }
+
+        let moves = [
+            (Input::Left, node.tetramino.clone(), node.position + vec2!(-1_i16, 0), node.rotation),
+            (Input::Right, node.tetramino.clone(), node.position + vec2!(1_i16, 0), node.rotation),
+            (Input::SoftDrop, node.tetramino.clone(), node.position + vec2!(0_i16, -1), node.rotation),
+            (Input::Rotate, node.tetramino.rotated(), node.position, (node.rotation + 1) % 4),
+        ];
+        for (input, tetramino, position, rotation) in moves {
+            if !fits(&tetramino, position) || visited.contains(&(position.x, position.y, rotation)) {
+                continue;
+            }
+            let mut inputs = node.inputs.clone();
+            inputs.push(input);
+            let next = Node { tetramino, position, rotation, inputs };
+            visited.insert(next.key());
+            queue.push_back(next);
+        }
+    }
+    None
+}
+
+/// Result of the evaluation of a placement by the trainer
+#[derive(Debug, Clone)]
+pub struct Feedback {
+    /// Inputs the player did
+    pub done: Vec<Input>,
+    /// Shortest sequence of inputs to the same spot
+    pub best: Vec<Input>,
+}
+
+impl Feedback {
+    /// Whether the player used more inputs than needed
+    pub fn is_fault(&self) -> bool {
+        self.done.len() > self.best.len()
+    }
+}
+
+/// Training mode that compares the inputs of each placement with the best ones
+#[derive(Debug, Default)]
+pub struct Trainer {
+    /// Whether the training mode is on
+    pub enabled: bool,
+    /// Whether a piece placed with extra inputs goes back to the spawn
+    pub restart_on_mistake: bool,
+    /// Inputs done since the current piece spawned
+    inputs: Vec<Input>,
+    /// Evaluation of the last placement
+    feedback: Option<Feedback>,
+}
+
+impl Trainer {
+    /// Registers an input done on the current piece
+    pub fn record(&mut self, input: Input) {
+        self.inputs.push(input);
+    }
+
+    /// Forgets the inputs of the current piece
+    pub fn reset(&mut self) {
+        self.inputs.clear();
+    }
+
+    /// Evaluates a placement against the best sequence of inputs
+    ///
+    /// Returns whether the piece should go back to the spawn, which happens on a fault when
+    /// [Trainer::restart_on_mistake] is on
+    pub fn evaluate(&mut self, best: Option<Vec<Input>>) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        let Some(best) = best else {
+            return false;
+        };
+        let feedback = Feedback {
+            done: std::mem::take(&mut self.inputs),
+            best,
+        };
+        let restart = feedback.is_fault() && self.restart_on_mistake;
+        self.feedback = Some(feedback);
+        restart
+    }
+
+    /// Lines of text describing the state of the trainer, to show in the HUD
+    pub fn hud(&self) -> Vec<String> {
+        if !self.enabled {
+            return vec!["F: finesse trainer".to_owned()];
+        }
+        let mut lines = vec![format!(
+            "trainer on (G: restart on mistake {})",
+            if self.restart_on_mistake { "on" } else { "off" }
+        )];
+        if let Some(feedback) = &self.feedback {
+            if feedback.is_fault() {
+                lines.push(format!("extra inputs: {} of {}", feedback.done.len(), feedback.best.len()));
+                lines.push(format!("you: {}", format_inputs(&feedback.done)));
+                lines.push(format!("best: {}", format_inputs(&feedback.best)));
+            } else {
+                lines.push(format!("ok: {} inputs", feedback.done.len()));
+            }
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logic::{
+        bag::Bag,
+        board::{Board, TetraminoKind},
+    };
+
+    /// Columns of the empty board of the tests
+    const COLUMNS: i16 = 6;
+
+    /// Whether a tetramino is inside an empty board of [COLUMNS] columns
+    fn fits(tetramino: &Tetramino, position: Vector2<i16>) -> bool {
+        tetramino.get_blocks(position).all(|block| (0..COLUMNS).contains(&block.x) && block.y >= 0)
+    }
+
+    /// The cells of the square hard dropped from `x`, and the spawn it comes from
+    fn square_dropped_at(x: i16) -> (Tetramino, Vector2<i16>, Vec<Vector2<i16>>) {
+        let square = Bag::tetramino(TetraminoKind::Square);
+        let mut position = vec2!(x, 8);
+        while fits(&square, position + vec2!(0_i16, -1)) {
+            position.y -= 1;
+        }
+        let cells = square.get_blocks(position).collect();
+        (square, vec2!(2_i16, 8), cells)
+    }
+
+    #[test]
+    fn a_drop_below_the_spawn_needs_only_the_drop() {
+        let (square, spawn, target) = square_dropped_at(2);
+        assert_eq!(best_inputs(&square, spawn, &target, fits), Some(vec![Input::HardDrop]));
+    }
+
+    #[test]
+    fn moves_to_the_side_before_the_drop() {
+        let (square, spawn, target) = square_dropped_at(0);
+        let best = best_inputs(&square, spawn, &target, fits).unwrap();
+        assert_eq!(best, vec![Input::Left, Input::Left, Input::HardDrop]);
+
+        let optimal = Feedback { done: best.clone(), best: best.clone() };
+        assert!(!optimal.is_fault());
+        let extra = Feedback {
+            done: vec![Input::Left, Input::Left, Input::Right, Input::Left, Input::HardDrop],
+            best,
+        };
+        assert!(extra.is_fault());
+    }
+
+    #[test]
+    fn an_unreachable_spot_has_no_inputs() {
+        let (square, spawn, _) = square_dropped_at(2);
+        let floating = square.get_blocks(vec2!(2_i16, 4)).collect::<Vec<_>>();
+        assert_eq!(best_inputs(&square, spawn, &floating, fits), None);
+    }
+
+    #[test]
+    fn a_retried_drop_gives_back_its_points() {
+        let mut board = Board::new(7);
+        for _ in 0..3 {
+            board.hard_drop();
+            board.respawn();
+        }
+        assert_eq!(board.score, 0);
+        board.hard_drop();
+        let dropped = board.score;
+        assert!(dropped > 0);
+        board.lock();
+        board.respawn();
+        assert_eq!(board.score, dropped);
+    }
+}
@@ -0,0 +1,77 @@
+//! Golden image tests of the scenes, rendered offscreen and compared with the images kept in `tests/golden`
+//!
+//! Run with `cargo test golden`, or with `UPDATE_GOLDEN=1` to accept the current rendering as the reference
+use std::path::Path;
+
+use image::RgbaImage;
+
+use crate::{
+    core::rgb::Rgba,
+    gui::{
+        interface::{Canvas, Interface},
+        snapshot, Rect,
+    },
+};
+
+use super::{home_scene::HomeScene, level_scene::LevelScene};
+
+/// Directory of the reference images
+const GOLDEN_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden");
+/// Size of the rendered frames
+const SIZE: (u32, u32) = (640, 480);
+/// Biggest difference of a channel accepted, for rounding differences between drivers
+const TOLERANCE: u8 = 8;
+/// Seed of the level, so its pieces are always the same
+const LEVEL_SEED: u64 = 7;
+
+/// Draws a frame showing `world` and reads it back
+fn render(interface: &mut Interface, world: Rect, draw: impl FnOnce(&mut Canvas)) -> RgbaImage {
+    interface.camera.show(world);
+    interface.capture_next_frame();
+    let mut canvas = interface.draw();
+    canvas.clear(Rgba::BLACK);
+    draw(&mut canvas);
+    canvas.finish().unwrap();
+    interface.read_pixels().expect("could not read the frame")
+}
+
+/// Compares `frame` with the reference image `<name>.png`, or overwrites the reference when `UPDATE_GOLDEN=1`
+///
+/// When they differ, `<name>.actual.png` and `<name>.diff.png` are written next to the reference to show what changed
+fn check(name: &str, frame: RgbaImage) {
+    let dir = Path::new(GOLDEN_DIR);
+    let path = dir.join(format!("{name}.png"));
+    if std::env::var("UPDATE_GOLDEN").is_ok_and(|value| value == "1") {
+        std::fs::create_dir_all(dir).unwrap();
+        frame.save(&path).unwrap();
+        return;
+    }
+    let expected = image::open(&path)
+        .unwrap_or_else(|err| panic!("could not open {}: {err}", path.display()))
+        .to_rgba8();
+    let comparison = snapshot::compare(&frame, &expected, TOLERANCE);
+    if !comparison.matches() {
+        frame.save(dir.join(format!("{name}.actual.png"))).unwrap();
+        comparison.diff.save(dir.join(format!("{name}.diff.png"))).unwrap();
+        panic!(
+            "{name}: {} pixels differ (max difference {})",
+            comparison.differing, comparison.max_difference
+        );
+    }
+}
+
+#[test]
+fn the_home_scene_matches_its_golden_image() {
+    let mut interface = Interface::headless(SIZE.0, SIZE.1).unwrap();
+    let home = HomeScene::new(&interface).unwrap();
+    let frame = render(&mut interface, home.world_region(), |canvas| home.draw(canvas));
+    check("home", frame);
+}
+
+#[test]
+fn the_level_scene_matches_its_golden_image() {
+    let mut interface = Interface::headless(SIZE.0, SIZE.1).unwrap();
+    let level = LevelScene::with_seed(&interface, LEVEL_SEED).unwrap();
+    let frame = render(&mut interface, level.world_region(), |canvas| level.draw(canvas, 1.));
+    check("level", frame);
+}
@@ -1,20 +1,26 @@
-use std::rc::Rc;
-
-use glium::{glutin::event::VirtualKeyCode, texture::SrgbTexture2d};
+use glium::glutin::event::VirtualKeyCode;
 
 use crate::{
     gui::{
+        assets::TextureHandle,
+        fonts::{FontId, FontRegistry, FontStyle},
         interface::{Canvas, Interface},
-        systems::{TextObject, SolidColorObject},
-        Rect,
+        systems::{BlendMode, EdgeMode, HorizontalAlign, Insets, NineSliceObject, TextLayout, TextObject},
+        GuiError, Rect,
     },
-    include_png, core::rgb::Rgb, vector2::Vec2,
+    core::rgb::Rgba, vector2::Vec2,
 };
 
-use super::Scene;
+use super::{layer, Scene};
 
 pub struct HomeScene {
-    brick: Rc<SrgbTexture2d>,
+    brick: TextureHandle,
+    /// Font of the title, bold when the assets have it
+    title_font: FontId,
+    /// Whether there is a saved game, showing the button to continue it
+    pub has_save: bool,
+    /// Whether the button to continue was clicked, until the [super::GameState] takes it
+    continue_clicked: bool,
 }
 
 impl HomeScene {
@@ -22,52 +28,119 @@ impl HomeScene {
             center:  Vec2 {x : 0., y: 0.} ,
             size: Vec2 { x : 35.+5., y: 15.},
         };
+    const CONTINUE_REGION: Rect = Rect {
+            center:  Vec2 {x : 0., y: -20.} ,
+            size: Vec2 { x : 45., y: 15.},
+        };
 
-    pub fn new(interface: &Interface) -> HomeScene {
-        HomeScene {
-            brick: interface.create_texture(include_png!("../assets/brick.png")),
-        }
+    /// Width of the borders of the brick texture of the buttons, in pixels
+    const BUTTON_INSETS: Insets = Insets::uniform(4);
+
+    pub fn new(interface: &Interface) -> Result<HomeScene, GuiError> {
+        Ok(HomeScene {
+            brick: interface.load_texture("brick.png", include_bytes!("../assets/brick.png"))?,
+            title_font: interface.font(FontRegistry::DEFAULT_FAMILY, FontStyle::Bold),
+            has_save: false,
+            continue_clicked: false,
+        })
     }
     /// Receives the keypress event
     pub fn key_down(&mut self, _key: VirtualKeyCode) -> Scene {
         Scene::HomeScene
     }
+    /// The regions of the interface that can be clicked, with their names
+    pub fn ui_regions(&self) -> Vec<(&'static str, Rect)> {
+        let mut regions = vec![("start", Self::BUTTON_REGION)];
+        if self.has_save {
+            regions.push(("continue", Self::CONTINUE_REGION));
+        }
+        regions
+    }
     pub fn on_click(&mut self, position : Vec2) -> Scene {
         if Self::BUTTON_REGION.left() < position.x && position.x < Self::BUTTON_REGION.right()
         && Self::BUTTON_REGION.bottom() < position.y && position.y < Self::BUTTON_REGION.top(){
             return Scene::LevelScene;
         }
+        if self.has_save && Self::CONTINUE_REGION.left() < position.x && position.x < Self::CONTINUE_REGION.right()
+        && Self::CONTINUE_REGION.bottom() < position.y && position.y < Self::CONTINUE_REGION.top(){
+            self.continue_clicked = true;
+            return Scene::LevelScene;
+        }
+        Scene::HomeScene
+    }
+    /// Returns whether the button to continue the saved game was clicked since the last call
+    pub fn take_continue(&mut self) -> bool {
+        std::mem::take(&mut self.continue_clicked)
+    }
+    /// Updates the game state
+    pub fn update(&mut self, _delta_t: u128) -> Scene {
         Scene::HomeScene
     }
-    /// Updates the game state and draws on the table
-    pub fn update(&mut self, canvas: &mut Canvas, _delta_t: u128) -> Scene {
+
+    /// Draws the scene on the table
+    pub fn draw(&self, canvas: &mut Canvas) {
 
         canvas.draw(TextObject {
             text: "Blocos".into(),
-            position: vec2!(-30., 80.),
-            color: Rgb::WHITE,
+            position: vec2!(0_f32, 80.),
+            color: Rgba::WHITE,
             font_size: 20.,
+            font: self.title_font,
+            layer: layer::HUD,
+            blend: BlendMode::Alpha,
+            layout: TextLayout {
+                horizontal: HorizontalAlign::Center,
+                ..Default::default()
+            },
         });
 
-        canvas.draw( SolidColorObject{
-            region: Self::BUTTON_REGION,
-            color: Rgb::WHITE
-        });
+        self.draw_button(canvas, Self::BUTTON_REGION);
         canvas.draw(TextObject {
             text: "Começar".into(),
-            position: vec2!(-17.5, 5.),
-            color: Rgb::BLACK,
+            position: Self::BUTTON_REGION.center,
+            color: Rgba::WHITE,
             font_size: 10.,
+            font: FontId::DEFAULT,
+            layer: layer::HUD,
+            blend: BlendMode::Alpha,
+            layout: TextLayout::centered(),
         });
+        if self.has_save {
+            self.draw_button(canvas, Self::CONTINUE_REGION);
+            canvas.draw(TextObject {
+                text: "Continuar".into(),
+                position: Self::CONTINUE_REGION.center,
+                color: Rgba::WHITE,
+                font_size: 10.,
+                font: FontId::DEFAULT,
+                layer: layer::HUD,
+                blend: BlendMode::Alpha,
+                layout: TextLayout::centered(),
+            });
+        }
+    }
 
-
-        Scene::HomeScene
+    /// Draws the background of a button, a panel of bricks
+    fn draw_button(&self, canvas: &mut Canvas, region: Rect) {
+        let texture = canvas.interface.texture(self.brick);
+        canvas.draw(NineSliceObject {
+            region,
+            texture,
+            source: Rect::UNIT,
+            insets: Self::BUTTON_INSETS,
+            pixel_size: 1.,
+            edges: EdgeMode::Tile,
+            fill: true,
+            tint: Rgba::WHITE,
+            layer: layer::BUTTON,
+            blend: BlendMode::Alpha,
+        });
     }
 
     pub fn world_region(&self) -> Rect {
         Rect {
-            center: vec2!(0., 0.), 
-            size: vec2!(100., 100.)
+            center: vec2!(0_f32, 0.), 
+            size: vec2!(200_f32, 200.)
         }
     }
 }
@@ -0,0 +1,19 @@
+//! Layers of the objects drawn by the scenes, from the bottom to the top
+use crate::gui::systems::Layer;
+
+/// The twinkles behind the board
+pub const BACKGROUND: Layer = -10;
+/// The empty cells of a board
+pub const GRID: Layer = 0;
+/// The blocks in the stack
+pub const STACK: Layer = 10;
+/// The translucent piece showing where the player would land
+pub const GHOST: Layer = 15;
+/// The falling piece and the next pieces
+pub const PIECE: Layer = 20;
+/// Sparks, dust and debris over the board
+pub const PARTICLES: Layer = 30;
+/// The background of the buttons
+pub const BUTTON: Layer = 90;
+/// Texts over everything else
+pub const HUD: Layer = 100;
@@ -1,364 +1,622 @@
-use std::{vec, rc::Rc};
+use std::{fs, io, path::Path, rc::Rc};
 
 use crate::{
     gui::{
+        animation::{AnimatedSprite, Animation, PlayMode},
+        atlas::{Atlas, AtlasBuilder, Sprite},
+        fonts::FontId,
         interface::{Canvas, Interface},
-        systems::{ImageObject, SolidColorObject, TextObject},
-        Rect,
+        particles::{Emission, Emitter, ParticleSettings, ParticleSystem},
+        systems::{BlendMode, EdgeMode, Insets, NineSliceObject, TextLayout, TextObject},
+        GuiError, Rect,
     },
-    include_png,
-    vector2::{ToVec2, Vec2, Vector2}, core::rgb::Rgb,
+    vector2::{ToVec2, Vec2}, core::rgb::Rgba,
 };
 use glium::glutin::event::VirtualKeyCode;
-use glium::texture::SrgbTexture2d;
+use rand::random;
 
-use super::{bag::Bag, Scene};
-/// The size of a tetramino in the map
-pub const SIZE: f32 = 5.;
-
-/// Representation of a block of a tetramino in the stack
-#[derive(Debug, Clone)]
-struct Block {
-    /// The color of the block
-    color: Rgb,
+use super::{
+    layer,
+    board::{Board, Lock, SIZE},
+    finesse::{Input, Trainer},
+    save::{SaveError, SaveReader, SaveWriter},
+    stats::Stats,
+    Scene,
+};
+/// The sprites of the level, taken from its atlas
+struct Skin {
+    brick: Sprite,
+    block: Sprite,
+    background: Vec<(Vec2, AnimatedSprite)>,
 }
 
-/// The tetramino in the game
-#[derive(Clone, Debug)]
-pub struct Tetramino {
-    /// Vector of positions of the tetramino in relation of the center of the tetramino
-    ///
-    /// The position can be a fractional value such as 0.25 to keep the center of mass stable,
-    /// however when converting to an integer it is necessary to apply the floor function
+/// Represent the actual state of the game
+#[derive(Debug)]
+pub struct LevelScene {
+    /// The grid with the stack and the player
+    board: Board,
+    /// The bricks of the frame around the next tetraminos, in the atlas of the blocks
+    brick: Sprite,
+    /// The block of the skin, tinted with the color of each piece
+    block: Sprite,
+    loss : bool,
+    /// Statistics of the current game
+    stats: Stats,
+    /// The statistics of the game that was lost, shown until the player leaves the game over screen
+    game_over: Option<Stats>,
+    /// Feedback of the last export of statistics
+    export_message: Option<String>,
+    /// The finesse training mode
+    trainer: Trainer,
+    /// Whether a game was played and not finished, so there is something to save
     ///
-    /// **Warning**: Do not convert to integer by just applying ```as i16```, this is like applying `.trunc` where `-0.25` becomes `0` instead of `-1`, which can cause errors
-    pub block_positions: [Option<Vec2>; 4],
-    pub color: Rgb,
+    /// Set by the first input or the first piece locked, so a game only left running is not saved
+    in_progress: bool,
+    /// How hard the camera should shake for the hits since the last frame, taken by the [super::GameState]
+    trauma: f32,
+    /// Sparks of the lines cleared, dust of the hard drops and the stack collapsing in the game over
+    particles: ParticleSystem,
+    /// The twinkles of the background, with where they are
+    background: Vec<(Vec2, AnimatedSprite)>,
 }
 
-impl Tetramino {
-    /// Get tetramino center relative to its blocks
-    fn get_center(&mut self) -> Vec2 {
-        let mut center = Vec2::ZERO;
-        let mut block_count: i16 = 0;
-        self.block_positions.iter().flatten().for_each(|block| {
-            block_count += 1;
-            center.x += block.x;
-            center.y += block.y;
-        });
-        center /= block_count as f32;
-        center
-    }
-    fn get_blocks(&self, position : Vector2<i16>) -> vec::IntoIter<Vector2<i16>>{
-        self.block_positions
-            .into_iter()
-            .flatten()
-            .map(|block| Vector2 {
-                x: (position.x as f32 + block.x).floor() as i16,
-                y: (position.y as f32 + block.y).floor() as i16,
-            })
-            .collect::<Vec<Vector2<i16>>>()
-            .into_iter()
+impl LevelScene {
+    pub fn world_region(&self) -> Rect {
+        Rect {
+            center: vec2!(self.board.columns as f32, self.board.rows as f32) * SIZE / 2.,
+            size: vec2!(200_f32, 200.),
+        }
     }
-    fn object_vec(&self, pos: Vector2<i16>) -> Vec<SolidColorObject> {
-        self.get_blocks(pos)
-            .map(|position| SolidColorObject {
-                region: grid_region(position),
-                color: self.color,
-            })
-            .collect()
+    /// Directory where the statistics are exported
+    const STATS_DIR: &'static str = "stats";
+    /// File where the game in progress is saved when the player quits
+    pub const SAVE_PATH: &'static str = "save.txt";
+    /// Where a save that cannot be continued is moved, so the home stops offering it and it can still be inspected
+    pub const INVALID_SAVE_PATH: &'static str = "save.invalid.txt";
+    /// Region of the frame around the next tetraminos
+    const NEXT_FRAME: Rect = Rect {
+        center: Vec2 { x: 62., y: 40. },
+        size: Vec2 { x: 30., y: 50. },
+    };
+    /// Where the twinkles of the background are, how long after the start of their animation and how fast they play
+    const TWINKLES: [(Vec2, u128, f32); 8] = [
+        (Vec2 { x: -40., y: 90. }, 0, 1.),
+        (Vec2 { x: -25., y: 40. }, 350_000, 0.8),
+        (Vec2 { x: -55., y: 10. }, 700_000, 1.2),
+        (Vec2 { x: -10., y: 120. }, 200_000, 0.9),
+        (Vec2 { x: 95., y: 110. }, 500_000, 1.1),
+        (Vec2 { x: 110., y: 60. }, 900_000, 0.7),
+        (Vec2 { x: 90., y: -5. }, 150_000, 1.3),
+        (Vec2 { x: 35., y: -30. }, 650_000, 1.),
+    ];
+    /// Size of a twinkle in the world
+    const TWINKLE_SIZE: f32 = 6.;
+    /// How long each frame of a twinkle is shown, in microseconds
+    const TWINKLE_FRAME: u128 = 180_000;
+    /// Files of the assets packed in the atlas of the level
+    const ATLAS_FILES: [&'static str; 3] = ["brick.png", "blocks.png", "blocks.toml"];
+    /// Trauma of the camera when a piece is hard dropped
+    const HARD_DROP_TRAUMA: f32 = 0.3;
+    /// Trauma of the camera when four lines are cleared at once
+    const TETRIS_TRAUMA: f32 = 0.7;
+
+    /// Create the game state
+    pub fn new(interface: &Interface) -> Result<LevelScene, GuiError> {
+        Self::with_seed(interface, random())
     }
-}
 
-/// The informations of the player in the grid
-#[derive(Debug)]
-pub struct Player {
-    tetramino: Tetramino,
-    position: Vector2<i16>,
-}
+    /// Create the game state with the pieces generated from `seed`, so the game can be reproduced
+    pub fn with_seed(interface: &Interface, seed: u64) -> Result<LevelScene, GuiError> {
+        let board = Board::new(seed);
+        let mut stats = Stats::new();
+        stats.piece_spawned(board.player.tetramino.kind);
+        let Skin { brick, block, background } = Self::load_skin(interface)?;
 
-/// Get a object in the map based on the position in the grid and the color  
-fn grid_region(position: Vector2<i16>) -> Rect {
-    Rect {
-        center: (position.to_vec2() + vec2!(0.5, 0.5)) * SIZE,
-        size: vec2!(SIZE, SIZE),
+        Ok(LevelScene {
+            board,
+            brick,
+            block,
+            loss: false,
+            stats,
+            game_over: None,
+            export_message: None,
+            trainer: Trainer::default(),
+            in_progress: false,
+            trauma: 0.,
+            particles: ParticleSystem::new(),
+            background,
+        })
     }
-}
 
-impl Player {
-    /// Get a vector of objecto of  each block of the tetramino
-    fn object_vec(&self) -> Vec<SolidColorObject> {
-        self.tetramino.object_vec(self.position)
-    }
-    /// Returns a vector containing each position of the tetramino blocks relative to the origin
-    fn get_blocks(&self) -> vec::IntoIter<Vector2<i16>> {
-        self.tetramino.get_blocks(self.position)
+    /// The sprites of the level, from its atlas
+    fn load_skin(interface: &Interface) -> Result<Skin, GuiError> {
+        let atlas = Self::load_atlas(interface)?;
+        let skin = atlas
+            .sheet("blocks")
+            .ok_or_else(|| GuiError::Texture("the atlas of the level has no sheet `blocks`".into()))?;
+        let frames = ["spark0", "spark1", "spark2"];
+        let twinkle = Animation::from_sheet("twinkle", &skin, &frames, Self::TWINKLE_FRAME, PlayMode::PingPong)
+            .ok_or_else(|| {
+                GuiError::Texture("the sprite sheet blocks.toml has no frames `spark0` to `spark2`".into())
+            })?;
+        let twinkle = Rc::new(twinkle);
+        let background = Self::TWINKLES
+            .iter()
+            .map(|&(position, offset, speed)| {
+                let mut sprite = AnimatedSprite::new(twinkle.clone());
+                sprite.update(offset);
+                sprite.speed = speed;
+                (position, sprite)
+            })
+            .collect();
+        Ok(Skin {
+            brick: atlas
+                .get("brick")
+                .ok_or_else(|| GuiError::Texture("the atlas of the level has no image `brick`".into()))?,
+            block: skin
+                .get("block")
+                .ok_or_else(|| GuiError::Texture("the sprite sheet blocks.toml has no frame `block`".into()))?,
+            background,
+        })
     }
-}
-/// Represent the actual state of the game
-#[derive(Debug)]
-pub struct LevelScene {
-    /// Actual player in the game  
-    player: Player,
-    /// Number of columns in the grid
-    pub columns: i16,
-    /// Number of rows in the grid
-    pub rows: i16,
-    /// Time between the last update that moved the player down and the current one
-    time: u128,
-    /// Time between player update and another
-    max_time: u128,
-    /// Vector of lines of blocks on the grid
-    stack: Vec<Vec<Option<Block>>>,
-    score: u32,
-    texture: Rc<SrgbTexture2d>,
-    bag: Bag,
-    loss : bool
-}
 
-impl LevelScene {
-    pub fn world_region(&self) -> Rect {
-        Rect {
-            center: vec2!(self.columns as f32, self.rows as f32) * SIZE / 2.,
-            size: vec2!(100., 100.),
+    /// Builds the atlas again when one of its files is in `changed`, the files reported by
+    /// [Interface::reload_assets]
+    ///
+    /// Returns whether the atlas was built again. The current sprites are kept if it fails
+    pub fn reload_skin(&mut self, interface: &Interface, changed: &[String]) -> Result<bool, GuiError> {
+        if !changed.iter().any(|file| Self::ATLAS_FILES.contains(&file.as_str())) {
+            return Ok(false);
         }
+        let Skin { brick, block, background } = Self::load_skin(interface)?;
+        self.brick = brick;
+        self.block = block;
+        self.background = background;
+        Ok(true)
+    }
+
+    /// The images of the level from the assets, the skin of the blocks and the bricks, packed in a single texture
+    /// so they are drawn in the same batches
+    fn load_atlas(interface: &Interface) -> Result<Atlas, GuiError> {
+        let mut builder = AtlasBuilder::new();
+        builder.add("brick", interface.load_image("brick.png", include_bytes!("../assets/brick.png"))?);
+        builder.add_sheet(
+            "blocks",
+            interface.load_image("blocks.png", include_bytes!("../assets/blocks.png"))?,
+            &interface.load_text("blocks.toml", include_str!("../assets/blocks.toml")),
+        )?;
+        builder.build(interface)
+    }
+
+    /// The block of the skin, so other scenes draw their boards with it
+    pub fn block(&self) -> Sprite {
+        self.block.clone()
     }
-    /// Generate the next player of the game
-    fn next_player(&mut self) -> Player {
-        let tetramino = self.bag.pop();
-
-        Player {
-            position: vec2!(
-                ((self.columns as f32 / 2.).ceil() as i16) - 2,
-                self.rows - 1
-            ),
-            tetramino,
+
+    /// Writes the game in progress to [LevelScene::SAVE_PATH], if there is one
+    pub fn save(&self) -> io::Result<()> {
+        if !self.in_progress {
+            return Ok(());
         }
+        let mut save = SaveWriter::new();
+        self.board.save(&mut save);
+        self.stats.save(&mut save);
+        save.field("trainer_enabled", self.trainer.enabled);
+        save.field("trainer_restart_on_mistake", self.trainer.restart_on_mistake);
+        save.write_to(Path::new(Self::SAVE_PATH))
     }
 
-    /// Create the game state
-    pub fn new(interface: &Interface) -> LevelScene {
-        let mut bag = Bag::new();
-        let columns  = 10;
-        let rows = 20;
-        let tetramino =  bag.pop();
-        let player = Player {
-            position: vec2!((columns as f32 / 2.).ceil() as i16 - 2, rows - 2),
-            tetramino,
+    /// Continues the game saved in [LevelScene::SAVE_PATH]
+    ///
+    /// The current game is kept if the save cannot be read, and a save that was read but is invalid
+    /// is moved to [LevelScene::INVALID_SAVE_PATH]
+    pub fn resume(&mut self) -> Result<(), SaveError> {
+        let (board, stats, trainer) = match Self::load_save() {
+            Ok(game) => game,
+            Err(err) => {
+                if !matches!(err, SaveError::Io(_)) {
+                    if let Err(err) = fs::rename(Self::SAVE_PATH, Self::INVALID_SAVE_PATH) {
+                        eprintln!("could not move the invalid save: {err}");
+                    }
+                }
+                return Err(err);
+            }
         };
 
-        LevelScene {
-            time: 0,
-            player,
-            columns,
-            rows,
-            max_time: 1000000,
-            stack: vec![],
-            texture:  interface.create_texture(include_png!("../assets/brick.png")),
-            bag,
-            score: 0,
-            loss: false
-        }
+        self.board = board;
+        self.stats = stats;
+        self.trainer = trainer;
+        self.loss = false;
+        self.game_over = None;
+        self.export_message = None;
+        self.in_progress = true;
+        Ok(())
+    }
+
+    /// Returns how hard the camera should shake since the last call
+    pub fn take_trauma(&mut self) -> f32 {
+        std::mem::take(&mut self.trauma)
+    }
+
+    /// Whether there is a saved game that can be continued, reading the whole of it
+    pub fn has_save() -> bool {
+        Self::load_save().is_ok()
+    }
+
+    /// Reads the game saved in [LevelScene::SAVE_PATH]
+    fn load_save() -> Result<(Board, Stats, Trainer), SaveError> {
+        let save = SaveReader::read_from(Path::new(Self::SAVE_PATH))?;
+        let board = Board::load(&save)?;
+        let stats = Stats::load(&save)?;
+        let mut trainer = Trainer::default();
+        trainer.enabled = save.field("trainer_enabled")?;
+        trainer.restart_on_mistake = save.field("trainer_restart_on_mistake")?;
+        Ok((board, stats, trainer))
     }
     /// Receives the keypress event
     pub fn key_down(&mut self, key: VirtualKeyCode) -> Scene {
-        match key {
-            VirtualKeyCode::Up => {
-                self.rotate_player();
-            }
-            VirtualKeyCode::Space => self.move_to_end(),
-            VirtualKeyCode::Down => {
-                self.translate_player(vec2!(0_i16, -1));
+        if self.game_over.is_some() {
+            return self.game_over_key_down(key);
+        }
+        if let Some(input) = Input::from_key(key) {
+            self.in_progress = true;
+            self.stats.key_pressed();
+            self.trainer.record(input);
+            if self.board.apply_input(input) {
+                self.add_player_to_stack(true);
             }
+            return Scene::LevelScene;
+        }
+        match key {
             VirtualKeyCode::W => {
-                self.player.position.y += 1;
-            }
-            VirtualKeyCode::Right => {
-                self.translate_player(vec2!(1_i16, 0));
-            }
-            VirtualKeyCode::Left => {
-                self.translate_player(vec2!(-1_i16, 0));
+                self.board.player.position.y += 1;
             }
             VirtualKeyCode::R => self.restart(),
+            VirtualKeyCode::F => self.trainer.enabled = !self.trainer.enabled,
+            VirtualKeyCode::G => self.trainer.restart_on_mistake = !self.trainer.restart_on_mistake,
             _ => (),
         };
         Scene::LevelScene
     }
 
-    /// Move the player to the end of the stack and put he in
-    /// Moves the player to the position where he fits,
-    /// going down until he finds a block or the floor
-    /// and then puts him in the final position
-    fn move_to_end(&mut self) {
-        let mut i = 0;
-        while self.translate_player(vec2!(0_i16, -1)) { 
-            i += 1;
+    /// Receives the keypress event while the game over screen is shown
+    ///
+    /// `E` exports the statistics, `Return` goes back to the home
+    fn game_over_key_down(&mut self, key: VirtualKeyCode) -> Scene {
+        match key {
+            VirtualKeyCode::E => {
+                if let Some(stats) = &self.game_over {
+                    self.export_message = Some(match stats.export(Path::new(Self::STATS_DIR)) {
+                        Ok(path) => format!("exported to {}.csv/.json", path.display()),
+                        Err(err) => format!("export failed: {err}"),
+                    });
+                }
+                Scene::LevelScene
+            }
+            VirtualKeyCode::Return => {
+                self.game_over = None;
+                self.export_message = None;
+                Scene::HomeScene
+            }
+            _ => Scene::LevelScene,
         }
-        self.score += i*2;
-        self.add_player_to_stack();
     }
 
     /// Restart the game
     ///
-    /// Clears the stack and generates a new player
+    /// Clears the stack and generates a new player, removing the save of the game abandoned
     fn restart(&mut self) {
-        self.stack = vec![];
-        self.player = self.next_player();
-        self.score = 0;
+        self.board.clear();
+        self.stats = Stats::new();
+        self.stats.piece_spawned(self.board.player.tetramino.kind);
+        self.trainer.reset();
         self.loss = true;
+        self.in_progress = false;
+        Self::remove_save();
     }
 
-    /// Checks whether a player block can be in the received position
-    ///
-    /// returns false if it is outside the sides,
-    /// or lower than it should be,
-    /// or in a stack block position otherise returns true
-    pub fn is_valid_player_position(&self, pos: Vector2<i16>) -> bool {
-        if 0 > pos.x || pos.x >= self.columns {
-            return false;
-        }
-        if 0 > pos.y {
-            return false;
-        }
-
-        if let Some(row) = self.stack.get(pos.y as usize) {
-            if row[pos.x as usize].is_some() {
-                return false;
+    /// Removes [LevelScene::SAVE_PATH], so the home does not offer to continue a game that ended
+    fn remove_save() {
+        if let Err(err) = fs::remove_file(Self::SAVE_PATH) {
+            if err.kind() != io::ErrorKind::NotFound {
+                eprintln!("could not remove the save: {err}");
             }
         }
-        true
     }
 
-    /// Move the posistion of the basead on delta if the new pos is valid
-    ///
-    /// It receives a difference (delta),
-    /// if each block in relation to the new position is in a valid position,
-    /// the position is replaced otherwise, nothing is done
-    /// returns whether it was moved or not
-    pub fn translate_player(&mut self, delta: Vector2<i16>) -> bool {
-        let can_move = self
-            .player
-            .get_blocks()
-            .all(|block| self.is_valid_player_position(delta + block));
-        if can_move {
-            self.player.position += delta;
-        }
-        can_move
+    /// Ends the game, keeping its statistics to show in the game over screen
+    fn game_over(&mut self) {
+        self.emit_collapse();
+        let stats = self.stats.clone();
+        self.restart();
+        self.loss = false;
+        self.game_over = Some(stats);
     }
 
-    /// Rotates the player's tetramino blocks if is possible
-    /// Generates new tetramino positions, rotating 90 degrees relative to the center
-    /// if the new positions are invalid, do nothing, otherwise the position will be the rotated position
-    fn rotate_player(&mut self) {
-        let center = self.player.tetramino.get_center();
-        let new_blocks = self.player.tetramino.block_positions.map(|op| {
-            op.map(|block| {
-                let relative_position = center - block;
-                center + vec2!(relative_position.y, -relative_position.x)
-            })
-        });
-        let can_rotate = |b: Vec2| {
-            let pos = self.player.position + vec2!(b.x.floor() as i16, b.y.floor() as i16);
-            self.is_valid_player_position(pos)
-        };
-        if new_blocks.into_iter().flatten().all(can_rotate) {
-            self.player.tetramino.block_positions = new_blocks;
-        }
-    }
     /// Method to add the player to the block stack
     ///
-    /// checks if the player fits in the grid, if not, restarts the game,
-    /// and then adds each player's block to the stack and generates a new one adds a new player,
-    /// and removes the lines where it is filled
-    pub fn add_player_to_stack(&mut self) {
-        let max_height = self.player.get_blocks().map(|b| b.y).max().unwrap();
-        if max_height >= self.rows {
-            self.restart();
+    /// evaluates the finesse of the placement, sending the player back to the spawn if the trainer asks so,
+    /// then puts the player in the stack, ending the game if it does not fit.
+    /// `hard_dropped` is false when gravity locked the player, which needs no drop input.
+    /// A hard drop throws dust and shakes the camera, unless the player is sent back
+    pub fn add_player_to_stack(&mut self, hard_dropped: bool) {
+        let best_inputs = self.board.best_inputs(hard_dropped);
+        let minimal_inputs = best_inputs.as_ref().map(|inputs| inputs.len() as u32);
+        if self.trainer.evaluate(best_inputs) {
+            self.stats.piece_retried();
+            self.board.respawn();
             return;
         }
-
-        while self.stack.len() <= max_height as usize {
-            self.stack.push(vec![None; self.columns as usize]);
+        if hard_dropped {
+            self.emit_dust();
+            self.trauma += Self::HARD_DROP_TRAUMA;
         }
-        for block in self.player.get_blocks() {
-            self.stack[block.y as usize][block.x as usize] = Some(Block {
-                color: self.player.tetramino.color,
-            })
-        }
-        self.player = self.next_player();
-        let mut i = 0;
-        while i < self.stack.len() {
-            if self.stack[i].iter().all(Option::is_some) {
-                self.stack.remove(i);
-                self.score += 100;
-            } else {
-                i += 1;
+
+        let completed_rows = self.board.completed_rows();
+        match self.board.lock() {
+            Lock::ToppedOut => self.game_over(),
+            Lock::Placed { lines_cleared } => {
+                self.in_progress = true;
+                let (height, holes) = (self.board.stack_height(), self.board.stack_holes());
+                self.stats.piece_placed(lines_cleared, minimal_inputs, height, holes);
+                self.emit_sparks(&completed_rows);
+                if lines_cleared == 4 {
+                    self.trauma += Self::TETRIS_TRAUMA;
+                }
+                self.stats.piece_spawned(self.board.player.tetramino.kind);
+                self.trainer.reset();
             }
         }
     }
-    /// Updates the game state and draws on the table
-    pub fn update(&mut self, canvas: &mut Canvas, delta_t: u128) -> Scene {
+    /// Updates the game state, advancing the gravity and the time
+    pub fn update(&mut self, delta_t: u128) -> Scene {
+        self.particles.update(delta_t as f32 / 1_000_000.);
+        for (_, sprite) in &mut self.background {
+            sprite.update(delta_t);
+        }
         if self.loss {
             self.loss = false;
             return Scene::HomeScene;
         }
-        for i in 0..self.columns {
-            for j in 0..self.rows {
-                let mut object = SolidColorObject {
-                    region: grid_region(vec2!(i, j)),
-                    color: Rgb::new(64, 64, 64),
-                };
-                object.region.size = object.region.size * 0.9;
-                canvas.draw(object);
-            }
+        if self.game_over.is_some() {
+            return Scene::LevelScene;
         }
-        
-        for (i, row) in self.stack.iter().enumerate() {
-            for (j,  op) in  row.iter().enumerate() {
-                if let Some(Block {color})  = op{
-                    canvas.draw(SolidColorObject {
-                        region: grid_region(vec2!(j as i16, i as i16)),
-                        color: *color,
-                    });
-                }
-            }
-        };
+        self.stats.time += delta_t;
+        if self.board.fall(delta_t) {
+            self.add_player_to_stack(false);
+        }
+
+        Scene::LevelScene
+    }
+
+    /// Draws the board and the HUD, or the game over screen
+    pub fn draw(&self, canvas: &mut Canvas, alpha: f32) {
+        for (position, sprite) in &self.background {
+            let region = Rect {
+                center: *position,
+                size: vec2!(Self::TWINKLE_SIZE, Self::TWINKLE_SIZE),
+            };
+            canvas.draw_iter(sprite.image(region, layer::BACKGROUND));
+        }
+        self.particles.draw(canvas);
+        if let Some(stats) = &self.game_over {
+            self.draw_game_over(canvas, stats);
+            return;
+        }
+        self.board.draw(canvas, Vec2::ZERO, alpha, &self.block);
+        canvas.draw(NineSliceObject {
+            region: Self::NEXT_FRAME,
+            texture: self.brick.texture.clone(),
+            source: self.brick.source,
+            insets: Insets::uniform(4),
+            pixel_size: 0.5,
+            edges: EdgeMode::Tile,
+            fill: false,
+            tint: Rgba::WHITE,
+            layer: layer::GRID,
+            blend: BlendMode::Alpha,
+        });
 
         canvas.draw(TextObject{
-            color: Rgb::WHITE,
+            color: Rgba::WHITE,
             font_size: 5., 
-            position: vec2!(52., 100.),
-            text: format!("score: {}", self.score)
+            font: FontId::DEFAULT,
+            layer: layer::HUD,
+            blend: BlendMode::Alpha,
+            position: vec2!(52_f32, 100.),
+            text: format!("score: {}", self.board.score),
+            layout: TextLayout::default(),
         });
+        for (i, line) in self.stats.summary().into_iter().enumerate() {
+            canvas.draw(TextObject{
+                color: Rgba::WHITE,
+                font_size: 3.,
+                font: FontId::DEFAULT,
+                layer: layer::HUD,
+                blend: BlendMode::Alpha,
+                position: vec2!(-45., 100. - i as f32 * 4.),
+                text: line,
+                layout: TextLayout::default(),
+            });
+        }
         
         canvas.draw(TextObject{
-            color: Rgb::WHITE,
+            color: Rgba::WHITE,
             font_size: 5., 
-            position: vec2!(52., 80.),
-            text: "next tetraminos".to_owned()
+            font: FontId::DEFAULT,
+            layer: layer::HUD,
+            blend: BlendMode::Alpha,
+            position: vec2!(52_f32, 80.),
+            text: "next tetraminos".to_owned(),
+            layout: TextLayout::default(),
+        });
+        for (i, line) in self.trainer.hud().into_iter().enumerate() {
+            canvas.draw(TextObject{
+                color: Rgba::WHITE,
+                font_size: 3.,
+                font: FontId::DEFAULT,
+                layer: layer::HUD,
+                blend: BlendMode::Alpha,
+                position: vec2!(-45., 75. - i as f32 * 4.),
+                text: line,
+                layout: TextLayout::default(),
+            });
+        }
+    }
+    /// Draws the game over screen with all the statistics of the lost game
+    fn draw_game_over(&self, canvas: &mut Canvas, stats: &Stats) {
+        canvas.draw(TextObject{
+            color: Rgba::WHITE,
+            font_size: 8.,
+            font: FontId::DEFAULT,
+            layer: layer::HUD,
+            blend: BlendMode::Alpha,
+            position: vec2!(-10_f32, 100.),
+            text: "Fim de jogo".to_owned(),
+            layout: TextLayout::default(),
         });
-        let nexts = self.bag.next_tetraminos();
-        for (i, tetramino) in nexts.into_iter().enumerate() {
-            let pos = vec2!(52,  50 - i as i16 * 15 );
-            canvas.draw_iter(tetramino.object_vec(vec2!(0_i16,0)).into_iter().map(|mut obj| {
-                obj.region.center += pos;
-                obj
-            }));
+        let mut lines = stats.report();
+        lines.push(String::new());
+        lines.push("E: export stats   Enter: back".to_owned());
+        lines.extend(self.export_message.clone());
+        for (i, line) in lines.into_iter().enumerate() {
+            canvas.draw(TextObject{
+                color: Rgba::WHITE,
+                font_size: 4.,
+                font: FontId::DEFAULT,
+                layer: layer::HUD,
+                blend: BlendMode::Alpha,
+                position: vec2!(-10., 85. - i as f32 * 6.),
+                text: line,
+                layout: TextLayout::default(),
+            });
         }
+    }
+    /// Throws sparks along the rows cleared, given from the bottom as they were before the lock
+    fn emit_sparks(&mut self, rows: &[i16]) {
+        let width = self.board.columns as f32 * SIZE;
+        for &row in rows {
+            let settings = ParticleSettings {
+                min_lifetime: 0.3,
+                max_lifetime: 0.7,
+                min_speed: 20.,
+                max_speed: 60.,
+                area: vec2!(width, SIZE),
+                gravity: vec2!(0_f32, -60.),
+                drag: 1.5,
+                start_color: Rgba::new(255, 240, 160, 255),
+                end_color: Rgba::new(255, 120, 40, 0),
+                start_size: 1.2,
+                end_size: 0.3,
+                layer: layer::PARTICLES,
+                blend: BlendMode::Additive,
+                ..ParticleSettings::default()
+            };
+            let center = vec2!(width / 2., (row as f32 + 0.5) * SIZE);
+            self.particles.add(Emitter::new(settings, center, Emission::Burst(40), random()));
+        }
+    }
 
-        canvas.draw_iter(self.player.object_vec());
+    /// Throws dust from under the player after a hard drop
+    fn emit_dust(&mut self) {
+        let blocks: Vec<_> = self.board.player.get_blocks().collect();
+        let Some(bottom) = blocks.iter().map(|block| block.y).min() else {
+            return;
+        };
+        let settings = ParticleSettings {
+            min_lifetime: 0.3,
+            max_lifetime: 0.6,
+            min_speed: 5.,
+            max_speed: 15.,
+            direction: std::f32::consts::FRAC_PI_2,
+            spread: std::f32::consts::PI,
+            area: vec2!(SIZE, 0_f32),
+            gravity: vec2!(0_f32, -20.),
+            drag: 3.,
+            start_color: Rgba::new(180, 180, 180, 160),
+            end_color: Rgba::new(120, 120, 120, 0),
+            start_size: 1.,
+            end_size: 2.5,
+            layer: layer::PARTICLES,
+            ..ParticleSettings::default()
+        };
+        let mut emitter = Emitter::new(settings, Vec2::ZERO, Emission::Burst(0), random());
+        for block in blocks.iter().filter(|block| block.y == bottom) {
+            emitter.burst_at(vec2!(block.x as f32 + 0.5, bottom as f32) * SIZE, 6);
+        }
+        self.particles.add(emitter);
+    }
 
-        self.time += delta_t;
-        if self.time >= self.max_time {
-            if !self.translate_player(vec2!(0_i16, -1)) {
-                self.add_player_to_stack();
-            }
-            self.time -= self.max_time;
+    /// Breaks the stack into blocks that jump and fall off the screen
+    fn emit_collapse(&mut self) {
+        let settings = ParticleSettings {
+            min_lifetime: 1.5,
+            max_lifetime: 2.5,
+            min_speed: 10.,
+            max_speed: 40.,
+            direction: std::f32::consts::FRAC_PI_2,
+            spread: std::f32::consts::PI,
+            gravity: vec2!(0_f32, -120.),
+            start_size: SIZE,
+            end_size: SIZE * 0.6,
+            max_spin: 6.,
+            layer: layer::PARTICLES,
+            ..ParticleSettings::default()
+        };
+        // The particles of an emitter share the color, so there is an emitter for each color of the stack
+        let mut emitters: Vec<Emitter> = Vec::new();
+        for (position, color) in self.board.stack_blocks() {
+            let index = match emitters.iter().position(|emitter| emitter.settings.start_color == color.into()) {
+                Some(index) => index,
+                None => {
+                    let settings = ParticleSettings {
+                        start_color: color.into(),
+                        end_color: color.with_alpha(0),
+                        ..settings.clone()
+                    };
+                    emitters.push(Emitter::new(settings, Vec2::ZERO, Emission::Burst(0), random()));
+                    emitters.len() - 1
+                }
+            };
+            emitters[index].burst_at((position.to_vec2() + vec2!(0.5_f32, 0.5)) * SIZE, 1);
+        }
+        for emitter in emitters {
+            self.particles.add(emitter);
         }
+    }
 
-        Scene::LevelScene
+    /// The regions of the interface, with their names
+    pub fn ui_regions(&self) -> Vec<(&'static str, Rect)> {
+        vec![("next", Self::NEXT_FRAME)]
     }
-    pub fn on_click(&mut self, position : Vec2) -> Scene {
+
+    pub fn on_click(&mut self, _position : Vec2) -> Scene {
         Scene::LevelScene
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_placement_sent_back_by_the_trainer_has_no_effects() {
+        let interface = Interface::headless(1, 1).unwrap();
+        let mut level = LevelScene::with_seed(&interface, 7).unwrap();
+        level.trainer.enabled = true;
+        level.trainer.restart_on_mistake = true;
+
+        // Moving back and forth before dropping is a finesse fault
+        for key in [VirtualKeyCode::Left, VirtualKeyCode::Right, VirtualKeyCode::Space] {
+            level.key_down(key);
+        }
+        assert_eq!(level.stats.pieces_placed, 0);
+        assert_eq!(level.take_trauma(), 0.);
+        assert!(level.particles.emitters.is_empty());
+
+        level.key_down(VirtualKeyCode::Space);
+        assert_eq!(level.stats.pieces_placed, 1);
+        assert!(level.take_trauma() > 0.);
+        assert_eq!(level.particles.emitters.len(), 1);
+    }
+}
@@ -0,0 +1,819 @@
+//! Communication between two instances of the game over TCP, for the online versus
+//!
+//! The two instances run in lockstep: each one sends its inputs tagged with the frame they must be applied,
+//! and a frame is only simulated when the inputs of both players for it arrived.
+//! The messages are lines of text, so a session can be inspected with tools like `nc`.
+use std::{
+    collections::BTreeMap,
+    fmt,
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use super::finesse::Input;
+
+/// Version of the protocol, instances with different versions refuse to play together
+pub const PROTOCOL_VERSION: u32 = 1;
+/// Number of frames between an input being done and applied, hiding the latency of the connection
+pub const INPUT_DELAY: u64 = 3;
+/// Number of frames between two checks of desynchronization
+pub const HASH_INTERVAL: u64 = 60;
+/// Number of frames past the next one to simulate the messages of the opponent can refer to
+///
+/// The opponent simulates at most [INPUT_DELAY] frames past ours, since it waits for our inputs,
+/// and sends its inputs at most [INPUT_DELAY] frames past its own
+const MAX_FRAMES_AHEAD: u64 = 2 * INPUT_DELAY;
+/// Time between two measurements of latency
+const PING_INTERVAL: Duration = Duration::from_secs(1);
+/// Time to wait for the host when joining
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Time without any message after which the opponent is considered gone, several times the [PING_INTERVAL]
+const PEER_TIMEOUT: Duration = Duration::from_secs(10);
+/// Bytes waiting to be sent after which the opponent is considered to have stopped reading
+const MAX_PENDING: usize = 64 * 1024;
+/// Longest line accepted, the messages are much shorter
+const MAX_LINE: usize = 4096;
+
+/// How this instance takes part in the session
+#[derive(Debug, Clone)]
+pub enum Role {
+    /// Waits for the opponent in a port
+    Host(u16),
+    /// Connects to the host in an address
+    Join(String),
+}
+
+impl Role {
+    /// Reads the role from the command line arguments `--host <port>` or `--join <address>`
+    pub fn from_args<I: IntoIterator<Item = String>>(args: I) -> Option<Role> {
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--host" => return args.next().and_then(|port| port.parse().ok()).map(Role::Host),
+                "--join" => return args.next().map(Role::Join),
+                _ => {}
+            }
+        }
+        None
+    }
+}
+
+/// A message exchanged between the instances
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    /// First message of each side, the seed of the host is used by both
+    Hello { version: u32, seed: u64 },
+    /// Inputs of the sender to apply in a frame
+    Inputs { frame: u64, inputs: Vec<Input> },
+    /// Lines of garbage the board of the sender sent in a frame
+    Garbage { frame: u64, lines: u32 },
+    /// Hash of both boards after a frame
+    Hash { frame: u64, hash: u64 },
+    /// Request of a [Message::Pong] with the same time
+    Ping { time: u64 },
+    /// Answer of a [Message::Ping]
+    Pong { time: u64 },
+    /// The sender left the session
+    Bye,
+}
+
+/// Error when parsing a [Message]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseMessageError(String);
+
+impl fmt::Display for ParseMessageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid message: {:?}", self.0)
+    }
+}
+
+impl std::error::Error for ParseMessageError {}
+
+impl Input {
+    fn code(self) -> char {
+        match self {
+            Input::Left => 'L',
+            Input::Right => 'R',
+            Input::Rotate => 'U',
+            Input::SoftDrop => 'D',
+            Input::HardDrop => 'H',
+        }
+    }
+    fn from_code(code: char) -> Option<Input> {
+        match code {
+            'L' => Some(Input::Left),
+            'R' => Some(Input::Right),
+            'U' => Some(Input::Rotate),
+            'D' => Some(Input::SoftDrop),
+            'H' => Some(Input::HardDrop),
+            _ => None,
+        }
+    }
+}
+
+impl Message {
+    /// The frame the message refers to, if any
+    fn frame(&self) -> Option<u64> {
+        match self {
+            Message::Inputs { frame, .. } | Message::Garbage { frame, .. } | Message::Hash { frame, .. } => Some(*frame),
+            Message::Hello { .. } | Message::Ping { .. } | Message::Pong { .. } | Message::Bye => None,
+        }
+    }
+}
+
+/// Parses the next part of a message, failing with the whole `line` when it is missing or invalid
+fn parse_part<'a, T: FromStr>(parts: &mut impl Iterator<Item = &'a str>, line: &str) -> Result<T, ParseMessageError> {
+    parts
+        .next()
+        .and_then(|part| part.parse().ok())
+        .ok_or_else(|| ParseMessageError(line.to_owned()))
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Message::Hello { version, seed } => write!(f, "HELLO {version} {seed}"),
+            Message::Inputs { frame, inputs } => {
+                let inputs: String = inputs.iter().map(|input| input.code()).collect();
+                write!(f, "INPUTS {frame} -{inputs}")
+            }
+            Message::Garbage { frame, lines } => write!(f, "GARBAGE {frame} {lines}"),
+            Message::Hash { frame, hash } => write!(f, "HASH {frame} {hash:016x}"),
+            Message::Ping { time } => write!(f, "PING {time}"),
+            Message::Pong { time } => write!(f, "PONG {time}"),
+            Message::Bye => write!(f, "BYE"),
+        }
+    }
+}
+
+impl FromStr for Message {
+    type Err = ParseMessageError;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let error = || ParseMessageError(line.to_owned());
+        let mut parts = line.split_whitespace();
+        let kind = parts.next().ok_or_else(error)?;
+        let message = match kind {
+            "HELLO" => Message::Hello {
+                version: parse_part(&mut parts, line)?,
+                seed: parse_part(&mut parts, line)?,
+            },
+            "INPUTS" => {
+                let frame = parse_part(&mut parts, line)?;
+                let inputs = parts
+                    .next()
+                    .and_then(|part| part.strip_prefix('-'))
+                    .ok_or_else(error)?
+                    .chars()
+                    .map(Input::from_code)
+                    .collect::<Option<Vec<Input>>>()
+                    .ok_or_else(error)?;
+                Message::Inputs { frame, inputs }
+            }
+            "GARBAGE" => Message::Garbage {
+                frame: parse_part(&mut parts, line)?,
+                lines: parse_part(&mut parts, line)?,
+            },
+            "HASH" => Message::Hash {
+                frame: parse_part(&mut parts, line)?,
+                hash: parts
+                    .next()
+                    .and_then(|part| u64::from_str_radix(part, 16).ok())
+                    .ok_or_else(error)?,
+            },
+            "PING" => Message::Ping { time: parse_part(&mut parts, line)? },
+            "PONG" => Message::Pong { time: parse_part(&mut parts, line)? },
+            "BYE" => Message::Bye,
+            _ => return Err(error()),
+        };
+        Ok(message)
+    }
+}
+
+/// A non-blocking TCP connection that exchanges [Message]s
+///
+/// Neither sending nor receiving waits: the bytes the socket does not take are queued and sent on the next calls
+#[derive(Debug)]
+pub struct Connection {
+    stream: TcpStream,
+    /// Bytes received that do not form a complete line yet
+    buffer: Vec<u8>,
+    /// Bytes of the messages sent that the socket did not take yet
+    pending: Vec<u8>,
+    /// Why the connection was closed
+    closed: Option<String>,
+}
+
+impl Connection {
+    /// Wraps a connected stream
+    pub fn new(stream: TcpStream) -> io::Result<Connection> {
+        stream.set_nodelay(true)?;
+        stream.set_nonblocking(true)?;
+        Ok(Connection {
+            stream,
+            buffer: vec![],
+            pending: vec![],
+            closed: None,
+        })
+    }
+
+    /// Connects to a host, waiting at most a few seconds
+    pub fn connect<A: ToSocketAddrs>(address: A) -> io::Result<Connection> {
+        let mut last_error = io::Error::new(io::ErrorKind::NotFound, "no address to connect");
+        for address in address.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&address, CONNECT_TIMEOUT) {
+                Ok(stream) => return Connection::new(stream),
+                Err(err) => last_error = err,
+            }
+        }
+        Err(last_error)
+    }
+
+    /// Whether the other side closed the connection or it failed
+    pub fn is_closed(&self) -> bool {
+        self.closed.is_some()
+    }
+
+    /// Why the connection was closed, if it was
+    pub fn close_reason(&self) -> Option<&str> {
+        self.closed.as_deref()
+    }
+
+    fn close(&mut self, reason: &str) {
+        self.closed.get_or_insert_with(|| reason.to_owned());
+    }
+
+    /// Queues a message and sends what the socket takes without waiting
+    pub fn send(&mut self, message: &Message) {
+        if self.is_closed() {
+            return;
+        }
+        self.pending.extend_from_slice(format!("{message}\n").as_bytes());
+        self.flush();
+    }
+
+    /// Sends the queued bytes the socket takes, closing the connection when too many are left
+    fn flush(&mut self) {
+        while !self.pending.is_empty() && !self.is_closed() {
+            match self.stream.write(&self.pending) {
+                Ok(0) => self.close("connection lost"),
+                Ok(n) => {
+                    self.pending.drain(..n);
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(_) => self.close("connection lost"),
+            }
+        }
+        // The socket has its own buffer, so this only grows when the opponent stopped reading
+        if self.pending.len() > MAX_PENDING {
+            self.close("opponent stopped responding");
+        }
+    }
+
+    /// Sends the queued messages and returns every message received since the last call
+    ///
+    /// Lines that are not valid messages are ignored
+    pub fn receive(&mut self) -> Vec<Message> {
+        self.flush();
+        let mut messages = vec![];
+        let mut chunk = [0u8; 4096];
+        while !self.is_closed() {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => self.close("connection lost"),
+                Ok(n) => {
+                    self.buffer.extend_from_slice(&chunk[..n]);
+                    self.take_lines(&mut messages);
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(_) => self.close("connection lost"),
+            }
+        }
+        messages
+    }
+
+    /// Parses the complete lines of the buffer, closing the connection when what is left is too long to be a line
+    fn take_lines(&mut self, messages: &mut Vec<Message>) {
+        while let Some(end) = self.buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            if let Ok(message) = String::from_utf8_lossy(&line).trim().parse() {
+                messages.push(message);
+            }
+        }
+        if self.buffer.len() > MAX_LINE {
+            self.buffer.clear();
+            self.close("opponent sent an invalid message");
+        }
+    }
+}
+
+/// Waits without blocking for an opponent to connect
+#[derive(Debug)]
+pub struct Listener {
+    listener: TcpListener,
+}
+
+impl Listener {
+    /// Starts to listen in `port` of every interface
+    pub fn bind(port: u16) -> io::Result<Listener> {
+        let listener = TcpListener::bind(SocketAddr::from(([0, 0, 0, 0], port)))?;
+        listener.set_nonblocking(true)?;
+        Ok(Listener { listener })
+    }
+
+    /// The address being listened
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Returns the connection of the opponent, if one arrived
+    pub fn accept(&self) -> io::Result<Option<Connection>> {
+        match self.listener.accept() {
+            Ok((stream, _)) => Connection::new(stream).map(Some),
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+/// State of a [Session]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Status {
+    /// Waiting for the [Message::Hello] of the opponent
+    Handshake,
+    /// Both sides are playing
+    Playing,
+    /// The hashes of a frame were different in each side
+    Desync { frame: u64 },
+    /// The connection was closed, with the reason
+    Disconnected(String),
+}
+
+/// A lockstep session with an opponent
+///
+/// It does not know about the game: each frame the scene gives the local inputs with [Session::push_inputs]
+/// and receives the inputs of both players from [Session::next_frame]
+#[derive(Debug)]
+pub struct Session {
+    connection: Connection,
+    /// Index of the local player: 0 for the host, 1 for the guest
+    pub local_player: usize,
+    /// Seed of the bags of both boards
+    pub seed: u64,
+    pub status: Status,
+    /// Next frame to be simulated
+    frame: u64,
+    /// Next frame to receive local inputs
+    input_frame: u64,
+    local_inputs: BTreeMap<u64, Vec<Input>>,
+    remote_inputs: BTreeMap<u64, Vec<Input>>,
+    /// Garbage the opponent said its board sent in frames not simulated yet
+    remote_garbage: BTreeMap<u64, u32>,
+    /// Garbage the remote board sent in the simulation, waiting for the message of the opponent
+    expected_garbage: BTreeMap<u64, u32>,
+    local_hashes: BTreeMap<u64, u64>,
+    remote_hashes: BTreeMap<u64, u64>,
+    start: Instant,
+    last_ping: Option<Instant>,
+    /// When the last message of the opponent arrived, or when the session started
+    last_received: Instant,
+    /// Round trip time of the last ping
+    pub latency: Option<Duration>,
+}
+
+impl Session {
+    /// Creates a session over a connection and sends the [Message::Hello]
+    ///
+    /// The host proposes the seed, the guest uses the one it receives
+    pub fn new(connection: Connection, host: bool, seed: u64) -> Session {
+        let mut session = Session {
+            connection,
+            local_player: if host { 0 } else { 1 },
+            seed,
+            status: Status::Handshake,
+            frame: 0,
+            input_frame: INPUT_DELAY,
+            local_inputs: BTreeMap::new(),
+            remote_inputs: BTreeMap::new(),
+            remote_garbage: BTreeMap::new(),
+            expected_garbage: BTreeMap::new(),
+            local_hashes: BTreeMap::new(),
+            remote_hashes: BTreeMap::new(),
+            start: Instant::now(),
+            last_ping: None,
+            last_received: Instant::now(),
+            latency: None,
+        };
+        // The first frames have no inputs, as nobody could have pressed a key before them
+        for frame in 0..INPUT_DELAY {
+            session.local_inputs.insert(frame, vec![]);
+            session.remote_inputs.insert(frame, vec![]);
+        }
+        session.connection.send(&Message::Hello {
+            version: PROTOCOL_VERSION,
+            seed,
+        });
+        session
+    }
+
+    /// Next frame to be simulated, the number of frames simulated so far
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Index of the opponent
+    pub fn remote_player(&self) -> usize {
+        1 - self.local_player
+    }
+
+    /// Whether the session can still advance
+    pub fn is_active(&self) -> bool {
+        matches!(self.status, Status::Handshake | Status::Playing)
+    }
+
+    /// Reads the messages of the opponent and measures the latency
+    ///
+    /// An opponent that refers to a frame more than [MAX_FRAMES_AHEAD] frames past the next one is disconnected,
+    /// instead of having its messages kept until the simulation reaches them.
+    /// An opponent silent for [PEER_TIMEOUT] is disconnected too, as it answers the pings while it plays
+    pub fn poll(&mut self) {
+        let messages = self.connection.receive();
+        if !messages.is_empty() {
+            self.last_received = Instant::now();
+        } else if self.last_received.elapsed() >= PEER_TIMEOUT {
+            self.disconnect("opponent stopped responding".to_owned());
+        }
+        for message in messages {
+            if message.frame().is_some_and(|frame| frame > self.frame + MAX_FRAMES_AHEAD) {
+                self.disconnect("opponent sent a frame too far ahead".to_owned());
+                return;
+            }
+            match message {
+                Message::Hello { version, seed } => {
+                    if version != PROTOCOL_VERSION {
+                        self.disconnect(format!("opponent uses protocol {version}, we use {PROTOCOL_VERSION}"));
+                        return;
+                    }
+                    if self.local_player == 1 {
+                        self.seed = seed;
+                    }
+                    if self.status == Status::Handshake {
+                        self.status = Status::Playing;
+                    }
+                }
+                // The inputs of a frame already simulated are a repetition, keeping them would never free them
+                Message::Inputs { frame, inputs } => {
+                    if frame >= self.frame {
+                        self.remote_inputs.insert(frame, inputs);
+                    }
+                }
+                Message::Garbage { frame, lines } => {
+                    if let Some(expected) = self.expected_garbage.remove(&frame) {
+                        self.compare_garbage(frame, expected, lines);
+                    } else if frame < self.frame {
+                        self.compare_garbage(frame, 0, lines);
+                    } else {
+                        self.remote_garbage.insert(frame, lines);
+                    }
+                }
+                Message::Hash { frame, hash } => {
+                    if frame >= self.frame || self.local_hashes.contains_key(&frame) {
+                        self.remote_hashes.insert(frame, hash);
+                        self.check_hash(frame);
+                    }
+                }
+                Message::Ping { time } => self.connection.send(&Message::Pong { time }),
+                Message::Pong { time } => {
+                    let now = self.start.elapsed().as_micros() as u64;
+                    self.latency = Some(Duration::from_micros(now.saturating_sub(time)));
+                }
+                Message::Bye => {
+                    self.disconnect("opponent left".to_owned());
+                    return;
+                }
+            }
+        }
+        if let Some(reason) = self.connection.close_reason() {
+            let reason = reason.to_owned();
+            self.disconnect(reason);
+        }
+        if self.status == Status::Playing && self.last_ping.is_none_or(|last| last.elapsed() >= PING_INTERVAL) {
+            self.last_ping = Some(Instant::now());
+            let time = self.start.elapsed().as_micros() as u64;
+            self.connection.send(&Message::Ping { time });
+        }
+    }
+
+    /// Sends the inputs done by the local player since the last call
+    ///
+    /// They will be applied [INPUT_DELAY] frames after the current one.
+    /// Nothing is sent when the local side is already that far ahead of the simulation
+    pub fn push_inputs(&mut self, inputs: &mut Vec<Input>) {
+        if self.status != Status::Playing || self.input_frame >= self.frame + INPUT_DELAY {
+            return;
+        }
+        let inputs = std::mem::take(inputs);
+        self.connection.send(&Message::Inputs {
+            frame: self.input_frame,
+            inputs: inputs.clone(),
+        });
+        self.local_inputs.insert(self.input_frame, inputs);
+        self.input_frame += 1;
+    }
+
+    /// Returns the inputs of both players for the next frame, indexed by player, if both arrived
+    pub fn next_frame(&mut self) -> Option<(u64, [Vec<Input>; 2])> {
+        if self.status != Status::Playing
+            || !self.local_inputs.contains_key(&self.frame)
+            || !self.remote_inputs.contains_key(&self.frame)
+        {
+            return None;
+        }
+        let local = self.local_inputs.remove(&self.frame)?;
+        let remote = self.remote_inputs.remove(&self.frame)?;
+        let frame = self.frame;
+        self.frame += 1;
+        let inputs = if self.local_player == 0 { [local, remote] } else { [remote, local] };
+        Some((frame, inputs))
+    }
+
+    /// Tells the opponent that the local board sent garbage in a frame
+    pub fn send_garbage(&mut self, frame: u64, lines: u32) {
+        self.connection.send(&Message::Garbage { frame, lines });
+    }
+
+    /// Checks the garbage the remote board sent in the simulation of a frame against what the opponent said
+    ///
+    /// The message of the opponent may arrive after the frame is simulated, in which case it is checked when it arrives
+    pub fn check_garbage(&mut self, frame: u64, lines: u32) {
+        if let Some(reported) = self.remote_garbage.remove(&frame) {
+            self.compare_garbage(frame, lines, reported);
+        } else if lines > 0 {
+            self.expected_garbage.insert(frame, lines);
+        }
+    }
+
+    fn compare_garbage(&mut self, frame: u64, simulated: u32, reported: u32) {
+        if simulated != reported && self.is_active() {
+            self.status = Status::Desync { frame };
+        }
+    }
+
+    /// Records the hash of both boards after a frame, sending it to the opponent
+    ///
+    /// Only the frames multiple of [HASH_INTERVAL] are compared, the hashes of the others are ignored
+    pub fn record_hash(&mut self, frame: u64, hash: u64) {
+        if !frame.is_multiple_of(HASH_INTERVAL) {
+            return;
+        }
+        self.local_hashes.insert(frame, hash);
+        self.connection.send(&Message::Hash { frame, hash });
+        self.check_hash(frame);
+    }
+
+    fn check_hash(&mut self, frame: u64) {
+        let (Some(local), Some(remote)) = (self.local_hashes.get(&frame), self.remote_hashes.get(&frame)) else {
+            return;
+        };
+        if local != remote && self.is_active() {
+            self.status = Status::Desync { frame };
+        }
+        self.local_hashes.remove(&frame);
+        self.remote_hashes.remove(&frame);
+    }
+
+    /// Leaves the session, telling the opponent
+    pub fn leave(&mut self) {
+        self.connection.send(&Message::Bye);
+        if self.is_active() {
+            self.status = Status::Disconnected("left the session".to_owned());
+        }
+    }
+
+    fn disconnect(&mut self, reason: String) {
+        if self.is_active() {
+            self.status = Status::Disconnected(reason);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::Ipv4Addr, thread};
+
+    use super::*;
+
+    /// Longest a test waits for the other side over loopback
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Calls `step` until it returns something, failing the test after [TIMEOUT]
+    fn wait<T>(mut step: impl FnMut() -> Option<T>) -> T {
+        let start = Instant::now();
+        loop {
+            if let Some(value) = step() {
+                return value;
+            }
+            assert!(start.elapsed() < TIMEOUT, "timed out waiting over loopback");
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    /// Two connections to each other over loopback
+    fn connected_pair() -> (Connection, Connection) {
+        let listener = Listener::bind(0).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let guest = Connection::connect((Ipv4Addr::LOCALHOST, port)).unwrap();
+        let host = wait(|| listener.accept().unwrap());
+        (host, guest)
+    }
+
+    /// A host and a guest that finished the handshake
+    fn playing_pair() -> (Session, Session) {
+        let (host, guest) = connected_pair();
+        let mut host = Session::new(host, true, 42);
+        let mut guest = Session::new(guest, false, 7);
+        wait(|| {
+            host.poll();
+            guest.poll();
+            (host.status == Status::Playing && guest.status == Status::Playing).then_some(())
+        });
+        (host, guest)
+    }
+
+    /// Waits for the inputs of both players of the next frame
+    fn next_frame(session: &mut Session) -> (u64, [Vec<Input>; 2]) {
+        wait(|| {
+            session.poll();
+            session.next_frame()
+        })
+    }
+
+    #[test]
+    fn messages_survive_a_round_trip_as_text() {
+        let messages = [
+            Message::Hello { version: PROTOCOL_VERSION, seed: u64::MAX },
+            Message::Inputs { frame: 12, inputs: vec![] },
+            Message::Inputs {
+                frame: 13,
+                inputs: vec![Input::Left, Input::Right, Input::Rotate, Input::SoftDrop, Input::HardDrop],
+            },
+            Message::Garbage { frame: 60, lines: 4 },
+            Message::Hash { frame: 120, hash: 0x0123_4567_89ab_cdef },
+            Message::Ping { time: 1_000_000 },
+            Message::Pong { time: 1_000_000 },
+            Message::Bye,
+        ];
+        for message in messages {
+            assert_eq!(message.to_string().parse::<Message>(), Ok(message));
+        }
+        assert!("INPUTS 3 -X".parse::<Message>().is_err());
+        assert!("HASH 3".parse::<Message>().is_err());
+        assert!("HI".parse::<Message>().is_err());
+    }
+
+    #[test]
+    fn numbers_too_big_for_their_field_are_rejected() {
+        let wrapped = u64::from(u32::MAX) + 1 + u64::from(PROTOCOL_VERSION);
+        assert!(format!("HELLO {wrapped} 0").parse::<Message>().is_err());
+        assert!(format!("GARBAGE 0 {}", u64::from(u32::MAX) + 1).parse::<Message>().is_err());
+    }
+
+    #[test]
+    fn the_guest_plays_with_the_seed_of_the_host() {
+        let (host, guest) = playing_pair();
+        assert_eq!((host.seed, guest.seed), (42, 42));
+        assert_eq!((host.local_player, guest.local_player), (0, 1));
+    }
+
+    #[test]
+    fn inputs_are_applied_after_the_delay_in_both_sides() {
+        let (mut host, mut guest) = playing_pair();
+        assert_eq!(next_frame(&mut host), (0, [vec![], vec![]]));
+        assert_eq!(next_frame(&mut guest), (0, [vec![], vec![]]));
+        host.push_inputs(&mut vec![Input::Left]);
+        guest.push_inputs(&mut vec![Input::Rotate, Input::HardDrop]);
+        for frame in 1..INPUT_DELAY {
+            host.push_inputs(&mut vec![]);
+            guest.push_inputs(&mut vec![]);
+            assert_eq!(next_frame(&mut host), (frame, [vec![], vec![]]));
+            assert_eq!(next_frame(&mut guest), (frame, [vec![], vec![]]));
+        }
+        let expected = (INPUT_DELAY, [vec![Input::Left], vec![Input::Rotate, Input::HardDrop]]);
+        assert_eq!(next_frame(&mut host), expected);
+        assert_eq!(next_frame(&mut guest), expected);
+    }
+
+    #[test]
+    fn garbage_is_checked_against_the_simulation() {
+        let (mut host, mut guest) = playing_pair();
+        host.send_garbage(5, 2);
+        guest.check_garbage(5, 2);
+        wait(|| {
+            guest.poll();
+            guest.expected_garbage.is_empty().then_some(())
+        });
+        assert_eq!(guest.status, Status::Playing);
+
+        host.send_garbage(6, 1);
+        guest.check_garbage(6, 3);
+        wait(|| {
+            guest.poll();
+            (guest.status == Status::Desync { frame: 6 }).then_some(())
+        });
+    }
+
+    #[test]
+    fn different_hashes_are_a_desync() {
+        let (mut host, mut guest) = playing_pair();
+        // Both sides simulated past the frames of the hashes
+        host.frame = 2 * HASH_INTERVAL + 1;
+        guest.frame = 2 * HASH_INTERVAL + 1;
+        host.record_hash(HASH_INTERVAL, 1);
+        guest.record_hash(HASH_INTERVAL, 1);
+        host.record_hash(2 * HASH_INTERVAL, 1);
+        guest.record_hash(2 * HASH_INTERVAL, 2);
+        for session in [&mut host, &mut guest] {
+            wait(|| {
+                session.poll();
+                (session.status == Status::Desync { frame: 2 * HASH_INTERVAL }).then_some(())
+            });
+        }
+    }
+
+    #[test]
+    fn frames_far_ahead_disconnect_the_opponent() {
+        let (mut host, mut guest) = playing_pair();
+        guest.connection.send(&Message::Inputs { frame: MAX_FRAMES_AHEAD, inputs: vec![] });
+        guest.connection.send(&Message::Inputs { frame: MAX_FRAMES_AHEAD + 1, inputs: vec![] });
+        wait(|| {
+            host.poll();
+            (!host.is_active()).then_some(())
+        });
+        assert_eq!(host.status, Status::Disconnected("opponent sent a frame too far ahead".to_owned()));
+        assert!(!host.remote_inputs.contains_key(&(MAX_FRAMES_AHEAD + 1)));
+    }
+
+    #[test]
+    fn leaving_disconnects_the_opponent() {
+        let (mut host, mut guest) = playing_pair();
+        guest.leave();
+        assert_eq!(guest.status, Status::Disconnected("left the session".to_owned()));
+        wait(|| {
+            host.poll();
+            (!host.is_active()).then_some(())
+        });
+        assert_eq!(host.status, Status::Disconnected("opponent left".to_owned()));
+    }
+
+    #[test]
+    fn an_opponent_that_drops_is_disconnected() {
+        let (mut host, guest) = playing_pair();
+        drop(guest);
+        wait(|| {
+            host.poll();
+            (!host.is_active()).then_some(())
+        });
+        assert_eq!(host.status, Status::Disconnected("connection lost".to_owned()));
+    }
+
+    #[test]
+    fn a_silent_opponent_is_disconnected() {
+        let (mut host, _guest) = playing_pair();
+        host.poll();
+        assert!(host.is_active());
+        host.last_received -= PEER_TIMEOUT;
+        host.poll();
+        assert_eq!(host.status, Status::Disconnected("opponent stopped responding".to_owned()));
+    }
+
+    #[test]
+    fn an_opponent_that_stops_reading_closes_the_connection_without_blocking() {
+        let (mut host, _guest) = connected_pair();
+        let message = Message::Inputs { frame: 0, inputs: vec![Input::SoftDrop; 64] };
+        // The guest never reads, so the socket buffers fill and the bytes start to queue
+        wait(|| {
+            (0..10_000).any(|_| {
+                host.send(&message);
+                host.is_closed()
+            })
+            .then_some(())
+        });
+        assert_eq!(host.close_reason(), Some("opponent stopped responding"));
+    }
+
+    #[test]
+    fn a_line_too_long_closes_the_connection() {
+        let (mut host, guest) = connected_pair();
+        let mut stream = guest.stream;
+        stream.set_nonblocking(false).unwrap();
+        stream.write_all(&vec![b'A'; MAX_LINE + 1]).unwrap();
+        wait(|| {
+            host.receive();
+            host.close_reason().map(str::to_owned)
+        });
+        assert_eq!(host.close_reason(), Some("opponent sent an invalid message"));
+    }
+}
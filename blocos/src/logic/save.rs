@@ -0,0 +1,198 @@
+//! Saving and loading of a game in progress
+//!
+//! The save is a text file with one `key value...` field per line, the first line being the version.
+//! Saves of other versions are rejected instead of being read wrongly.
+use std::{
+    collections::HashMap,
+    fmt::{self, Display},
+    fs, io,
+    path::Path,
+    str::FromStr,
+};
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha12Rng;
+
+/// Version of the format of the save, must be increased when the fields change
+pub const SAVE_VERSION: u32 = 2;
+/// First word of the save, identifying the file
+const MAGIC: &str = "blocos-save";
+/// Number of words in the stream of a [ChaCha12Rng], its position is a 68 bit number
+const RNG_STREAM_WORDS: u128 = 1 << 68;
+
+/// Error when loading a save
+#[derive(Debug)]
+pub enum SaveError {
+    /// The file could not be read
+    Io(io::Error),
+    /// The file is not a save of the game
+    NotASave,
+    /// The save was written by another version of the game
+    Version {
+        /// Version of the save
+        found: u32,
+    },
+    /// A field that should be in the save is not there
+    Missing(String),
+    /// A field has a value that cannot be read
+    Invalid(String),
+}
+
+impl Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::Io(err) => write!(f, "could not read the save: {err}"),
+            SaveError::NotASave => write!(f, "the file is not a save"),
+            SaveError::Version { found } => {
+                write!(f, "the save has version {found}, but this game reads version {SAVE_VERSION}")
+            }
+            SaveError::Missing(key) => write!(f, "the save has no field `{key}`"),
+            SaveError::Invalid(key) => write!(f, "the field `{key}` of the save is invalid"),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+impl From<io::Error> for SaveError {
+    fn from(err: io::Error) -> Self {
+        SaveError::Io(err)
+    }
+}
+
+/// Builds the text of a save
+#[derive(Debug, Default)]
+pub struct SaveWriter {
+    lines: Vec<String>,
+}
+
+impl SaveWriter {
+    /// Create a writer with the header of the current version
+    pub fn new() -> SaveWriter {
+        SaveWriter {
+            lines: vec![format!("{MAGIC} {SAVE_VERSION}")],
+        }
+    }
+
+    /// Writes a field with a single value
+    pub fn field<T: Display>(&mut self, key: &str, value: T) {
+        self.lines.push(format!("{key} {value}"));
+    }
+
+    /// Writes a field with a list of values, separated by spaces
+    pub fn list<T: Display, I: IntoIterator<Item = T>>(&mut self, key: &str, values: I) {
+        let mut line = key.to_owned();
+        for value in values {
+            line.push(' ');
+            line.push_str(&value.to_string());
+        }
+        self.lines.push(line);
+    }
+
+    /// Writes the position of a generator, so it continues from there without repeating what it generated
+    pub fn rng(&mut self, key: &str, rng: &ChaCha12Rng) {
+        self.field(key, rng.get_word_pos());
+    }
+
+    /// The text of the save, one field per line
+    pub fn text(&self) -> String {
+        self.lines.join("\n") + "\n"
+    }
+
+    /// Writes the save to a file
+    ///
+    /// The text goes to a temporary file next to it that then replaces the file, so a crash while writing
+    /// keeps the previous save intact
+    pub fn write_to(&self, path: &Path) -> io::Result<()> {
+        let temporary = path.with_extension("tmp");
+        fs::write(&temporary, self.text())?;
+        fs::rename(&temporary, path)
+    }
+}
+
+/// Reads the fields of a save
+#[derive(Debug)]
+pub struct SaveReader {
+    /// The values of each line, by key. Keys can repeat, like the rows of the stack
+    fields: HashMap<String, Vec<Vec<String>>>,
+}
+
+impl SaveReader {
+    /// Reads a save from a file, checking its version
+    pub fn read_from(path: &Path) -> Result<SaveReader, SaveError> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Reads the text of a save, checking its version
+    pub fn parse(text: &str) -> Result<SaveReader, SaveError> {
+        let mut lines = text.lines();
+        let mut header = lines.next().ok_or(SaveError::NotASave)?.split_whitespace();
+        if header.next() != Some(MAGIC) {
+            return Err(SaveError::NotASave);
+        }
+        let version: u32 = header
+            .next()
+            .and_then(|version| version.parse().ok())
+            .ok_or(SaveError::NotASave)?;
+        if version != SAVE_VERSION {
+            return Err(SaveError::Version { found: version });
+        }
+
+        let mut fields: HashMap<String, Vec<Vec<String>>> = HashMap::new();
+        for line in lines {
+            let mut words = line.split_whitespace().map(str::to_owned);
+            if let Some(key) = words.next() {
+                fields.entry(key).or_default().push(words.collect());
+            }
+        }
+        Ok(SaveReader { fields })
+    }
+
+    /// Reads a field with a single value
+    pub fn field<T: FromStr>(&self, key: &str) -> Result<T, SaveError> {
+        self.list(key)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| SaveError::Invalid(key.to_owned()))
+    }
+
+    /// Reads a field with a list of values
+    pub fn list<T: FromStr>(&self, key: &str) -> Result<Vec<T>, SaveError> {
+        let line = self
+            .fields
+            .get(key)
+            .and_then(|lines| lines.first())
+            .ok_or_else(|| SaveError::Missing(key.to_owned()))?;
+        parse_all(key, line)
+    }
+
+    /// Reads a generator written by [SaveWriter::rng], recreating it from `seed`
+    ///
+    /// Fails with [SaveError::Invalid] when the position is past the end of the stream of the generator
+    pub fn rng(&self, key: &str, seed: u64) -> Result<ChaCha12Rng, SaveError> {
+        let position: u128 = self.field(key)?;
+        if position >= RNG_STREAM_WORDS {
+            return Err(SaveError::Invalid(key.to_owned()));
+        }
+        let mut rng = ChaCha12Rng::seed_from_u64(seed);
+        rng.set_word_pos(position);
+        Ok(rng)
+    }
+
+    /// Reads every line of a field that can repeat, each one as a list of values
+    pub fn repeated<T: FromStr>(&self, key: &str) -> Result<Vec<Vec<T>>, SaveError> {
+        self.fields
+            .get(key)
+            .map_or(&[][..], Vec::as_slice)
+            .iter()
+            .map(|line| parse_all(key, line))
+            .collect()
+    }
+}
+
+fn parse_all<T: FromStr>(key: &str, values: &[String]) -> Result<Vec<T>, SaveError> {
+    values
+        .iter()
+        .map(|value| value.parse().map_err(|_| SaveError::Invalid(key.to_owned())))
+        .collect()
+}
@@ -0,0 +1,155 @@
+//! Preferences of the player kept between games, like the effects applied to the screen
+use std::path::Path;
+
+use glium::glutin::event::VirtualKeyCode;
+
+use crate::gui::{
+    systems::{PostEffect, PostProcess},
+    window::{FullscreenMode, WindowConfig},
+};
+
+use super::save::{SaveError, SaveReader, SaveWriter};
+
+/// Preferences of the player, saved in [Settings::PATH] whenever they change
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Settings {
+    /// Scanlines and bent screen of an old monitor
+    pub crt: bool,
+    /// Glow around the bright blocks
+    pub bloom: bool,
+    /// Stronger colors and contrast
+    pub color_grading: bool,
+    /// Darker borders of the screen
+    pub vignette: bool,
+    /// Pixelated transition when the scene changes
+    pub transitions: bool,
+    /// How the window covers the monitor
+    pub fullscreen: FullscreenMode,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            crt: false,
+            bloom: false,
+            color_grading: false,
+            vignette: false,
+            transitions: true,
+            fullscreen: FullscreenMode::Windowed,
+        }
+    }
+}
+
+impl Settings {
+    /// File where the settings are saved
+    pub const PATH: &'static str = "settings.txt";
+    /// Largest size of the pixels of the transition, in pixels of the window
+    const TRANSITION_PIXEL_SIZE: f32 = 24.;
+
+    /// Reads the settings saved, or the default ones if they cannot be read
+    pub fn load() -> Settings {
+        match Self::read() {
+            Ok(settings) => settings,
+            Err(SaveError::Io(_)) => Settings::default(),
+            Err(err) => {
+                eprintln!("could not read the settings, using the default ones: {err}");
+                Settings::default()
+            }
+        }
+    }
+
+    fn read() -> Result<Settings, SaveError> {
+        let save = SaveReader::read_from(Path::new(Self::PATH))?;
+        Ok(Settings {
+            crt: save.field("crt")?,
+            bloom: save.field("bloom")?,
+            color_grading: save.field("color_grading")?,
+            vignette: save.field("vignette")?,
+            transitions: save.field("transitions")?,
+            // Settings saved before the option existed do not have it
+            fullscreen: match save.field::<String>("fullscreen") {
+                Ok(name) => Self::parse_fullscreen(&name).ok_or_else(|| SaveError::Invalid("fullscreen".to_owned()))?,
+                Err(_) => FullscreenMode::Windowed,
+            },
+        })
+    }
+
+    /// Writes the settings to [Settings::PATH]
+    pub fn save(&self) {
+        let mut save = SaveWriter::new();
+        save.field("crt", self.crt);
+        save.field("bloom", self.bloom);
+        save.field("color_grading", self.color_grading);
+        save.field("vignette", self.vignette);
+        save.field("transitions", self.transitions);
+        save.field("fullscreen", Self::fullscreen_name(self.fullscreen));
+        if let Err(err) = save.write_to(Path::new(Self::PATH)) {
+            eprintln!("could not save the settings: {err}");
+        }
+    }
+
+    /// Name of a fullscreen mode in the saved settings
+    fn fullscreen_name(mode: FullscreenMode) -> &'static str {
+        match mode {
+            FullscreenMode::Windowed => "windowed",
+            FullscreenMode::Borderless => "borderless",
+            FullscreenMode::Exclusive => "exclusive",
+        }
+    }
+
+    /// The fullscreen mode of a name in the saved settings, reading the older `true` and `false` too
+    fn parse_fullscreen(name: &str) -> Option<FullscreenMode> {
+        match name {
+            "windowed" | "false" => Some(FullscreenMode::Windowed),
+            "borderless" | "true" => Some(FullscreenMode::Borderless),
+            "exclusive" => Some(FullscreenMode::Exclusive),
+            _ => None,
+        }
+    }
+
+    /// Toggles a setting with the keys F1 to F5, or goes to the next fullscreen mode with F6,
+    /// returning whether the key changed one
+    pub fn key_down(&mut self, key: VirtualKeyCode) -> bool {
+        let setting = match key {
+            VirtualKeyCode::F1 => &mut self.crt,
+            VirtualKeyCode::F2 => &mut self.bloom,
+            VirtualKeyCode::F3 => &mut self.color_grading,
+            VirtualKeyCode::F4 => &mut self.vignette,
+            VirtualKeyCode::F5 => &mut self.transitions,
+            VirtualKeyCode::F6 => {
+                self.fullscreen = match self.fullscreen {
+                    FullscreenMode::Windowed => FullscreenMode::Borderless,
+                    FullscreenMode::Borderless => FullscreenMode::Exclusive,
+                    FullscreenMode::Exclusive => FullscreenMode::Windowed,
+                };
+                return true;
+            }
+            _ => return false,
+        };
+        *setting = !*setting;
+        true
+    }
+
+    /// The config of the window with the fullscreen chosen, the rest as in `config`
+    pub fn window_config(&self, config: &WindowConfig) -> WindowConfig {
+        WindowConfig {
+            fullscreen: self.fullscreen,
+            ..config.clone()
+        }
+    }
+
+    /// Enables the effects chosen, with the transition `progress` from 1 when the scene changed to 0 when it ended
+    pub fn apply(&self, post_process: &mut PostProcess, progress: f32) {
+        post_process.set_enabled("crt", self.crt);
+        post_process.set_enabled("bloom", self.bloom);
+        post_process.set_enabled("color_grading", self.color_grading);
+        post_process.set_enabled("vignette", self.vignette);
+        let transition = self.transitions && progress > 0.;
+        if let Some(pass) = post_process.pass_mut("pixelate") {
+            pass.enabled = transition;
+            pass.effect = PostEffect::Pixelate {
+                size: 1. + (Self::TRANSITION_PIXEL_SIZE - 1.) * progress * progress,
+            };
+        }
+    }
+}
@@ -0,0 +1,404 @@
+//! Statistics collected during a game of blocos, and export of them to CSV/JSON
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use super::{
+    board::TetraminoKind,
+    save::{SaveError, SaveReader, SaveWriter},
+};
+
+/// A snapshot of the stack taken after each placed piece
+#[derive(Debug, Clone, Copy)]
+pub struct TimelineEntry {
+    /// Number of the piece that generated this entry (starting at 1)
+    pub piece: u32,
+    /// Time since the start of the game, in microseconds
+    pub time: u128,
+    /// Height of the highest filled row of the stack
+    pub height: i16,
+    /// Number of empty cells with a block above them
+    pub holes: u32,
+}
+
+/// Statistics of a single game
+#[derive(Debug, Clone, Default)]
+pub struct Stats {
+    /// Time played, in microseconds
+    pub time: u128,
+    /// Number of pieces put in the stack
+    pub pieces_placed: u32,
+    /// Number of keys pressed that affect the piece
+    pub key_presses: u32,
+    /// Number of keys pressed since the current piece spawned
+    pub current_piece_presses: u32,
+    /// Number of pieces placed with more inputs than needed
+    pub finesse_faults: u32,
+    /// How many times each type of piece came, indexed by [TetraminoKind]
+    pub piece_counts: [u32; TetraminoKind::COUNT],
+    /// How many clears of 1, 2, 3 and 4 lines happened
+    pub clears: [u32; 4],
+    /// Total of lines cleared
+    pub lines: u32,
+    /// Number of consecutive pieces that cleared lines
+    pub combo: u32,
+    /// Biggest combo of the game
+    pub max_combo: u32,
+    /// Height and holes of the stack after each piece
+    pub timeline: Vec<TimelineEntry>,
+}
+
+impl Stats {
+    /// Name of each clear type, in the same order of [Stats::clears]
+    pub const CLEAR_NAMES: [&'static str; 4] = ["single", "double", "triple", "tetris"];
+
+    /// Create the statistics of a new game
+    pub fn new() -> Stats {
+        Stats::default()
+    }
+
+    /// Registers a key press for the current piece
+    pub fn key_pressed(&mut self) {
+        self.key_presses += 1;
+        self.current_piece_presses += 1;
+    }
+
+    /// Registers a new piece that came from the bag
+    pub fn piece_spawned(&mut self, kind: TetraminoKind) {
+        self.piece_counts[kind as usize] += 1;
+        self.current_piece_presses = 0;
+    }
+
+    /// Registers a piece put in the stack
+    ///
+    /// `minimal_inputs` is the fewest number of inputs that could place the piece in the same spot,
+    /// if the player used more than that, it counts as a finesse fault
+    pub fn piece_placed(&mut self, lines_cleared: u32, minimal_inputs: Option<u32>, height: i16, holes: u32) {
+        self.pieces_placed += 1;
+        if minimal_inputs.is_some_and(|min| self.current_piece_presses > min) {
+            self.finesse_faults += 1;
+        }
+        if lines_cleared > 0 {
+            self.clears[(lines_cleared.min(4) - 1) as usize] += 1;
+            self.lines += lines_cleared;
+            self.combo += 1;
+            self.max_combo = self.max_combo.max(self.combo);
+        } else {
+            self.combo = 0;
+        }
+        self.timeline.push(TimelineEntry {
+            piece: self.pieces_placed,
+            time: self.time,
+            height,
+            holes,
+        });
+    }
+
+    /// Registers a piece sent back to the spawn by the finesse trainer after a fault
+    pub fn piece_retried(&mut self) {
+        self.finesse_faults += 1;
+        self.current_piece_presses = 0;
+    }
+
+    /// Writes the statistics to a save, with the fields prefixed by `stats_`
+    pub fn save(&self, save: &mut SaveWriter) {
+        save.field("stats_time", self.time);
+        save.field("stats_pieces_placed", self.pieces_placed);
+        save.field("stats_key_presses", self.key_presses);
+        save.field("stats_current_piece_presses", self.current_piece_presses);
+        save.field("stats_finesse_faults", self.finesse_faults);
+        save.list("stats_piece_counts", self.piece_counts);
+        save.list("stats_clears", self.clears);
+        save.field("stats_lines", self.lines);
+        save.field("stats_combo", self.combo);
+        save.field("stats_max_combo", self.max_combo);
+        for e in &self.timeline {
+            save.list(
+                "stats_timeline",
+                [e.piece.to_string(), e.time.to_string(), e.height.to_string(), e.holes.to_string()],
+            );
+        }
+    }
+
+    /// Reads statistics written by [Stats::save]
+    pub fn load(save: &SaveReader) -> Result<Stats, SaveError> {
+        let invalid = |key: &str| SaveError::Invalid(key.to_owned());
+        let timeline = save
+            .repeated::<String>("stats_timeline")?
+            .into_iter()
+            .map(|values| match values.as_slice() {
+                [piece, time, height, holes] => Some(TimelineEntry {
+                    piece: piece.parse().ok()?,
+                    time: time.parse().ok()?,
+                    height: height.parse().ok()?,
+                    holes: holes.parse().ok()?,
+                }),
+                _ => None,
+            })
+            .collect::<Option<_>>()
+            .ok_or_else(|| invalid("stats_timeline"))?;
+        Ok(Stats {
+            time: save.field("stats_time")?,
+            pieces_placed: save.field("stats_pieces_placed")?,
+            key_presses: save.field("stats_key_presses")?,
+            current_piece_presses: save.field("stats_current_piece_presses")?,
+            finesse_faults: save.field("stats_finesse_faults")?,
+            piece_counts: save
+                .list("stats_piece_counts")?
+                .try_into()
+                .map_err(|_| invalid("stats_piece_counts"))?,
+            clears: save.list("stats_clears")?.try_into().map_err(|_| invalid("stats_clears"))?,
+            lines: save.field("stats_lines")?,
+            combo: save.field("stats_combo")?,
+            max_combo: save.field("stats_max_combo")?,
+            timeline,
+        })
+    }
+
+    /// Time played in seconds
+    pub fn seconds(&self) -> f32 {
+        self.time as f32 / 1_000_000.
+    }
+
+    /// Average of pieces placed per second
+    pub fn pieces_per_second(&self) -> f32 {
+        per(self.pieces_placed as f32, self.seconds())
+    }
+
+    /// Average of lines cleared per minute
+    pub fn lines_per_minute(&self) -> f32 {
+        per(self.lines as f32, self.seconds() / 60.)
+    }
+
+    /// Average of keys pressed for each piece placed
+    pub fn keys_per_piece(&self) -> f32 {
+        per(self.key_presses as f32, self.pieces_placed as f32)
+    }
+
+    /// Lines of text with the main statistics, to show during the game
+    pub fn summary(&self) -> Vec<String> {
+        vec![
+            format!("time: {:.1}s", self.seconds()),
+            format!("pieces: {}", self.pieces_placed),
+            format!("pps: {:.2}", self.pieces_per_second()),
+            format!("lines: {}", self.lines),
+            format!("combo: {}", self.combo),
+        ]
+    }
+
+    /// Lines of text with all the statistics, to show in the end of the game
+    pub fn report(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!("time: {:.1}s", self.seconds()),
+            format!("pieces: {}  pps: {:.2}", self.pieces_placed, self.pieces_per_second()),
+            format!("lines: {}  lpm: {:.1}", self.lines, self.lines_per_minute()),
+            format!("keys: {}  kpp: {:.2}", self.key_presses, self.keys_per_piece()),
+            format!("finesse faults: {}", self.finesse_faults),
+            format!("max combo: {}", self.max_combo),
+        ];
+        lines.push(
+            Self::CLEAR_NAMES
+                .iter()
+                .zip(self.clears)
+                .map(|(name, count)| format!("{name}: {count}"))
+                .collect::<Vec<_>>()
+                .join("  "),
+        );
+        lines.push(
+            TetraminoKind::ALL
+                .iter()
+                .map(|kind| format!("{}: {}", kind.name(), self.piece_counts[*kind as usize]))
+                .collect::<Vec<_>>()
+                .join("  "),
+        );
+        if let Some(last) = self.timeline.last() {
+            let max_height = self.timeline.iter().map(|e| e.height).max().unwrap_or(0);
+            lines.push(format!("height: {}  max height: {}  holes: {}", last.height, max_height, last.holes));
+        }
+        lines
+    }
+
+    /// Serializes the statistics as JSON
+    ///
+    /// The names of the pieces and of the clears are identifiers, so they are written without escaping
+    pub fn to_json(&self) -> String {
+        let pieces = TetraminoKind::ALL
+            .iter()
+            .map(|kind| format!("\"{}\": {}", kind.name(), self.piece_counts[*kind as usize]))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let clears = Self::CLEAR_NAMES
+            .iter()
+            .zip(self.clears)
+            .map(|(name, count)| format!("\"{name}\": {count}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let timeline = self
+            .timeline
+            .iter()
+            .map(|e| {
+                format!(
+                    "    {{\"piece\": {}, \"time\": {}, \"height\": {}, \"holes\": {}}}",
+                    e.piece, e.time, e.height, e.holes
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",\n");
+        format!(
+            "{{\n  \"time_us\": {},\n  \"pieces_placed\": {},\n  \"pieces_per_second\": {},\n  \"lines\": {},\n  \"lines_per_minute\": {},\n  \"key_presses\": {},\n  \"keys_per_piece\": {},\n  \"finesse_faults\": {},\n  \"max_combo\": {},\n  \"pieces\": {{{}}},\n  \"clears\": {{{}}},\n  \"timeline\": [\n{}\n  ]\n}}\n",
+            self.time,
+            self.pieces_placed,
+            self.pieces_per_second(),
+            self.lines,
+            self.lines_per_minute(),
+            self.key_presses,
+            self.keys_per_piece(),
+            self.finesse_faults,
+            self.max_combo,
+            pieces,
+            clears,
+            timeline
+        )
+    }
+
+    /// Serializes the statistics as CSV
+    ///
+    /// The first part has one `stat,value` row per statistic,
+    /// and after an empty line comes the timeline with one row per piece
+    pub fn to_csv(&self) -> String {
+        let mut rows = vec![
+            "stat,value".to_owned(),
+            format!("time_us,{}", self.time),
+            format!("pieces_placed,{}", self.pieces_placed),
+            format!("pieces_per_second,{}", self.pieces_per_second()),
+            format!("lines,{}", self.lines),
+            format!("lines_per_minute,{}", self.lines_per_minute()),
+            format!("key_presses,{}", self.key_presses),
+            format!("keys_per_piece,{}", self.keys_per_piece()),
+            format!("finesse_faults,{}", self.finesse_faults),
+            format!("max_combo,{}", self.max_combo),
+        ];
+        for kind in TetraminoKind::ALL {
+            rows.push(format!("piece_{},{}", kind.name(), self.piece_counts[kind as usize]));
+        }
+        for (name, count) in Self::CLEAR_NAMES.iter().zip(self.clears) {
+            rows.push(format!("clear_{name},{count}"));
+        }
+        rows.push(String::new());
+        rows.push("piece,time_us,height,holes".to_owned());
+        for e in &self.timeline {
+            rows.push(format!("{},{},{},{}", e.piece, e.time, e.height, e.holes));
+        }
+        rows.join("\n") + "\n"
+    }
+
+    /// Writes the statistics to `dir` as `stats-<timestamp>.csv` and `stats-<timestamp>.json`,
+    /// with the milliseconds since the Unix epoch so two exports in the same second do not overwrite each other
+    ///
+    /// Returns the path of the written files without extension
+    pub fn export(&self, dir: &Path) -> io::Result<PathBuf> {
+        fs::create_dir_all(dir)?;
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or(0);
+        let path = dir.join(format!("stats-{timestamp}"));
+        fs::write(path.with_extension("csv"), self.to_csv())?;
+        fs::write(path.with_extension("json"), self.to_json())?;
+        Ok(path)
+    }
+}
+
+/// Divides `value` by `total`, returning 0 when there is nothing to divide by
+fn per(value: f32, total: f32) -> f32 {
+    if total > 0. {
+        value / total
+    } else {
+        0.
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A game of a minute with a T placed with a finesse fault and an I that was retried and then cleared four lines
+    fn game() -> Stats {
+        let mut stats = Stats::new();
+        stats.piece_spawned(TetraminoKind::T);
+        for _ in 0..3 {
+            stats.key_pressed();
+        }
+        stats.time = 30_000_000;
+        stats.piece_placed(0, Some(2), 1, 0);
+        stats.piece_spawned(TetraminoKind::Straight);
+        stats.key_pressed();
+        stats.piece_retried();
+        stats.key_pressed();
+        stats.time = 60_000_000;
+        stats.piece_placed(4, Some(1), 0, 0);
+        stats
+    }
+
+    #[test]
+    fn counts_the_pieces_keys_and_faults() {
+        let stats = game();
+        assert_eq!(stats.pieces_placed, 2);
+        assert_eq!(stats.key_presses, 5);
+        assert_eq!(stats.current_piece_presses, 1);
+        assert_eq!(stats.finesse_faults, 2);
+        assert_eq!(stats.piece_counts, [0, 1, 0, 0, 1, 0, 0]);
+        assert_eq!(stats.clears, [0, 0, 0, 1]);
+        assert_eq!((stats.lines, stats.combo, stats.max_combo), (4, 1, 1));
+        assert_eq!(stats.lines_per_minute(), 4.);
+        assert_eq!(stats.keys_per_piece(), 2.5);
+    }
+
+    #[test]
+    fn reports_every_statistic() {
+        assert_eq!(
+            game().report(),
+            [
+                "time: 60.0s",
+                "pieces: 2  pps: 0.03",
+                "lines: 4  lpm: 4.0",
+                "keys: 5  kpp: 2.50",
+                "finesse faults: 2",
+                "max combo: 1",
+                "single: 0  double: 0  triple: 0  tetris: 1",
+                "O: 0  T: 1  L: 0  J: 0  I: 1  Z: 0  S: 0",
+                "height: 0  max height: 1  holes: 0",
+            ]
+        );
+    }
+
+    #[test]
+    fn exports_json() {
+        let expected = format!(
+            "{{\n  \"time_us\": 60000000,\n  \"pieces_placed\": 2,\n  \"pieces_per_second\": {},\n  \"lines\": 4,\n  \
+             \"lines_per_minute\": 4,\n  \"key_presses\": 5,\n  \"keys_per_piece\": 2.5,\n  \"finesse_faults\": 2,\n  \
+             \"max_combo\": 1,\n  \
+             \"pieces\": {{\"O\": 0, \"T\": 1, \"L\": 0, \"J\": 0, \"I\": 1, \"Z\": 0, \"S\": 0}},\n  \
+             \"clears\": {{\"single\": 0, \"double\": 0, \"triple\": 0, \"tetris\": 1}},\n  \
+             \"timeline\": [\n    {{\"piece\": 1, \"time\": 30000000, \"height\": 1, \"holes\": 0}},\n    \
+             {{\"piece\": 2, \"time\": 60000000, \"height\": 0, \"holes\": 0}}\n  ]\n}}\n",
+            2_f32 / 60.
+        );
+        assert_eq!(game().to_json(), expected);
+    }
+
+    #[test]
+    fn exports_csv() {
+        let expected = format!(
+            "stat,value\ntime_us,60000000\npieces_placed,2\npieces_per_second,{}\nlines,4\nlines_per_minute,4\n\
+             key_presses,5\nkeys_per_piece,2.5\nfinesse_faults,2\nmax_combo,1\n\
+             piece_O,0\npiece_T,1\npiece_L,0\npiece_J,0\npiece_I,1\npiece_Z,0\npiece_S,0\n\
+             clear_single,0\nclear_double,0\nclear_triple,0\nclear_tetris,1\n\
+             \npiece,time_us,height,holes\n1,30000000,1,0\n2,60000000,0,0\n",
+            2_f32 / 60.
+        );
+        assert_eq!(game().to_csv(), expected);
+    }
+}
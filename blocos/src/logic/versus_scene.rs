@@ -0,0 +1,343 @@
+//! Online 1v1 over TCP, where each instance simulates both boards in lockstep
+use glium::glutin::event::VirtualKeyCode;
+use rand::random;
+
+use crate::{
+    core::{rgb::Rgba, timestep::TICK_MICROS},
+    gui::{
+        atlas::Sprite,
+        fonts::FontId,
+        interface::Canvas,
+        systems::{BlendMode, TextLayout, TextObject},
+        Rect,
+    },
+    vector2::Vec2,
+};
+
+use super::{
+    layer,
+    board::{Board, Lock},
+    finesse::Input,
+    net::{Connection, Listener, Role, Session, Status, HASH_INTERVAL},
+    Scene,
+};
+
+/// Number of frames the garbage waits before entering the board of the opponent
+const GARBAGE_DELAY: u64 = 30;
+/// Position of the bottom left corner of each board in the world
+const BOARD_ORIGINS: [Vec2; 2] = [Vec2 { x: 0., y: 0. }, Vec2 { x: 90., y: 0. }];
+
+/// Connection phase of the versus
+#[derive(Debug)]
+enum Link {
+    /// No session was requested
+    Offline,
+    /// Waiting for the opponent to connect to the port
+    Waiting(Listener),
+    /// Connected, playing or after the end of the game
+    Connected(Box<Session>),
+    /// The connection could not be established
+    Failed(String),
+}
+
+/// Scene of the online versus
+#[derive(Debug)]
+pub struct VersusScene {
+    link: Link,
+    /// Boards of the host (0) and of the guest (1)
+    boards: [Board; 2],
+    /// Inputs done by the local player not sent yet
+    pending: Vec<Input>,
+    /// Garbage waiting to enter each board, with the frame it enters
+    incoming_garbage: [Vec<(u64, u32)>; 2],
+    /// The player that lost, when the game is over
+    loser: Option<usize>,
+    /// The block of the skin the boards are drawn with
+    block: Sprite,
+}
+
+impl VersusScene {
+    /// Creates the scene without a session, drawing the blocks with `block`
+    pub fn new(block: Sprite) -> VersusScene {
+        VersusScene {
+            link: Link::Offline,
+            boards: [Board::new(0), Board::new(0)],
+            pending: vec![],
+            incoming_garbage: [vec![], vec![]],
+            loser: None,
+            block,
+        }
+    }
+
+    /// Changes the block the boards are drawn with, when the skin is reloaded
+    pub fn set_block(&mut self, block: Sprite) {
+        self.block = block;
+    }
+
+    /// Starts hosting or joining a session
+    pub fn start(&mut self, role: Role) {
+        *self = VersusScene::new(self.block.clone());
+        self.link = match role {
+            Role::Host(port) => match Listener::bind(port) {
+                Ok(listener) => Link::Waiting(listener),
+                Err(err) => Link::Failed(format!("could not listen on port {port}: {err}")),
+            },
+            Role::Join(address) => match Connection::connect(address.as_str()) {
+                Ok(connection) => Link::Connected(Box::new(Session::new(connection, false, 0))),
+                Err(err) => Link::Failed(format!("could not connect to {address}: {err}")),
+            },
+        };
+    }
+
+    pub fn world_region(&self) -> Rect {
+        Rect {
+            center: vec2!(70_f32, 50.),
+            size: vec2!(360_f32, 360.),
+        }
+    }
+
+    /// Leaves the session, telling the opponent, and goes back to the scene without a session
+    pub fn leave(&mut self) {
+        if let Link::Connected(session) = &mut self.link {
+            session.leave();
+        }
+        *self = VersusScene::new(self.block.clone());
+    }
+
+    /// Receives the keypress event
+    ///
+    /// `Escape` leaves the session
+    pub fn key_down(&mut self, key: VirtualKeyCode) -> Scene {
+        if key == VirtualKeyCode::Escape {
+            self.leave();
+            return Scene::HomeScene;
+        }
+        if let Some(input) = Input::from_key(key) {
+            if self.loser.is_none() {
+                self.pending.push(input);
+            }
+        }
+        Scene::VersusScene
+    }
+
+    pub fn on_click(&mut self, _position: Vec2) -> Scene {
+        Scene::VersusScene
+    }
+
+    /// Advances the connection and a frame of the lockstep, called once per tick of the game
+    ///
+    /// It does not draw, so two scenes can be run in the same process, one hosting and other joining by loopback
+    pub fn update(&mut self) -> Scene {
+        if let Link::Waiting(listener) = &self.link {
+            self.link = match listener.accept() {
+                Ok(Some(connection)) => Link::Connected(Box::new(Session::new(connection, true, random()))),
+                Ok(None) => return Scene::VersusScene,
+                Err(err) => Link::Failed(format!("could not accept the opponent: {err}")),
+            };
+        }
+        if let Link::Connected(session) = &mut self.link {
+            let handshaking = session.status == Status::Handshake;
+            session.poll();
+            if handshaking && session.status == Status::Playing {
+                self.boards = [Board::new(session.seed), Board::new(session.seed)];
+            }
+        }
+        self.tick();
+        Scene::VersusScene
+    }
+
+    /// Sends the inputs of the local player and simulates the next frame, if the inputs of both players arrived
+    ///
+    /// A frame is one tick of the game, so the pieces fall [TICK_MICROS] in each one
+    fn tick(&mut self) {
+        let Link::Connected(session) = &mut self.link else {
+            return;
+        };
+        if self.loser.is_some() || !session.is_active() {
+            return;
+        }
+        session.push_inputs(&mut self.pending);
+        if let Some((frame, inputs)) = session.next_frame() {
+            let mut sent = [0; 2];
+            for (player, inputs) in inputs.into_iter().enumerate() {
+                let board = &mut self.boards[player];
+                // Every piece locked in the frame, several hard drops can arrive together
+                let mut locks = vec![];
+                for input in inputs {
+                    if board.apply_input(input) {
+                        let lock = board.lock();
+                        locks.push(lock);
+                        if lock == Lock::ToppedOut {
+                            break;
+                        }
+                    }
+                }
+                if locks.is_empty() && board.fall(TICK_MICROS) {
+                    locks.push(board.lock());
+                }
+                for lock in locks {
+                    match lock {
+                        Lock::ToppedOut => {
+                            self.loser.get_or_insert(player);
+                        }
+                        Lock::Placed { lines_cleared } => sent[player] += garbage_for(lines_cleared),
+                    }
+                }
+            }
+            for (player, &lines) in sent.iter().enumerate() {
+                if lines > 0 {
+                    self.incoming_garbage[1 - player].push((frame + GARBAGE_DELAY, lines));
+                }
+                let board = &mut self.boards[player];
+                self.incoming_garbage[player].retain(|&(at, lines)| {
+                    if at <= frame {
+                        board.add_garbage(lines);
+                    }
+                    at > frame
+                });
+            }
+            let (local, remote) = (session.local_player, session.remote_player());
+            if sent[local] > 0 {
+                session.send_garbage(frame, sent[local]);
+            }
+            session.check_garbage(frame, sent[remote]);
+            if frame.is_multiple_of(HASH_INTERVAL) {
+                session.record_hash(frame, self.boards[0].hash() ^ self.boards[1].hash().rotate_left(1));
+            }
+        }
+    }
+
+    /// Draws the boards and the state of the connection
+    pub fn draw(&self, canvas: &mut Canvas, alpha: f32) {
+        let mut lines = vec![];
+        match &self.link {
+            Link::Offline => lines.push("run with --host <port> or --join <address>".to_owned()),
+            Link::Waiting(listener) => match listener.local_addr() {
+                Ok(address) => lines.push(format!("waiting for the opponent on {address}")),
+                Err(_) => lines.push("waiting for the opponent".to_owned()),
+            },
+            Link::Failed(reason) => lines.push(reason.clone()),
+            Link::Connected(session) => {
+                for (player, origin) in BOARD_ORIGINS.into_iter().enumerate() {
+                    self.boards[player].draw(canvas, origin, alpha, &self.block);
+                    let name = if player == session.local_player { "you" } else { "opponent" };
+                    canvas.draw(TextObject {
+                        color: Rgba::WHITE,
+                        font_size: 4.,
+                        font: FontId::DEFAULT,
+                        layer: layer::HUD,
+                        blend: BlendMode::Alpha,
+                        position: origin + vec2!(0_f32, 110.),
+                        text: format!("{name}: {}", self.boards[player].score),
+                        layout: TextLayout::default(),
+                    });
+                }
+                match &session.status {
+                    Status::Handshake => lines.push("connecting...".to_owned()),
+                    Status::Playing => {}
+                    Status::Desync { frame } => lines.push(format!("desync detected at frame {frame}")),
+                    Status::Disconnected(reason) => lines.push(format!("disconnected: {reason}")),
+                }
+                if let Some(latency) = session.latency {
+                    lines.push(format!("ping: {} ms", latency.as_millis()));
+                }
+                if let Some(loser) = self.loser {
+                    lines.push(if loser == session.local_player { "you lost" } else { "you won" }.to_owned());
+                }
+            }
+        }
+        lines.push("Esc: leave".to_owned());
+        canvas.draw(TextObject {
+            color: Rgba::WHITE,
+            font_size: 4.,
+            font: FontId::DEFAULT,
+            layer: layer::HUD,
+            blend: BlendMode::Alpha,
+            position: vec2!(-15_f32, -10.),
+            text: lines.join("\n"),
+            layout: TextLayout {
+                max_width: Some(80.),
+                line_spacing: 1.5,
+                ..Default::default()
+            },
+        });
+    }
+}
+
+/// Lines of garbage sent by a piece that cleared `lines_cleared` lines
+fn garbage_for(lines_cleared: u32) -> u32 {
+    match lines_cleared {
+        0 | 1 => 0,
+        2 => 1,
+        3 => 2,
+        _ => 4,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        rc::Rc,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use glium::texture::{MipmapsOption, SrgbFormat, SrgbTexture2d};
+
+    use super::*;
+    use crate::gui::backend::Backend;
+
+    /// The session of a scene that connected
+    fn session(scene: &VersusScene) -> Option<&Session> {
+        match &scene.link {
+            Link::Connected(session) => Some(session),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn two_scenes_in_one_process_play_in_lockstep() {
+        let backend = Backend::headless(1, 1).unwrap();
+        let texture =
+            SrgbTexture2d::empty_with_format(&backend, SrgbFormat::U8U8U8U8, MipmapsOption::NoMipmap, 1, 1).unwrap();
+        let block = Sprite { texture: Rc::new(texture), source: Rect::UNIT };
+        let mut host = VersusScene::new(block.clone());
+        let mut guest = VersusScene::new(block);
+
+        host.start(Role::Host(0));
+        let Link::Waiting(listener) = &host.link else {
+            panic!("could not listen on loopback");
+        };
+        let port = listener.local_addr().unwrap().port();
+        guest.start(Role::Join(format!("127.0.0.1:{port}")));
+
+        host.key_down(VirtualKeyCode::Space);
+        guest.key_down(VirtualKeyCode::Left);
+        guest.key_down(VirtualKeyCode::Space);
+        let start = Instant::now();
+        let frame = |scene: &VersusScene| session(scene).map_or(0, Session::frame);
+        // Long enough for the boards to be compared by hash a few times, ending with both in the same frame
+        let frames = 3 * HASH_INTERVAL;
+        while frame(&host) < frames || frame(&host) != frame(&guest) {
+            assert!(start.elapsed() < Duration::from_secs(10), "the scenes did not advance together");
+            // The one behind advances, so they meet in the same frame
+            let (host_frame, guest_frame) = (frame(&host), frame(&guest));
+            if host_frame <= guest_frame {
+                host.update();
+            }
+            if guest_frame <= host_frame {
+                guest.update();
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        for scene in [&host, &guest] {
+            assert_eq!(session(scene).unwrap().status, Status::Playing);
+        }
+        for player in 0..2 {
+            assert_eq!(host.boards[player].hash(), guest.boards[player].hash());
+        }
+        let seed = session(&host).unwrap().seed;
+        assert_ne!(host.boards[1].hash(), Board::new(seed).hash(), "the inputs of the guest were not applied");
+    }
+}
@@ -11,37 +11,59 @@ pub mod logic;
 
 extern crate glium;
 use glium::{
-    glutin::{event, event::{KeyboardInput, ElementState, VirtualKeyCode, MouseButton}, event_loop, dpi::PhysicalPosition},
-    Surface,
+    glutin::{event, event::{KeyboardInput, ElementState, VirtualKeyCode, MouseButton}, event_loop, dpi::PhysicalSize},
 };
-use std::{time, cmp::{min, max}};
+use std::{io, path::{Path, PathBuf}, thread::JoinHandle, time};
 
 pub use crate::core::vector2;
-use crate::{gui::interface, logic::GameState, vector2::{Vec2, ToVec2}};
+use crate::{
+    core::{rgb::Rgba, timestep::{FixedTimestep, FrameLimit, TICK_MICROS}},
+    gui::{assets::Assets, capture::Recorder, debug::DebugOverlay, interface::{self, Interface}, window::WindowConfig, GuiError},
+    logic::{GameState, net::Role},
+    vector2::Vec2,
+};
+
+/// Directory where the screenshots and the clips are saved
+const CAPTURE_DIR: &str = "captures";
+/// Shown when a change of the window, like the vsync or the anti-aliasing, only applies after restarting
+const RESTART_NOTICE: &str = "restart the game to apply the new video settings";
 
 fn main() {
-    const TARGET_FPS: u64 = 120;
+    const DEFAULT_FRAME_LIMIT: FrameLimit = FrameLimit::Fps(120);
+    let frame_limit = FrameLimit::from_args(std::env::args()).unwrap_or(DEFAULT_FRAME_LIMIT);
     let event_loop = event_loop::EventLoop::new();
-    let mut facade = interface::Interface::create(&event_loop);
-    let mut game_state = GameState::new(&facade);
-    let mut last_update =   time::Instant::now();
+    let window_config = WindowConfig {
+        min_size: Some((320, 240)),
+        resizable: true,
+        vsync: frame_limit == FrameLimit::Vsync,
+        samples: 4,
+        icon: Some(Assets::default_dir().join("brick.png")),
+        ..WindowConfig::default()
+    };
+    let mut facade = interface::Interface::create(&event_loop, window_config).unwrap_or_else(|err| exit_with(err));
+    let mut game_state = GameState::new(&facade).unwrap_or_else(|err| exit_with(err));
+    if facade.set_window_config(game_state.window_config(facade.window_config())) {
+        game_state.show_notice(RESTART_NOTICE);
+    }
+    if let Some(role) = Role::from_args(std::env::args()) {
+        game_state.start_versus(role);
+    }
+    let mut timestep = FixedTimestep::new();
+    let mut next_frame = time::Instant::now();
+    let mut last_frame = time::Instant::now();
     let mut last_key : Option<VirtualKeyCode> = None;  
     let mut mouse_position = Vec2::ZERO;
+    let mut saving: Vec<Saving> = Vec::new();
+    let mut debug_overlay = DebugOverlay::new();
+    let mut screenshot_pending = false;
+    let mut last_error: Option<String> = None;
     
     event_loop.run(move |ev, _, control_flow| {
-        let start_time = time::Instant::now();
-        match &control_flow {
-            event_loop::ControlFlow::WaitUntil(_) => {},
-             _ => {
-                *control_flow = event_loop::ControlFlow::WaitUntil(start_time);
-             }
-        };
-
         if let event::Event::WindowEvent { event, .. } = ev {
             match event {
                 event::WindowEvent::CursorMoved { position, .. } => {
-                    let scale_factor=facade.display.gl_window().window().scale_factor();
-                    let size = &facade.display.gl_window().window().inner_size();
+                    let scale_factor=facade.backend.scale_factor();
+                    let size = PhysicalSize::<u32>::from(facade.backend.dimensions());
                     let (x,y) : (f32, f32) = position.to_logical::<f64>(scale_factor).into();
                     let (w,h) : (f32, f32)= size.to_logical::<f64>(scale_factor).into();
                     mouse_position = vec2!(x/w-0.5, -y/h + 0.5) * 2.;
@@ -51,10 +73,12 @@ fn main() {
                     game_state.on_click(facade.camera.target_to_world(mouse_position));
                 }
                 event::WindowEvent::CloseRequested => {
+                    game_state.on_exit();
+                    exit_captures(&mut facade, &mut saving);
                     *control_flow = event_loop::ControlFlow::Exit
                 }
                 event::WindowEvent::Resized(window_size) => {
-                    facade.camera.target.size.x = (window_size.height as f32) / (window_size.width as f32);
+                    facade.resize(window_size.width, window_size.height);
                 }
                 event::WindowEvent::KeyboardInput {
                     input:
@@ -68,7 +92,17 @@ fn main() {
                     match state {
                         ElementState::Pressed => {
                             if last_key.is_none() || last_key.is_some_and(|k| k != input) {
-                                game_state.key_down(input);
+                                match input {
+                                    VirtualKeyCode::F12 => {
+                                        facade.capture_next_frame();
+                                        screenshot_pending = true;
+                                    }
+                                    VirtualKeyCode::F11 => {
+                                        saving.extend(toggle_recording(&mut facade).map(|thread| ("clip", thread)))
+                                    }
+                                    VirtualKeyCode::Grave => debug_overlay.toggle(),
+                                    _ => game_state.key_down(input),
+                                }
                             }
                             last_key = Some(input);
                         },
@@ -80,32 +114,126 @@ fn main() {
                 },
                 _ => (),
             }
-        }  else {
-            match ev {
-                event::Event::MainEventsCleared => {
-
-                },
-                event::Event::NewEvents(event::StartCause::ResumeTimeReached { .. } ) => {
-                    let delta_t = time::Instant::now().duration_since(last_update).as_micros();
-                    last_update = start_time;
-                    facade.camera.world = game_state.world_region();
-                    let mut canvas = facade.draw();
-                    canvas.target.clear_color(0.0, 0.0, 0.0, 1.0);
-                    game_state.update(&mut canvas, delta_t);
-                    canvas.target.finish().unwrap();
-            
-                    let elapsed_time = time::Instant::now().duration_since(start_time).as_millis() as u64;
-                    let wait_millis = max(1000 / TARGET_FPS, elapsed_time) - elapsed_time;
-                    let new_inst = start_time + time::Duration::from_millis(wait_millis);
-                    *control_flow = event_loop::ControlFlow::WaitUntil(new_inst);
-                },
-                event::Event::RedrawEventsCleared => {
+        }  else if let event::Event::MainEventsCleared = ev {
+            if *control_flow == event_loop::ControlFlow::Exit {
+                return;
+            }
+            for capture in extract_finished(&mut saving) {
+                report_saved(capture);
+            }
+            let now = time::Instant::now();
+            if now < next_frame {
+                *control_flow = event_loop::ControlFlow::WaitUntil(next_frame);
+                return;
+            }
+            let ticks = timestep.advance(now);
+            for _ in 0..ticks {
+                game_state.tick(TICK_MICROS);
+            }
 
-                },
-                _ => {}
+            let changed = facade.reload_assets();
+            if !changed.is_empty() {
+                game_state.reload_assets(&facade, &changed);
             }
+            facade.camera.goal = game_state.world_region();
+            facade.camera.add_trauma(game_state.take_trauma());
+            let delta = now.duration_since(last_frame).as_secs_f32();
+            facade.update_cameras(delta);
+            debug_overlay.record_frame(now, delta, ticks);
+            last_frame = now;
+            game_state.apply_effects(&mut facade.post_process);
+            if facade.set_window_config(game_state.window_config(facade.window_config())) {
+                game_state.show_notice(RESTART_NOTICE);
+            }
+            let mut canvas = facade.draw();
+            canvas.clear(Rgba::BLACK);
+            game_state.draw(&mut canvas, timestep.alpha());
+            if debug_overlay.visible {
+                let mouse = canvas.interface.camera.target_to_world(mouse_position);
+                debug_overlay.draw(&mut canvas, &game_state.debug_info(mouse));
+            }
+            match canvas.finish() {
+                Ok(()) => last_error = None,
+                Err(err) if err.is_fatal() => {
+                    eprintln!("{err}");
+                    game_state.on_exit();
+                    exit_captures(&mut facade, &mut saving);
+                    *control_flow = event_loop::ControlFlow::Exit;
+                    return;
+                }
+                Err(err) => {
+                    // The same error usually repeats in the next frames, it is written once until a frame works
+                    let message = err.to_string();
+                    if last_error.as_ref() != Some(&message) {
+                        eprintln!("skipping the frame: {message}");
+                        last_error = Some(message);
+                    }
+                }
+            }
+            if std::mem::take(&mut screenshot_pending) {
+                match facade.screenshot(Path::new(CAPTURE_DIR)) {
+                    Some(thread) => saving.push(("screenshot", thread)),
+                    None => eprintln!("could not save the screenshot: could not read the frame"),
+                }
+            }
+
+            *control_flow = match frame_limit.frame_time() {
+                Some(frame_time) => {
+                    // Schedules from the previous deadline to keep a steady rate,
+                    // unless the frame took so long that the deadline is already in the past
+                    next_frame = (next_frame + frame_time).max(now);
+                    event_loop::ControlFlow::WaitUntil(next_frame)
+                }
+                None => event_loop::ControlFlow::Poll,
+            };
         }
+    });
+}
 
+/// Starts recording the last seconds of the game, or stops and saves the clip in [CAPTURE_DIR]
+///
+/// Returns the thread saving the clip
+fn toggle_recording(facade: &mut Interface) -> Option<JoinHandle<io::Result<PathBuf>>> {
+    const CLIP_FPS: u32 = 15;
+    const CLIP_SECONDS: f32 = 10.;
+    if facade.is_recording() {
+        facade.stop_recording(Path::new(CAPTURE_DIR))
+    } else {
+        println!("recording, press F11 again to save the last {CLIP_SECONDS} seconds");
+        facade.start_recording(Recorder::new(CLIP_FPS, CLIP_SECONDS));
+        None
+    }
+}
 
-    });
-}
\ No newline at end of file
+/// A capture being saved in another thread, with what it is
+type Saving = (&'static str, JoinHandle<io::Result<PathBuf>>);
+
+/// Shows where a capture that finished saving was saved, or why it was not
+fn report_saved((what, thread): Saving) {
+    match thread.join() {
+        Ok(Ok(path)) => println!("{what} saved to {}", path.display()),
+        Ok(Err(err)) => eprintln!("could not save the {what}: {err}"),
+        Err(_) => eprintln!("could not save the {what}: the thread panicked"),
+    }
+}
+
+/// Saves the clip being recorded and waits for every capture to be saved, before the game exits
+fn exit_captures(facade: &mut Interface, saving: &mut Vec<Saving>) {
+    saving.extend(facade.stop_recording(Path::new(CAPTURE_DIR)).map(|thread| ("clip", thread)));
+    for capture in saving.drain(..) {
+        report_saved(capture);
+    }
+}
+
+/// Shows why the game could not start and exits
+fn exit_with(err: GuiError) -> ! {
+    eprintln!("{err}");
+    std::process::exit(1)
+}
+
+/// Removes from `threads` the ones that finished
+fn extract_finished(threads: &mut Vec<Saving>) -> Vec<Saving> {
+    let (finished, running) = threads.drain(..).partition(|(_, thread)| thread.is_finished());
+    *threads = running;
+    finished
+}